@@ -11,13 +11,59 @@ const COMMANDS: &[&str] = &[
     "niri_request",
     "get_upower_properties",
     "get_networkmanager_state",
-    "get_systray_items"
+    "get_systray_items",
+    "resync_systray_items",
+    "get_keyboard_layouts",
+    "switch_keyboard_layout",
+    "get_displays",
+    "apply_display_settings",
+    "confirm_display_settings",
+    "revert_display_settings",
+    "get_compositor_kind",
+    "get_workspaces",
+    "get_windows",
+    "focus_workspace",
+    "focus_window",
+    "get_upower_devices",
+    "get_battery_history",
+    "get_battery_attention",
+    "get_power_profiles",
+    "set_power_profile",
+    "get_power_supply_state",
+    "get_charge_thresholds",
+    "set_charge_thresholds",
+    "apply_charge_threshold_preset",
+    "get_wifi_devices",
+    "request_wifi_scan",
+    "connect_wifi",
+    "disconnect_wifi",
+    "get_network_devices",
+    "get_saved_connections",
+    "activate_saved_connection",
+    "deactivate_saved_connection",
+    "answer_secret_prompt",
+    "get_radio_state",
+    "set_wireless_enabled",
+    "set_wwan_enabled",
+    "set_networking_enabled",
+    "set_airplane_mode",
+    "get_network_throughput",
+    "get_hotspot",
+    "start_hotspot",
+    "stop_hotspot",
+    "activate_tray_item",
+    "scroll_tray_item",
+    "tray_menu_opened",
+    "tray_menu_closed",
+    "set_symbolic_colors"
 ];
 
 fn main() {
     tauri_plugin::Builder::new(COMMANDS).build();
 
-    // Generate Typescript definitions for niri_ipc types with schemars and json-schema-to-typescript
+    // Generate Typescript definitions for niri_ipc types with schemars and json-schema-to-typescript.
+    // These are only used by the niri-specific `niri_request` passthrough; the compositor-agnostic
+    // types in `compositor` are exported with ts-rs like everything else.
 
     // Never rerun unless build.rs changes
     println!("cargo:rerun-if-changed=build.rs");
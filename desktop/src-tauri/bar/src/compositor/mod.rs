@@ -0,0 +1,189 @@
+use std::{env, path::PathBuf, sync::Mutex, thread};
+
+use niri_ipc::socket::SOCKET_PATH_ENV;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+
+use crate::{
+    displays::{Display, DisplaySettings},
+    keyboard_layout::{KeyboardLayoutState, KeyboardLayoutTarget},
+    BarHandler,
+};
+
+mod niri;
+mod sway;
+
+pub use niri::NiriCompositor;
+pub use sway::SwayCompositor;
+
+pub type CompositorResult<T> = Result<T, String>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Compositor.ts")]
+pub enum CompositorKind {
+    Niri,
+    Sway,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Compositor.ts")]
+pub struct CompositorWorkspace {
+    /// A unique identifier for this workspace. Only stable for the lifetime of the workspace.
+    pub id: u64,
+    /// The index or number of the workspace on its output, as shown to the user.
+    pub idx: u32,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    /// Whether this workspace is the visible one on its output.
+    pub is_active: bool,
+    /// Whether this workspace has keyboard focus.
+    pub is_focused: bool,
+    pub is_urgent: bool,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Compositor.ts")]
+pub struct CompositorWindow {
+    /// A unique identifier for this window. Only stable for the lifetime of the window.
+    pub id: u64,
+    pub title: Option<String>,
+    /// The Wayland app ID, or the X11 class for XWayland windows.
+    pub app_id: Option<String>,
+    pub workspace_id: Option<u64>,
+    pub is_focused: bool,
+    pub is_urgent: bool,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Compositor.ts")]
+pub enum CompositorEvent {
+    /// The full list of workspaces changed.
+    WorkspacesChanged { workspaces: Vec<CompositorWorkspace> },
+    /// The full list of windows changed.
+    WindowsChanged { windows: Vec<CompositorWindow> },
+    /// A window was opened or one of its properties changed.
+    WindowOpenedOrChanged { window: CompositorWindow },
+    WindowClosed { id: u64 },
+    WindowFocusChanged { id: Option<u64> },
+    /// The configured keyboard layouts changed.
+    KeyboardLayoutsChanged { names: Vec<String>, current_idx: u8 },
+    KeyboardLayoutSwitched { idx: u8 },
+}
+
+/// A connection to a running Wayland compositor.
+///
+/// Requests are blocking and should be short-lived; events are read from a separate connection
+/// opened with `event_stream`, since both niri and sway dedicate a socket to the event stream.
+pub trait Compositor: Send {
+    fn kind(&self) -> CompositorKind;
+
+    fn workspaces(&mut self) -> CompositorResult<Vec<CompositorWorkspace>>;
+    fn windows(&mut self) -> CompositorResult<Vec<CompositorWindow>>;
+    fn focused_window(&mut self) -> CompositorResult<Option<CompositorWindow>> {
+        Ok(self.windows()?.into_iter().find(|window| window.is_focused))
+    }
+    fn outputs(&mut self) -> CompositorResult<Vec<Display>>;
+    /// Returns the names of the configured keyboard layouts and the index of the active one.
+    fn keyboard_layouts(&mut self) -> CompositorResult<(Vec<String>, u8)>;
+
+    fn focus_workspace(&mut self, id: u64) -> CompositorResult<()>;
+    fn focus_window(&mut self, id: u64) -> CompositorResult<()>;
+    fn switch_keyboard_layout(&mut self, target: KeyboardLayoutTarget) -> CompositorResult<()>;
+    fn configure_output(&mut self, output: &str, settings: &DisplaySettings) -> CompositorResult<()>;
+
+    /// Opens a new connection subscribed to compositor events.
+    /// The returned function blocks until the next event arrives.
+    fn event_stream(&self) -> CompositorResult<Box<dyn FnMut() -> CompositorResult<CompositorEvent> + Send>>;
+}
+
+/// Detects the running compositor from the environment and connects to it.
+pub fn connect() -> CompositorResult<Box<dyn Compositor>> {
+    if let Some(path) = env::var_os(SOCKET_PATH_ENV) {
+        return Ok(Box::new(NiriCompositor::connect_to(PathBuf::from(path))?));
+    }
+    if let Some(path) = env::var_os("SWAYSOCK").or_else(|| env::var_os("I3SOCK")) {
+        return Ok(Box::new(SwayCompositor::connect_to(PathBuf::from(path))?));
+    }
+    Err("No supported compositor found; neither NIRI_SOCKET nor SWAYSOCK is set".to_string())
+}
+
+impl BarHandler {
+    pub fn start_compositor_event_thread<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(KeyboardLayoutState::new(self.config.keyboard_layout.clone())));
+
+        let Some(compositor) = &self.compositor else {
+            return;
+        };
+
+        let mut next_event = match compositor.event_stream() {
+            Ok(next_event) => next_event,
+            Err(e) => {
+                eprintln!("Failed to start compositor event stream: {}", e);
+                return;
+            }
+        };
+
+        let app_handle = app_handle.clone();
+        thread::spawn(move || {
+            let mut action_connection = None;
+            loop {
+                match next_event() {
+                    Ok(event) => {
+                        Self::handle_keyboard_layout_event(&app_handle, &event, &mut action_connection);
+                        let _ = app_handle.emit("compositor_event", event);
+                    }
+                    Err(e) => {
+                        eprintln!("Compositor event stream error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_compositor_kind(
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<Option<CompositorKind>, ()> {
+    Ok(handler.lock().unwrap().compositor.as_ref().map(|compositor| compositor.kind()))
+}
+
+#[tauri::command]
+pub(crate) async fn get_workspaces(
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<Vec<CompositorWorkspace>, String> {
+    let mut handler = handler.lock().unwrap();
+    let compositor = handler.compositor.as_mut().ok_or("No compositor connection")?;
+    compositor.workspaces()
+}
+
+#[tauri::command]
+pub(crate) async fn get_windows(
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<Vec<CompositorWindow>, String> {
+    let mut handler = handler.lock().unwrap();
+    let compositor = handler.compositor.as_mut().ok_or("No compositor connection")?;
+    compositor.windows()
+}
+
+#[tauri::command]
+pub(crate) async fn focus_workspace(
+    payload: u64,
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<(), String> {
+    let mut handler = handler.lock().unwrap();
+    let compositor = handler.compositor.as_mut().ok_or("No compositor connection")?;
+    compositor.focus_workspace(payload)
+}
+
+#[tauri::command]
+pub(crate) async fn focus_window(
+    payload: u64,
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<(), String> {
+    let mut handler = handler.lock().unwrap();
+    let compositor = handler.compositor.as_mut().ok_or("No compositor connection")?;
+    compositor.focus_window(payload)
+}
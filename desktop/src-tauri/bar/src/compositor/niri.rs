@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use niri_ipc::{
+    Action, ConfiguredMode, ConfiguredPosition, Event, LayoutSwitchTarget, ModeToSet,
+    Output, OutputAction, PositionToSet, Reply, Request, Response, ScaleToSet, Transform, VrrToSet, Window,
+    Workspace, WorkspaceReferenceArg,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    compositor::{Compositor, CompositorEvent, CompositorKind, CompositorResult, CompositorWindow, CompositorWorkspace},
+    displays::{Display, DisplayMode, DisplaySettings, DisplayTransform},
+    keyboard_layout::KeyboardLayoutTarget,
+};
+
+/// niri's IPC sends one JSON request per line, and answers each with one line holding a `Reply`.
+/// After an event stream request, every following line is an `Event`.
+fn write_request(stream: &mut BufReader<UnixStream>, request: &Request) -> io::Result<()> {
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.get_mut().write_all(line.as_bytes())
+}
+
+fn read_line<T: DeserializeOwned>(stream: &mut BufReader<UnixStream>) -> io::Result<T> {
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Niri closed the socket"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn send_request(stream: &mut BufReader<UnixStream>, request: &Request) -> CompositorResult<Response> {
+    write_request(stream, request).map_err(|e| format!("Failed to send niri message: {}", e))?;
+    read_line::<Reply>(stream)
+        .map_err(|e| format!("Failed to read niri reply: {}", e))?
+        .map_err(|e| format!("Niri returned an error: {}", e))
+}
+
+pub struct NiriCompositor {
+    path: PathBuf,
+    stream: BufReader<UnixStream>,
+}
+
+impl NiriCompositor {
+    /// Connects to the niri IPC socket at the given path.
+    pub fn connect_to(path: PathBuf) -> CompositorResult<Self> {
+        let stream = UnixStream::connect(&path)
+            .map_err(|e| format!("Failed to connect to Niri IPC at {}: {}", path.display(), e))?;
+        Ok(Self { path, stream: BufReader::new(stream) })
+    }
+
+    fn send(&mut self, request: Request) -> CompositorResult<Response> {
+        send_request(&mut self.stream, &request)
+    }
+
+    fn action(&mut self, action: Action) -> CompositorResult<()> {
+        self.send(Request::Action(action)).map(|_| ())
+    }
+}
+
+impl From<Workspace> for CompositorWorkspace {
+    fn from(workspace: Workspace) -> Self {
+        CompositorWorkspace {
+            id: workspace.id,
+            idx: workspace.idx as u32,
+            name: workspace.name,
+            output: workspace.output,
+            is_active: workspace.is_active,
+            is_focused: workspace.is_focused,
+            is_urgent: workspace.is_urgent,
+        }
+    }
+}
+
+impl From<Window> for CompositorWindow {
+    fn from(window: Window) -> Self {
+        CompositorWindow {
+            id: window.id,
+            title: window.title,
+            app_id: window.app_id,
+            workspace_id: window.workspace_id,
+            is_focused: window.is_focused,
+            is_urgent: window.is_urgent,
+        }
+    }
+}
+
+impl From<Transform> for DisplayTransform {
+    fn from(transform: Transform) -> Self {
+        match transform {
+            Transform::Normal => DisplayTransform::Normal,
+            Transform::_90 => DisplayTransform::Rotate90,
+            Transform::_180 => DisplayTransform::Rotate180,
+            Transform::_270 => DisplayTransform::Rotate270,
+            Transform::Flipped => DisplayTransform::Flipped,
+            Transform::Flipped90 => DisplayTransform::Flipped90,
+            Transform::Flipped180 => DisplayTransform::Flipped180,
+            Transform::Flipped270 => DisplayTransform::Flipped270,
+        }
+    }
+}
+
+impl From<DisplayTransform> for Transform {
+    fn from(transform: DisplayTransform) -> Self {
+        match transform {
+            DisplayTransform::Normal => Transform::Normal,
+            DisplayTransform::Rotate90 => Transform::_90,
+            DisplayTransform::Rotate180 => Transform::_180,
+            DisplayTransform::Rotate270 => Transform::_270,
+            DisplayTransform::Flipped => Transform::Flipped,
+            DisplayTransform::Flipped90 => Transform::Flipped90,
+            DisplayTransform::Flipped180 => Transform::Flipped180,
+            DisplayTransform::Flipped270 => Transform::Flipped270,
+        }
+    }
+}
+
+impl From<Output> for Display {
+    fn from(output: Output) -> Self {
+        let logical = output.logical.as_ref();
+        Display {
+            name: output.name,
+            make: output.make,
+            model: output.model,
+            serial: output.serial,
+            physical_size: output.physical_size,
+            modes: output.modes.iter().map(|mode| DisplayMode {
+                width: mode.width,
+                height: mode.height,
+                refresh_rate: mode.refresh_rate,
+                is_preferred: mode.is_preferred,
+            }).collect(),
+            current_mode: output.current_mode,
+            enabled: logical.is_some(),
+            scale: logical.map_or(1.0, |l| l.scale),
+            transform: logical.map_or(DisplayTransform::Normal, |l| l.transform.into()),
+            position: logical.map_or((0, 0), |l| (l.x, l.y)),
+            vrr_supported: output.vrr_supported,
+            vrr_enabled: output.vrr_enabled,
+        }
+    }
+}
+
+impl Compositor for NiriCompositor {
+    fn kind(&self) -> CompositorKind {
+        CompositorKind::Niri
+    }
+
+    fn workspaces(&mut self) -> CompositorResult<Vec<CompositorWorkspace>> {
+        match self.send(Request::Workspaces)? {
+            Response::Workspaces(workspaces) => Ok(workspaces.into_iter().map(Into::into).collect()),
+            response => Err(format!("Unexpected response to workspaces request: {:?}", response)),
+        }
+    }
+
+    fn windows(&mut self) -> CompositorResult<Vec<CompositorWindow>> {
+        match self.send(Request::Windows)? {
+            Response::Windows(windows) => Ok(windows.into_iter().map(Into::into).collect()),
+            response => Err(format!("Unexpected response to windows request: {:?}", response)),
+        }
+    }
+
+    fn focused_window(&mut self) -> CompositorResult<Option<CompositorWindow>> {
+        match self.send(Request::FocusedWindow)? {
+            Response::FocusedWindow(window) => Ok(window.map(Into::into)),
+            response => Err(format!("Unexpected response to focused window request: {:?}", response)),
+        }
+    }
+
+    fn outputs(&mut self) -> CompositorResult<Vec<Display>> {
+        match self.send(Request::Outputs)? {
+            Response::Outputs(outputs) => {
+                let mut displays = outputs.into_values().map(Display::from).collect::<Vec<_>>();
+                displays.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(displays)
+            }
+            response => Err(format!("Unexpected response to outputs request: {:?}", response)),
+        }
+    }
+
+    fn keyboard_layouts(&mut self) -> CompositorResult<(Vec<String>, u8)> {
+        match self.send(Request::KeyboardLayouts)? {
+            Response::KeyboardLayouts(layouts) => Ok((layouts.names, layouts.current_idx)),
+            response => Err(format!("Unexpected response to keyboard layouts request: {:?}", response)),
+        }
+    }
+
+    fn focus_workspace(&mut self, id: u64) -> CompositorResult<()> {
+        self.action(Action::FocusWorkspace { reference: WorkspaceReferenceArg::Id(id) })
+    }
+
+    fn focus_window(&mut self, id: u64) -> CompositorResult<()> {
+        self.action(Action::FocusWindow { id })
+    }
+
+    fn switch_keyboard_layout(&mut self, target: KeyboardLayoutTarget) -> CompositorResult<()> {
+        let layout = match target {
+            KeyboardLayoutTarget::Next => LayoutSwitchTarget::Next,
+            KeyboardLayoutTarget::Prev => LayoutSwitchTarget::Prev,
+            KeyboardLayoutTarget::Index(idx) => LayoutSwitchTarget::Index(idx),
+        };
+        self.action(Action::SwitchLayout { layout })
+    }
+
+    fn configure_output(&mut self, output: &str, settings: &DisplaySettings) -> CompositorResult<()> {
+        let actions = if settings.enabled {
+            vec![
+                OutputAction::On,
+                OutputAction::Mode {
+                    mode: match settings.mode {
+                        Some(mode) => ModeToSet::Specific(ConfiguredMode {
+                            width: mode.width,
+                            height: mode.height,
+                            refresh: Some(mode.refresh_rate as f64 / 1000.0),
+                        }),
+                        None => ModeToSet::Automatic,
+                    },
+                },
+                OutputAction::Scale { scale: ScaleToSet::Specific(settings.scale) },
+                OutputAction::Transform { transform: settings.transform.into() },
+                OutputAction::Position {
+                    position: PositionToSet::Specific(ConfiguredPosition {
+                        x: settings.position.0,
+                        y: settings.position.1,
+                    }),
+                },
+                OutputAction::Vrr { vrr: VrrToSet { vrr: settings.vrr, on_demand: false } },
+            ]
+        } else {
+            vec![OutputAction::Off]
+        };
+
+        // Attempt every action even if one fails so we end up as close as possible to the requested settings.
+        let mut errors = Vec::new();
+        for action in actions {
+            if let Err(e) = self.send(Request::Output { output: output.to_string(), action }) {
+                errors.push(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{}: {}", output, errors.join(", ")))
+        }
+    }
+
+    fn event_stream(&self) -> CompositorResult<Box<dyn FnMut() -> CompositorResult<CompositorEvent> + Send>> {
+        let stream = UnixStream::connect(&self.path)
+            .map_err(|e| format!("Failed to connect to Niri IPC: {}", e))?;
+        subscribe(BufReader::new(stream))
+    }
+}
+
+/// Starts an event stream on a fresh connection and translates niri's events into ours.
+fn subscribe(mut stream: BufReader<UnixStream>) -> CompositorResult<Box<dyn FnMut() -> CompositorResult<CompositorEvent> + Send>> {
+    if !matches!(send_request(&mut stream, &Request::EventStream), Ok(Response::Handled)) {
+        return Err("Failed to start Niri event stream".to_string());
+    }
+
+    // niri sends incremental updates for some events, so we track enough state to send full updates instead.
+    let mut workspaces: Vec<Workspace> = Vec::new();
+    let mut windows: HashMap<u64, Window> = HashMap::new();
+
+    Ok(Box::new(move || loop {
+        let event = read_line::<Event>(&mut stream).map_err(|e| format!("Niri socket error: {}", e))?;
+        let event = match event {
+            Event::WorkspacesChanged { workspaces: new_workspaces } => {
+                workspaces = new_workspaces;
+                CompositorEvent::WorkspacesChanged {
+                    workspaces: workspaces.iter().cloned().map(Into::into).collect(),
+                }
+            }
+            Event::WorkspaceActivated { id, focused } => {
+                let output = workspaces.iter().find(|w| w.id == id).and_then(|w| w.output.clone());
+                for workspace in workspaces.iter_mut() {
+                    if workspace.output == output {
+                        workspace.is_active = workspace.id == id;
+                    }
+                    if focused {
+                        workspace.is_focused = workspace.id == id;
+                    }
+                }
+                CompositorEvent::WorkspacesChanged {
+                    workspaces: workspaces.iter().cloned().map(Into::into).collect(),
+                }
+            }
+            Event::WorkspaceUrgencyChanged { id, urgent } => {
+                if let Some(workspace) = workspaces.iter_mut().find(|w| w.id == id) {
+                    workspace.is_urgent = urgent;
+                }
+                CompositorEvent::WorkspacesChanged {
+                    workspaces: workspaces.iter().cloned().map(Into::into).collect(),
+                }
+            }
+            Event::WindowsChanged { windows: new_windows } => {
+                windows = new_windows.into_iter().map(|w| (w.id, w)).collect();
+                CompositorEvent::WindowsChanged {
+                    windows: windows.values().cloned().map(Into::into).collect(),
+                }
+            }
+            Event::WindowOpenedOrChanged { window } => {
+                windows.insert(window.id, window.clone());
+                CompositorEvent::WindowOpenedOrChanged { window: window.into() }
+            }
+            Event::WindowClosed { id } => {
+                windows.remove(&id);
+                CompositorEvent::WindowClosed { id }
+            }
+            Event::WindowFocusChanged { id } => {
+                for window in windows.values_mut() {
+                    window.is_focused = Some(window.id) == id;
+                }
+                CompositorEvent::WindowFocusChanged { id }
+            }
+            Event::WindowUrgencyChanged { id, urgent } => {
+                let Some(window) = windows.get_mut(&id) else {
+                    continue;
+                };
+                window.is_urgent = urgent;
+                CompositorEvent::WindowOpenedOrChanged { window: window.clone().into() }
+            }
+            Event::KeyboardLayoutsChanged { keyboard_layouts } => CompositorEvent::KeyboardLayoutsChanged {
+                names: keyboard_layouts.names,
+                current_idx: keyboard_layouts.current_idx,
+            },
+            Event::KeyboardLayoutSwitched { idx } => CompositorEvent::KeyboardLayoutSwitched { idx },
+            _ => continue,
+        };
+        return Ok(event);
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    /// Answers each request on a socket pair with the next reply, then writes the given events,
+    /// and returns the requests it was sent.
+    fn stand_in(replies: &[&str], events: &[&str]) -> (BufReader<UnixStream>, JoinHandle<Vec<String>>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let replies = replies.iter().map(|reply| reply.to_string()).collect::<Vec<_>>();
+        let events = events.iter().map(|event| event.to_string()).collect::<Vec<_>>();
+        let handle = thread::spawn(move || {
+            let mut server = BufReader::new(server);
+            let mut requests = Vec::new();
+            for reply in replies {
+                let mut line = String::new();
+                server.read_line(&mut line).unwrap();
+                requests.push(line.trim_end().to_string());
+                writeln!(server.get_mut(), "{}", reply).unwrap();
+            }
+            for event in events {
+                writeln!(server.get_mut(), "{}", event).unwrap();
+            }
+            requests
+        });
+        (BufReader::new(client), handle)
+    }
+
+    fn compositor(stream: BufReader<UnixStream>) -> NiriCompositor {
+        NiriCompositor { path: PathBuf::new(), stream }
+    }
+
+    const WORKSPACES: &str = r#"[
+        {"id": 1, "idx": 1, "name": null, "output": "DP-1", "is_urgent": false, "is_active": true, "is_focused": true},
+        {"id": 2, "idx": 2, "name": "web", "output": "DP-1", "is_urgent": false, "is_active": false, "is_focused": false},
+        {"id": 3, "idx": 1, "name": null, "output": "HDMI-A-1", "is_urgent": false, "is_active": true, "is_focused": false}
+    ]"#;
+
+    const WINDOWS: &str = r#"[
+        {"id": 7, "title": "Terminal", "app_id": "foot", "pid": 100, "workspace_id": 1,
+         "is_focused": true, "is_floating": false, "is_urgent": false},
+        {"id": 8, "title": null, "app_id": null, "pid": null, "workspace_id": 2,
+         "is_focused": false, "is_floating": true, "is_urgent": false}
+    ]"#;
+
+    fn single_line(json: &str) -> String {
+        json.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn workspaces() {
+        let reply = single_line(&format!(r#"{{"Ok": {{"Workspaces": {}}}}}"#, WORKSPACES));
+        let (stream, server) = stand_in(&[&reply], &[]);
+        let workspaces = compositor(stream).workspaces().unwrap();
+        assert_eq!(server.join().unwrap(), vec![r#""Workspaces""#]);
+
+        assert_eq!(workspaces.len(), 3);
+        assert_eq!(workspaces[1].id, 2);
+        assert_eq!(workspaces[1].name.as_deref(), Some("web"));
+        assert_eq!(workspaces[2].output.as_deref(), Some("HDMI-A-1"));
+        assert!(workspaces[0].is_focused && workspaces[0].is_active);
+    }
+
+    #[test]
+    fn windows() {
+        let reply = single_line(&format!(r#"{{"Ok": {{"Windows": {}}}}}"#, WINDOWS));
+        let (stream, server) = stand_in(&[&reply], &[]);
+        let windows = compositor(stream).windows().unwrap();
+        assert_eq!(server.join().unwrap(), vec![r#""Windows""#]);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].app_id.as_deref(), Some("foot"));
+        assert_eq!(windows[1].workspace_id, Some(2));
+        assert!(windows[0].is_focused);
+        assert_eq!(windows[1].title, None);
+    }
+
+    #[test]
+    fn error_reply() {
+        let (stream, server) = stand_in(&[r#"{"Err": "no such output"}"#], &[]);
+        let result = compositor(stream).windows();
+        server.join().unwrap();
+        assert_eq!(result.unwrap_err(), "Niri returned an error: no such output");
+    }
+
+    #[test]
+    fn focus_requests() {
+        let (stream, server) = stand_in(&[r#"{"Ok": "Handled"}"#, r#"{"Ok": "Handled"}"#], &[]);
+        let mut compositor = compositor(stream);
+        compositor.focus_workspace(2).unwrap();
+        compositor.focus_window(7).unwrap();
+
+        let requests = server.join().unwrap();
+        let parse = |request: &str| serde_json::from_str::<serde_json::Value>(request).unwrap();
+        assert_eq!(parse(&requests[0]), serde_json::json!({"Action": {"FocusWorkspace": {"reference": {"Id": 2}}}}));
+        assert_eq!(parse(&requests[1]), serde_json::json!({"Action": {"FocusWindow": {"id": 7}}}));
+    }
+
+    #[test]
+    fn event_stream_sends_full_updates() {
+        let workspaces = single_line(&format!(r#"{{"WorkspacesChanged": {{"workspaces": {}}}}}"#, WORKSPACES));
+        let windows = single_line(&format!(r#"{{"WindowsChanged": {{"windows": {}}}}}"#, WINDOWS));
+        let (stream, server) = stand_in(&[r#"{"Ok": "Handled"}"#], &[
+            &workspaces,
+            r#"{"WorkspaceActivated": {"id": 2, "focused": true}}"#,
+            &windows,
+            // Unknown windows are skipped rather than sent half-filled
+            r#"{"WindowUrgencyChanged": {"id": 99, "urgent": true}}"#,
+            r#"{"WindowUrgencyChanged": {"id": 8, "urgent": true}}"#,
+            r#"{"WindowFocusChanged": {"id": 8}}"#,
+            r#"{"WindowClosed": {"id": 7}}"#,
+        ]);
+        let mut next = subscribe(stream).unwrap();
+
+        assert!(matches!(next().unwrap(), CompositorEvent::WorkspacesChanged { workspaces } if workspaces.len() == 3));
+
+        // Activating a workspace only deactivates the others on the same output
+        let CompositorEvent::WorkspacesChanged { workspaces } = next().unwrap() else {
+            panic!("expected a full workspace update");
+        };
+        let active = workspaces.iter().filter(|w| w.is_active).map(|w| w.id).collect::<Vec<_>>();
+        let focused = workspaces.iter().filter(|w| w.is_focused).map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(active, vec![2, 3]);
+        assert_eq!(focused, vec![2]);
+
+        assert!(matches!(next().unwrap(), CompositorEvent::WindowsChanged { windows } if windows.len() == 2));
+        assert!(matches!(
+            next().unwrap(),
+            CompositorEvent::WindowOpenedOrChanged { window } if window.id == 8 && window.is_urgent
+        ));
+        assert!(matches!(next().unwrap(), CompositorEvent::WindowFocusChanged { id: Some(8) }));
+        assert!(matches!(next().unwrap(), CompositorEvent::WindowClosed { id: 7 }));
+
+        assert_eq!(server.join().unwrap(), vec![r#""EventStream""#]);
+        // The stand-in hung up
+        assert!(next().is_err());
+    }
+
+    #[test]
+    fn event_stream_refused() {
+        let (stream, server) = stand_in(&[r#"{"Err": "nope"}"#], &[]);
+        assert!(subscribe(stream).is_err());
+        server.join().unwrap();
+    }
+}
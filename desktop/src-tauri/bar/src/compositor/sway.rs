@@ -0,0 +1,543 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    compositor::{Compositor, CompositorEvent, CompositorKind, CompositorResult, CompositorWindow, CompositorWorkspace},
+    displays::{Display, DisplayMode, DisplaySettings, DisplayTransform},
+    keyboard_layout::KeyboardLayoutTarget,
+};
+
+/// See sway-ipc(7) for the protocol and message types.
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+const GET_INPUTS: u32 = 100;
+
+const EVENT_WORKSPACE: u32 = 0x80000000;
+const EVENT_WINDOW: u32 = 0x80000003;
+const EVENT_INPUT: u32 = 0x80000015;
+
+fn write_message(stream: &mut UnixStream, message_type: u32, payload: &str) -> io::Result<()> {
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+fn read_message(stream: &mut UnixStream) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[0..6] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid sway IPC magic string"));
+    }
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((message_type, payload))
+}
+
+#[derive(Deserialize)]
+struct SwayWorkspace {
+    id: u64,
+    num: i32,
+    name: String,
+    visible: bool,
+    focused: bool,
+    urgent: bool,
+    output: String,
+}
+
+impl From<SwayWorkspace> for CompositorWorkspace {
+    fn from(workspace: SwayWorkspace) -> Self {
+        CompositorWorkspace {
+            id: workspace.id,
+            idx: workspace.num.max(0) as u32,
+            name: Some(workspace.name),
+            output: Some(workspace.output),
+            is_active: workspace.visible,
+            is_focused: workspace.focused,
+            is_urgent: workspace.urgent,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayWindowProperties {
+    class: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    id: u64,
+    #[serde(rename = "type")]
+    node_type: String,
+    name: Option<String>,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    urgent: bool,
+    app_id: Option<String>,
+    window_properties: Option<SwayWindowProperties>,
+    pid: Option<i32>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+impl SwayNode {
+    fn is_window(&self) -> bool {
+        (self.node_type == "con" || self.node_type == "floating_con") && self.pid.is_some()
+    }
+
+    fn to_window(&self, workspace_id: Option<u64>) -> CompositorWindow {
+        CompositorWindow {
+            id: self.id,
+            title: self.name.clone(),
+            app_id: self.app_id.clone().or_else(|| {
+                self.window_properties.as_ref().and_then(|properties| properties.class.clone())
+            }),
+            workspace_id,
+            is_focused: self.focused,
+            is_urgent: self.urgent,
+        }
+    }
+
+    /// Collects every window in the tree along with the workspace containing it.
+    fn collect_windows(&self, workspace_id: Option<u64>, windows: &mut Vec<CompositorWindow>) {
+        let workspace_id = if self.node_type == "workspace" { Some(self.id) } else { workspace_id };
+        if self.is_window() {
+            windows.push(self.to_window(workspace_id));
+        }
+        for node in self.nodes.iter().chain(self.floating_nodes.iter()) {
+            node.collect_windows(workspace_id, windows);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayMode {
+    width: u16,
+    height: u16,
+    refresh: u32,
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    make: String,
+    model: String,
+    serial: String,
+    active: bool,
+    #[serde(default)]
+    modes: Vec<SwayMode>,
+    current_mode: Option<SwayMode>,
+    scale: Option<f64>,
+    transform: Option<String>,
+    rect: SwayRect,
+    adaptive_sync_status: Option<String>,
+}
+
+fn parse_transform(transform: &str) -> DisplayTransform {
+    match transform {
+        "90" => DisplayTransform::Rotate90,
+        "180" => DisplayTransform::Rotate180,
+        "270" => DisplayTransform::Rotate270,
+        "flipped" => DisplayTransform::Flipped,
+        "flipped-90" => DisplayTransform::Flipped90,
+        "flipped-180" => DisplayTransform::Flipped180,
+        "flipped-270" => DisplayTransform::Flipped270,
+        _ => DisplayTransform::Normal,
+    }
+}
+
+fn transform_name(transform: DisplayTransform) -> &'static str {
+    match transform {
+        DisplayTransform::Normal => "normal",
+        DisplayTransform::Rotate90 => "90",
+        DisplayTransform::Rotate180 => "180",
+        DisplayTransform::Rotate270 => "270",
+        DisplayTransform::Flipped => "flipped",
+        DisplayTransform::Flipped90 => "flipped-90",
+        DisplayTransform::Flipped180 => "flipped-180",
+        DisplayTransform::Flipped270 => "flipped-270",
+    }
+}
+
+impl From<SwayOutput> for Display {
+    fn from(output: SwayOutput) -> Self {
+        let current_mode = output.current_mode.as_ref().and_then(|current| {
+            output.modes.iter().position(|mode| {
+                mode.width == current.width && mode.height == current.height && mode.refresh == current.refresh
+            })
+        });
+        Display {
+            name: output.name,
+            make: output.make,
+            model: output.model,
+            serial: Some(output.serial).filter(|serial| !serial.is_empty() && serial != "Unknown"),
+            physical_size: None,
+            modes: output.modes.iter().map(|mode| DisplayMode {
+                width: mode.width,
+                height: mode.height,
+                refresh_rate: mode.refresh,
+                is_preferred: false,
+            }).collect(),
+            current_mode: if output.active { current_mode } else { None },
+            enabled: output.active,
+            scale: output.scale.unwrap_or(1.0),
+            transform: output.transform.as_deref().map_or(DisplayTransform::Normal, parse_transform),
+            position: (output.rect.x, output.rect.y),
+            vrr_supported: output.adaptive_sync_status.is_some(),
+            vrr_enabled: output.adaptive_sync_status.as_deref() == Some("enabled"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayInput {
+    #[serde(rename = "type")]
+    input_type: String,
+    #[serde(default)]
+    xkb_layout_names: Vec<String>,
+    xkb_active_layout_index: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct SwayCommandResult {
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayWindowEvent {
+    change: String,
+    container: SwayNode,
+}
+
+/// Switches by number when the workspace has one, since names can contain anything.
+/// Workspaces without a number report -1.
+fn workspace_command(workspace: &SwayWorkspace) -> String {
+    if workspace.num >= 0 {
+        format!("workspace number {}", workspace.num)
+    } else {
+        let name = workspace.name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("workspace \"{}\"", name)
+    }
+}
+
+pub struct SwayCompositor {
+    path: PathBuf,
+    stream: UnixStream,
+}
+
+impl SwayCompositor {
+    /// Connects to the sway (or i3-compatible) IPC socket at the given path.
+    pub fn connect_to(path: PathBuf) -> CompositorResult<Self> {
+        let stream = UnixStream::connect(&path)
+            .map_err(|e| format!("Failed to connect to sway IPC at {}: {}", path.display(), e))?;
+        Ok(Self { path, stream })
+    }
+
+    fn request<T: DeserializeOwned>(&mut self, message_type: u32, payload: &str) -> CompositorResult<T> {
+        write_message(&mut self.stream, message_type, payload)
+            .map_err(|e| format!("Failed to send sway message: {}", e))?;
+        let (_, reply) = read_message(&mut self.stream)
+            .map_err(|e| format!("Failed to read sway reply: {}", e))?;
+        serde_json::from_slice(&reply).map_err(|e| format!("Failed to parse sway reply: {}", e))
+    }
+
+    fn command(&mut self, command: &str) -> CompositorResult<()> {
+        let results: Vec<SwayCommandResult> = self.request(RUN_COMMAND, command)?;
+        let errors = results.into_iter()
+            .filter(|result| !result.success)
+            .map(|result| result.error.unwrap_or_else(|| "Unknown error".to_string()))
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Sway returned an error: {}", errors.join(", ")))
+        }
+    }
+}
+
+impl Compositor for SwayCompositor {
+    fn kind(&self) -> CompositorKind {
+        CompositorKind::Sway
+    }
+
+    fn workspaces(&mut self) -> CompositorResult<Vec<CompositorWorkspace>> {
+        let workspaces: Vec<SwayWorkspace> = self.request(GET_WORKSPACES, "")?;
+        Ok(workspaces.into_iter().map(Into::into).collect())
+    }
+
+    fn windows(&mut self) -> CompositorResult<Vec<CompositorWindow>> {
+        let tree: SwayNode = self.request(GET_TREE, "")?;
+        let mut windows = Vec::new();
+        tree.collect_windows(None, &mut windows);
+        Ok(windows)
+    }
+
+    fn outputs(&mut self) -> CompositorResult<Vec<Display>> {
+        let outputs: Vec<SwayOutput> = self.request(GET_OUTPUTS, "")?;
+        let mut displays = outputs.into_iter().map(Display::from).collect::<Vec<_>>();
+        displays.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(displays)
+    }
+
+    fn keyboard_layouts(&mut self) -> CompositorResult<(Vec<String>, u8)> {
+        let inputs: Vec<SwayInput> = self.request(GET_INPUTS, "")?;
+        // Sway tracks layouts per keyboard; use the first one since they're usually configured identically.
+        let keyboard = inputs.into_iter()
+            .find(|input| input.input_type == "keyboard" && !input.xkb_layout_names.is_empty())
+            .ok_or("No keyboard with XKB layouts found")?;
+        Ok((keyboard.xkb_layout_names, keyboard.xkb_active_layout_index.unwrap_or(0)))
+    }
+
+    fn focus_workspace(&mut self, id: u64) -> CompositorResult<()> {
+        // Criteria only match views, not workspaces, so look the workspace up and switch to it directly.
+        let workspaces: Vec<SwayWorkspace> = self.request(GET_WORKSPACES, "")?;
+        let workspace = workspaces.into_iter()
+            .find(|workspace| workspace.id == id)
+            .ok_or_else(|| format!("No sway workspace with id {}", id))?;
+        self.command(&workspace_command(&workspace))
+    }
+
+    fn focus_window(&mut self, id: u64) -> CompositorResult<()> {
+        self.command(&format!("[con_id={}] focus", id))
+    }
+
+    fn switch_keyboard_layout(&mut self, target: KeyboardLayoutTarget) -> CompositorResult<()> {
+        let target = match target {
+            KeyboardLayoutTarget::Next => "next".to_string(),
+            KeyboardLayoutTarget::Prev => "prev".to_string(),
+            KeyboardLayoutTarget::Index(idx) => idx.to_string(),
+        };
+        self.command(&format!("input type:keyboard xkb_switch_layout {}", target))
+    }
+
+    fn configure_output(&mut self, output: &str, settings: &DisplaySettings) -> CompositorResult<()> {
+        if !settings.enabled {
+            return self.command(&format!("output {} disable", output));
+        }
+
+        let mut command = format!("output {} enable", output);
+        if let Some(mode) = settings.mode {
+            command.push_str(&format!(
+                " mode {}x{}@{:.3}Hz",
+                mode.width,
+                mode.height,
+                mode.refresh_rate as f64 / 1000.0
+            ));
+        }
+        command.push_str(&format!(
+            " scale {} transform {} position {} {} adaptive_sync {}",
+            settings.scale,
+            transform_name(settings.transform),
+            settings.position.0,
+            settings.position.1,
+            if settings.vrr { "on" } else { "off" }
+        ));
+        self.command(&command)
+    }
+
+    fn event_stream(&self) -> CompositorResult<Box<dyn FnMut() -> CompositorResult<CompositorEvent> + Send>> {
+        let mut stream = UnixStream::connect(&self.path)
+            .map_err(|e| format!("Failed to connect to sway IPC: {}", e))?;
+        write_message(&mut stream, SUBSCRIBE, r#"["workspace", "window", "input"]"#)
+            .map_err(|e| format!("Failed to subscribe to sway events: {}", e))?;
+        let (_, reply) = read_message(&mut stream)
+            .map_err(|e| format!("Failed to read sway reply: {}", e))?;
+        if !String::from_utf8_lossy(&reply).contains("true") {
+            return Err("Sway refused the event subscription".to_string());
+        }
+
+        // Sway's events don't carry the full state we send, so we query it on a second connection.
+        let mut requests = SwayCompositor::connect_to(self.path.clone())?;
+        let mut layouts = requests.keyboard_layouts().ok();
+        let mut pending = vec![CompositorEvent::WorkspacesChanged { workspaces: requests.workspaces()? }];
+        pending.push(CompositorEvent::WindowsChanged { windows: requests.windows()? });
+        if let Some((names, current_idx)) = layouts.clone() {
+            pending.push(CompositorEvent::KeyboardLayoutsChanged { names, current_idx });
+        }
+        pending.reverse();
+
+        Ok(Box::new(move || loop {
+            if let Some(event) = pending.pop() {
+                return Ok(event);
+            }
+
+            let (message_type, payload) = read_message(&mut stream)
+                .map_err(|e| format!("Sway socket error: {}", e))?;
+            match message_type {
+                EVENT_WORKSPACE => {
+                    pending.push(CompositorEvent::WorkspacesChanged { workspaces: requests.workspaces()? });
+                }
+                EVENT_WINDOW => {
+                    let event: SwayWindowEvent = serde_json::from_slice(&payload)
+                        .map_err(|e| format!("Failed to parse sway window event: {}", e))?;
+                    match event.change.as_str() {
+                        "close" => pending.push(CompositorEvent::WindowClosed { id: event.container.id }),
+                        "focus" => pending.push(CompositorEvent::WindowFocusChanged { id: Some(event.container.id) }),
+                        // Window events don't say which workspace the window is on, so look it up in the tree.
+                        _ => {
+                            if let Some(window) = requests.windows()?.into_iter().find(|w| w.id == event.container.id) {
+                                pending.push(CompositorEvent::WindowOpenedOrChanged { window });
+                            }
+                        }
+                    }
+                }
+                EVENT_INPUT => {
+                    let new_layouts = requests.keyboard_layouts().ok();
+                    match (&layouts, &new_layouts) {
+                        (Some((names, idx)), Some((new_names, new_idx))) if names == new_names => {
+                            if idx != new_idx {
+                                pending.push(CompositorEvent::KeyboardLayoutSwitched { idx: *new_idx });
+                            }
+                        }
+                        (_, Some((names, current_idx))) => {
+                            pending.push(CompositorEvent::KeyboardLayoutsChanged {
+                                names: names.clone(),
+                                current_idx: *current_idx,
+                            });
+                        }
+                        _ => {}
+                    }
+                    layouts = new_layouts;
+                }
+                _ => {}
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    /// Answers each request on a socket pair with the next reply, checking its type,
+    /// and returns the payloads it was sent.
+    fn stand_in(replies: Vec<(u32, &'static str)>) -> (SwayCompositor, JoinHandle<Vec<String>>) {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let mut payloads = Vec::new();
+            for (expected_type, reply) in replies {
+                let (message_type, payload) = read_message(&mut server).unwrap();
+                assert_eq!(message_type, expected_type);
+                payloads.push(String::from_utf8(payload).unwrap());
+                write_message(&mut server, message_type, reply).unwrap();
+            }
+            payloads
+        });
+        (SwayCompositor { path: PathBuf::new(), stream: client }, handle)
+    }
+
+    const WORKSPACES: &str = r#"[
+        {"id": 10, "num": 2, "name": "2: web", "visible": true, "focused": true, "urgent": false, "output": "DP-1"},
+        {"id": 11, "num": -1, "name": "say \"hi\" \\o/", "visible": false, "focused": false, "urgent": false, "output": "DP-1"}
+    ]"#;
+
+    #[test]
+    fn message_framing() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        write_message(&mut a, GET_TREE, "hello").unwrap();
+
+        let mut header = [0u8; 14];
+        b.read_exact(&mut header).unwrap();
+        assert_eq!(&header[0..6], MAGIC);
+        assert_eq!(u32::from_ne_bytes(header[6..10].try_into().unwrap()), 5);
+        assert_eq!(u32::from_ne_bytes(header[10..14].try_into().unwrap()), GET_TREE);
+        let mut payload = [0u8; 5];
+        b.read_exact(&mut payload).unwrap();
+        assert_eq!(&payload, b"hello");
+
+        write_message(&mut a, EVENT_WINDOW, "{}").unwrap();
+        assert_eq!(read_message(&mut b).unwrap(), (EVENT_WINDOW, b"{}".to_vec()));
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        a.write_all(b"i3-ipx\0\0\0\0\0\0\0\0").unwrap();
+        assert!(read_message(&mut b).is_err());
+    }
+
+    #[test]
+    fn request_parses_reply() {
+        let (mut compositor, server) = stand_in(vec![(GET_WORKSPACES, WORKSPACES)]);
+        let workspaces = compositor.workspaces().unwrap();
+        assert_eq!(server.join().unwrap(), vec![""]);
+
+        assert_eq!(workspaces.len(), 2);
+        assert_eq!(workspaces[0].id, 10);
+        assert_eq!(workspaces[0].idx, 2);
+        assert!(workspaces[0].is_focused);
+        // Unnumbered workspaces don't get a negative index
+        assert_eq!(workspaces[1].idx, 0);
+    }
+
+    #[test]
+    fn command_reports_errors() {
+        let (mut compositor, server) = stand_in(vec![
+            (RUN_COMMAND, r#"[{"success": true}, {"success": false, "error": "Unknown output"}]"#),
+        ]);
+        let result = compositor.command("output X disable; output Y disable");
+        server.join().unwrap();
+        assert_eq!(result, Err("Sway returned an error: Unknown output".to_string()));
+    }
+
+    #[test]
+    fn focus_numbered_workspace() {
+        let (mut compositor, server) = stand_in(vec![
+            (GET_WORKSPACES, WORKSPACES),
+            (RUN_COMMAND, r#"[{"success": true}]"#),
+        ]);
+        compositor.focus_workspace(10).unwrap();
+        assert_eq!(server.join().unwrap(), vec!["", "workspace number 2"]);
+    }
+
+    #[test]
+    fn focus_named_workspace_escapes_name() {
+        let (mut compositor, server) = stand_in(vec![
+            (GET_WORKSPACES, WORKSPACES),
+            (RUN_COMMAND, r#"[{"success": true}]"#),
+        ]);
+        compositor.focus_workspace(11).unwrap();
+        assert_eq!(server.join().unwrap(), vec!["", r#"workspace "say \"hi\" \\o/""#]);
+    }
+
+    #[test]
+    fn focus_unknown_workspace() {
+        let (mut compositor, server) = stand_in(vec![(GET_WORKSPACES, WORKSPACES)]);
+        assert!(compositor.focus_workspace(99).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn focus_window() {
+        let (mut compositor, server) = stand_in(vec![(RUN_COMMAND, r#"[{"success": true}]"#)]);
+        compositor.focus_window(42).unwrap();
+        assert_eq!(server.join().unwrap(), vec!["[con_id=42] focus"]);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::upower::WarningLevel;
+
+/// User configuration for the bar backend, read from `$XDG_CONFIG_HOME/desktop/bar.toml`.
+/// Every section and value is optional; anything missing falls back to its default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BarConfig {
+    pub keyboard_layout: KeyboardLayoutConfig,
+    pub displays: DisplaysConfig,
+    pub battery: BatteryConfig,
+    pub power_profiles: PowerProfilesConfig,
+    pub power_hooks: PowerHooksConfig,
+    pub charge_thresholds: ChargeThresholdsConfig,
+    pub throughput: ThroughputConfig,
+    pub hotspot: HotspotConfig,
+    pub systray: SystrayConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct KeyboardLayoutConfig {
+    /// Remember the active layout for each window and switch back to it when the window is focused.
+    /// Newly-opened windows start on the first layout.
+    pub per_window: bool,
+    /// Overrides for the short display code of a layout, keyed by the full XKB layout name,
+    /// e.g. `"English (US)" = "US"`.
+    pub short_names: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DisplaysConfig {
+    /// Seconds to wait for a display change to be confirmed before reverting it.
+    pub revert_timeout_secs: u64,
+    /// How often to poll niri for output changes, in milliseconds.
+    pub poll_interval_ms: u64,
+    /// Re-apply the confirmed layout for a set of monitors when it's connected again.
+    pub restore_profiles: bool,
+}
+
+impl Default for DisplaysConfig {
+    fn default() -> Self {
+        Self {
+            revert_timeout_secs: 15,
+            poll_interval_ms: 2000,
+            restore_profiles: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    /// How many hours of charge history to keep for the battery graph.
+    pub history_hours: u64,
+    /// How many minutes of recent samples to average when estimating the time remaining.
+    pub estimate_window_minutes: u64,
+    /// Actions to take as the battery discharges. Each threshold fires once per discharge cycle.
+    pub thresholds: Vec<BatteryThreshold>,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            history_hours: 24,
+            estimate_window_minutes: 15,
+            thresholds: vec![
+                BatteryThreshold {
+                    percentage: Some(15.0),
+                    warning_level: Some(WarningLevel::Low),
+                    actions: vec![BatteryAction::Notify],
+                },
+                BatteryThreshold {
+                    percentage: Some(5.0),
+                    warning_level: Some(WarningLevel::Critical),
+                    actions: vec![BatteryAction::Notify, BatteryAction::Attention],
+                },
+            ],
+        }
+    }
+}
+
+/// A low-battery threshold, e.g.
+/// `{ percentage = 10, warning_level = "Critical", actions = ["notify", { command = "brightnessctl set 10%" }] }`.
+/// It's reached when the display device is discharging and either condition is met.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatteryThreshold {
+    /// Fire at or below this percentage.
+    pub percentage: Option<f64>,
+    /// Fire when UPower's warning level is at least this severe.
+    pub warning_level: Option<WarningLevel>,
+    pub actions: Vec<BatteryAction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryAction {
+    /// Show a notification through the notifications daemon.
+    Notify,
+    /// Put the bar's battery indicator into its attention state until the battery charges again.
+    Attention,
+    /// Run a shell command.
+    Command(String),
+    /// Suspend through logind. Replaced by UPower's critical action if that's hibernation.
+    Suspend,
+    /// Hibernate through logind.
+    Hibernate,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PowerProfilesConfig {
+    /// Hold the power-saver profile while the battery is discharging at or below this percentage.
+    /// The previous profile is restored once the battery charges again.
+    pub power_saver_below: Option<f64>,
+}
+
+/// Shell commands to run when the power supply or lid changes.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PowerHooksConfig {
+    pub on_ac_plugged: Option<String>,
+    pub on_ac_unplugged: Option<String>,
+    pub on_lid_opened: Option<String>,
+    pub on_lid_closed: Option<String>,
+    /// Runs in addition to `on_lid_closed` when no external display is connected.
+    pub on_lid_closed_undocked: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChargeThresholdsConfig {
+    /// Where to look for batteries. Only worth changing to point at a fake tree for testing.
+    pub sysfs_root: PathBuf,
+    /// The command used to write the thresholds when we can't write them directly,
+    /// e.g. `["pkexec"]` or `["sudo", "-n"]`. The files are written by a shell run through it.
+    pub privilege_helper: Vec<String>,
+    pub presets: Vec<ChargeThresholdPreset>,
+}
+
+impl Default for ChargeThresholdsConfig {
+    fn default() -> Self {
+        Self {
+            sysfs_root: PathBuf::from("/sys/class/power_supply"),
+            privilege_helper: vec!["pkexec".to_string()],
+            presets: vec![
+                ChargeThresholdPreset { name: "Conservation".to_string(), start: 60, end: 80 },
+                ChargeThresholdPreset { name: "Balanced".to_string(), start: 75, end: 90 },
+                ChargeThresholdPreset { name: "Full".to_string(), start: 0, end: 100 },
+            ],
+        }
+    }
+}
+
+/// A named pair of charge thresholds, in percent.
+#[derive(Clone, Debug, Deserialize, Serialize, TS)]
+#[ts(export, export_to="../../bindings/ChargeThresholds.ts")]
+pub struct ChargeThresholdPreset {
+    pub name: String,
+    /// Start charging when the battery drops below this.
+    pub start: u8,
+    /// Stop charging once the battery reaches this.
+    pub end: u8,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ThroughputConfig {
+    /// Where to read interface counters from. Only worth changing to feed in synthetic counters.
+    pub sysfs_root: PathBuf,
+    /// How often to read the counters, in milliseconds.
+    pub sample_interval_ms: u64,
+    /// How many seconds of counters to average each rate over, which smooths out bursts.
+    pub window_secs: u64,
+    /// How many rates to keep for the sparkline.
+    pub history_len: usize,
+}
+
+impl Default for ThroughputConfig {
+    fn default() -> Self {
+        Self {
+            sysfs_root: PathBuf::from("/sys/class/net"),
+            sample_interval_ms: 1000,
+            window_secs: 3,
+            history_len: 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HotspotConfig {
+    /// The network name. Defaults to the hostname followed by "-hotspot".
+    pub ssid: Option<String>,
+    /// At least 8 characters. A random password is generated and remembered if this isn't set.
+    pub password: Option<String>,
+    /// "a" for 5 GHz or "bg" for 2.4 GHz. NetworkManager picks one if this isn't set.
+    pub band: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SystrayConfig {
+    /// Move passive items into the overflow unless a rule says otherwise.
+    pub overflow_passive: bool,
+    /// Rules for particular items, e.g. `{ id = "nm-applet", hide = true }`.
+    /// The first rule that matches an item is the one that applies.
+    pub rules: Vec<SystrayRule>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SystrayRule {
+    /// Matches the item's ID, which is usually the application's name.
+    pub id: Option<String>,
+    /// Matches the item's title, for items whose ID changes between runs.
+    /// If both are set, both have to match.
+    pub title: Option<String>,
+    /// Don't show the item at all.
+    pub hide: bool,
+    /// Whether to move the item into the overflow while it's passive, instead of `overflow_passive`.
+    pub overflow_passive: Option<bool>,
+    /// Pinned items come first, lowest position first. The rest follow in the order they appeared.
+    pub position: Option<i32>,
+    /// An icon name or path to show instead of the item's own icon.
+    pub icon: Option<String>,
+    /// Text to show instead of the item's tooltip title.
+    pub tooltip: Option<String>,
+}
+
+impl BarConfig {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("desktop").join("bar.toml"))
+    }
+
+    /// Loads the configuration file, falling back to the defaults if it doesn't exist or can't be parsed.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("Failed to read bar config at {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("Failed to parse bar config at {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+}
@@ -0,0 +1,377 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tauri::{async_runtime::JoinHandle, AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+
+use crate::{
+    compositor::{self, Compositor},
+    config::DisplaysConfig,
+    BarHandler,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/Displays.ts")]
+pub struct DisplayMode {
+    pub width: u16,
+    pub height: u16,
+    /// Refresh rate in millihertz.
+    pub refresh_rate: u32,
+    pub is_preferred: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/Displays.ts")]
+pub enum DisplayTransform {
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Displays.ts")]
+pub struct Display {
+    /// The connector name, like "eDP-1".
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    /// Physical width and height in millimeters, if known.
+    pub physical_size: Option<(u32, u32)>,
+    pub modes: Vec<DisplayMode>,
+    /// Index of the current mode in `modes`, or null if the output is disabled.
+    pub current_mode: Option<usize>,
+    pub enabled: bool,
+    pub scale: f64,
+    pub transform: DisplayTransform,
+    /// Position of the output in the global logical coordinate space.
+    pub position: (i32, i32),
+    pub vrr_supported: bool,
+    pub vrr_enabled: bool,
+}
+
+impl Display {
+    /// A stable identifier for the physical monitor, independent of which connector it's plugged into.
+    /// Monitors without a serial fall back to the connector, so two identical ones don't share a key.
+    pub fn identifier(&self) -> String {
+        match &self.serial {
+            Some(serial) => format!("{} {} {}", self.make, self.model, serial),
+            None => format!("{} {} on {}", self.make, self.model, self.name),
+        }
+    }
+
+    /// Whether this is a built-in laptop panel, judging by its connector.
+    pub fn is_internal(&self) -> bool {
+        ["eDP", "LVDS", "DSI"].iter().any(|prefix| self.name.starts_with(prefix))
+    }
+}
+
+/// The settings to apply to a single output.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/Displays.ts")]
+pub struct DisplaySettings {
+    pub enabled: bool,
+    /// The mode to use, or null to let niri pick automatically.
+    pub mode: Option<DisplayMode>,
+    pub scale: f64,
+    pub transform: DisplayTransform,
+    pub position: (i32, i32),
+    pub vrr: bool,
+}
+
+impl From<&Display> for DisplaySettings {
+    fn from(display: &Display) -> Self {
+        DisplaySettings {
+            enabled: display.enabled,
+            mode: display.current_mode.and_then(|idx| display.modes.get(idx).copied()),
+            scale: display.scale,
+            transform: display.transform,
+            position: display.position,
+            vrr: display.vrr_enabled,
+        }
+    }
+}
+
+/// Settings for a set of outputs, keyed by connector name.
+pub type DisplayLayout = HashMap<String, DisplaySettings>;
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Displays.ts")]
+pub enum DisplayChangeStatus {
+    /// A change was applied and will be reverted unless confirmed within `timeout_secs` seconds.
+    Pending { timeout_secs: u64 },
+    Confirmed,
+    Reverted,
+    /// The set of connected monitors changed while a change was pending, so it was dropped
+    /// without reverting, since the layout it would revert to is for other monitors.
+    Discarded,
+}
+
+struct PendingChange {
+    previous: DisplayLayout,
+    revert_task: JoinHandle<()>,
+}
+
+/// Layouts the user has confirmed, keyed by the sorted identifiers of every connected monitor.
+/// Each layout is keyed by monitor identifier rather than connector so it survives docks renumbering connectors.
+#[derive(Default, Serialize, Deserialize)]
+struct DisplayProfiles(HashMap<String, HashMap<String, DisplaySettings>>);
+
+impl DisplayProfiles {
+    fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("desktop").join("display_profiles.json"))
+    }
+
+    fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| {
+                serde_json::from_str(&contents)
+                    .map_err(|e| eprintln!("Failed to parse display profiles at {}: {}", path.display(), e))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Failed to save display profiles to {}: {}", path.display(), e);
+        }
+    }
+
+    fn key(displays: &[Display]) -> String {
+        let mut identifiers = displays.iter().map(Display::identifier).collect::<Vec<_>>();
+        identifiers.sort();
+        identifiers.join(";")
+    }
+
+    fn insert(&mut self, displays: &[Display], layout: &DisplayLayout) {
+        let profile = displays.iter().filter_map(|display| {
+            layout.get(&display.name).map(|settings| (display.identifier(), settings.clone()))
+        }).collect();
+        self.0.insert(Self::key(displays), profile);
+    }
+
+    /// Gets the saved layout for this set of displays, keyed by connector name.
+    fn get(&self, displays: &[Display]) -> Option<DisplayLayout> {
+        let profile = self.0.get(&Self::key(displays))?;
+        Some(displays.iter().filter_map(|display| {
+            profile.get(&display.identifier()).map(|settings| (display.name.clone(), settings.clone()))
+        }).collect())
+    }
+}
+
+pub struct DisplaysState {
+    config: DisplaysConfig,
+    displays: Vec<Display>,
+    pending: Option<PendingChange>,
+    profiles: DisplayProfiles,
+}
+
+impl DisplaysState {
+    /// Whether an enabled monitor other than the built-in panel is connected.
+    pub fn has_external_display(&self) -> bool {
+        self.displays.iter().any(|display| display.enabled && !display.is_internal())
+    }
+}
+
+/// Applies a layout through the compositor.
+/// Every output is attempted even if an earlier one fails so we end up as close as possible to the requested layout.
+fn apply_layout(layout: &DisplayLayout) -> Result<(), String> {
+    let mut compositor = compositor::connect()?;
+    let errors = layout.iter()
+        .filter_map(|(output, settings)| compositor.configure_output(output, settings).err())
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+impl BarHandler {
+    pub fn start_display_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let config = self.config.displays.clone();
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+        app_handle.manage(Mutex::new(DisplaysState {
+            config,
+            displays: Vec::new(),
+            pending: None,
+            profiles: DisplayProfiles::load(),
+        }));
+
+        let app_handle = app_handle.clone();
+        // Not every compositor sends an event when outputs change, so poll through the compositor instead.
+        thread::spawn(move || {
+            let mut connection: Option<Box<dyn Compositor>> = None;
+            loop {
+                if connection.is_none() {
+                    connection = compositor::connect()
+                        .map_err(|e| eprintln!("Failed to connect to compositor: {}", e))
+                        .ok();
+                }
+                if let Some(c) = &mut connection {
+                    match c.outputs() {
+                        Ok(displays) => Self::update_displays(&app_handle, displays),
+                        Err(e) => {
+                            eprintln!("Failed to get compositor outputs: {}", e);
+                            connection = None;
+                        }
+                    }
+                }
+                thread::sleep(poll_interval);
+            }
+        });
+    }
+
+    fn update_displays<R: Runtime>(app_handle: &AppHandle<R>, displays: Vec<Display>) {
+        let state = app_handle.state::<Mutex<DisplaysState>>();
+        let mut state = state.lock().unwrap();
+        if state.displays == displays {
+            return;
+        }
+
+        let monitors_changed = DisplayProfiles::key(&state.displays) != DisplayProfiles::key(&displays);
+        state.displays = displays;
+        let _ = app_handle.emit("displays_changed", state.displays.clone());
+
+        if !monitors_changed || !state.config.restore_profiles {
+            return;
+        }
+
+        // A different set of monitors showed up; the pending change no longer applies to it.
+        if let Some(pending) = state.pending.take() {
+            pending.revert_task.abort();
+            let _ = app_handle.emit("display_change_status", DisplayChangeStatus::Discarded);
+        }
+
+        if let Some(layout) = state.profiles.get(&state.displays) {
+            let current = state.displays.iter()
+                .map(|display| (display.name.clone(), DisplaySettings::from(display)))
+                .collect::<DisplayLayout>();
+            if current != layout {
+                if let Err(e) = apply_layout(&layout) {
+                    eprintln!("Failed to restore saved display layout: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_displays(
+    state: State<'_, Mutex<DisplaysState>>,
+) -> Result<Vec<Display>, ()> {
+    Ok(state.lock().unwrap().displays.clone())
+}
+
+/// Applies a new layout and starts the revert timer.
+/// The change is reverted unless `confirm_display_settings` is called before the timeout.
+#[tauri::command]
+pub(crate) async fn apply_display_settings<R: Runtime>(
+    payload: DisplayLayout,
+    app_handle: AppHandle<R>,
+    state: State<'_, Mutex<DisplaysState>>,
+) -> Result<(), String> {
+    let app_handle_2 = app_handle.clone();
+    let timeout_secs = state.lock().unwrap().config.revert_timeout_secs;
+    let revert_task = tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(timeout_secs)).await;
+        let state = app_handle_2.state::<Mutex<DisplaysState>>();
+        let pending = state.lock().unwrap().pending.take();
+        if let Some(pending) = pending {
+            if let Err(e) = apply_layout(&pending.previous) {
+                eprintln!("Failed to revert display settings: {}", e);
+            }
+            let _ = app_handle_2.emit("display_change_status", DisplayChangeStatus::Reverted);
+        }
+    });
+
+    // Arm the revert before applying anything, since some outputs may change even if others fail.
+    // If a change is already pending, keep the original layout as the one to revert to.
+    {
+        let mut state = state.lock().unwrap();
+        let previous = match state.pending.take() {
+            Some(pending) => {
+                pending.revert_task.abort();
+                pending.previous
+            }
+            None => state.displays.iter()
+                .map(|display| (display.name.clone(), DisplaySettings::from(display)))
+                .collect(),
+        };
+        state.pending = Some(PendingChange { previous, revert_task });
+    }
+
+    // Talking to the compositor blocks, so don't hold the lock for it
+    let result = apply_layout(&payload);
+    let _ = app_handle.emit("display_change_status", DisplayChangeStatus::Pending { timeout_secs });
+    result
+}
+
+/// Keeps the pending display change and saves it as the layout for the current set of monitors.
+#[tauri::command]
+pub(crate) async fn confirm_display_settings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, Mutex<DisplaysState>>,
+) -> Result<(), ()> {
+    let known_displays = {
+        let mut state = state.lock().unwrap();
+        let Some(pending) = state.pending.take() else {
+            return Err(());
+        };
+        pending.revert_task.abort();
+        state.displays.clone()
+    };
+
+    // Save the layout as applied by the compositor rather than what we requested, since it may have been adjusted.
+    // Talking to the compositor blocks, so don't hold the lock for it.
+    let displays = compositor::connect()
+        .and_then(|mut compositor| compositor.outputs())
+        .unwrap_or(known_displays);
+    let mut state = state.lock().unwrap();
+    let layout = displays.iter()
+        .map(|display| (display.name.clone(), DisplaySettings::from(display)))
+        .collect::<DisplayLayout>();
+    state.profiles.insert(&displays, &layout);
+    state.profiles.save();
+
+    let _ = app_handle.emit("display_change_status", DisplayChangeStatus::Confirmed);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn revert_display_settings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, Mutex<DisplaysState>>,
+) -> Result<(), String> {
+    let pending = state.lock().unwrap().pending.take();
+    let Some(pending) = pending else {
+        return Ok(());
+    };
+    pending.revert_task.abort();
+
+    let result = apply_layout(&pending.previous);
+    let _ = app_handle.emit("display_change_status", DisplayChangeStatus::Reverted);
+    result
+}
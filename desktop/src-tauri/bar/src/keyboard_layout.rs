@@ -0,0 +1,215 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+
+use crate::{
+    compositor::{self, Compositor, CompositorEvent},
+    config::KeyboardLayoutConfig,
+    BarHandler,
+};
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/KeyboardLayouts.ts")]
+pub struct KeyboardLayout {
+    /// The full XKB layout name, like "English (US)".
+    pub name: String,
+    /// A short code to show in the bar, like "EN". Unique among the configured layouts.
+    pub short_name: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, TS)]
+#[ts(export, export_to="../../bindings/KeyboardLayouts.ts")]
+pub struct KeyboardLayouts {
+    pub layouts: Vec<KeyboardLayout>,
+    /// The index of the active layout in `layouts`.
+    pub current_idx: u8,
+}
+
+#[derive(Clone, Debug, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/KeyboardLayouts.ts")]
+pub enum KeyboardLayoutTarget {
+    Next,
+    Prev,
+    Index(u8),
+}
+
+pub struct KeyboardLayoutState {
+    config: KeyboardLayoutConfig,
+    layouts: KeyboardLayouts,
+    focused_window: Option<u64>,
+    /// The last layout used in each window. Only tracked if `per_window` is enabled.
+    window_layouts: HashMap<u64, u8>,
+}
+
+impl KeyboardLayoutState {
+    pub fn new(config: KeyboardLayoutConfig) -> Self {
+        Self {
+            config,
+            layouts: KeyboardLayouts::default(),
+            focused_window: None,
+            window_layouts: HashMap::new(),
+        }
+    }
+
+    /// Updates our state from a compositor event.
+    /// Returns whether the layouts changed and, if a newly-focused window used a different layout, the layout to switch to.
+    fn handle_event(&mut self, event: &CompositorEvent) -> (bool, Option<u8>) {
+        match event {
+            CompositorEvent::KeyboardLayoutsChanged { names, current_idx } => {
+                self.layouts = KeyboardLayouts {
+                    layouts: build_layouts(names, &self.config.short_names),
+                    current_idx: *current_idx,
+                };
+                // The indices we remembered may not refer to the same layouts anymore
+                self.window_layouts.clear();
+                (true, None)
+            }
+            CompositorEvent::KeyboardLayoutSwitched { idx } => {
+                self.layouts.current_idx = *idx;
+                if let (true, Some(window)) = (self.config.per_window, self.focused_window) {
+                    self.window_layouts.insert(window, *idx);
+                }
+                (true, None)
+            }
+            CompositorEvent::WindowFocusChanged { id } => {
+                self.focused_window = *id;
+                if !self.config.per_window {
+                    return (false, None);
+                }
+                let Some(window) = id else {
+                    return (false, None);
+                };
+
+                let layout = self.window_layouts.get(window).copied().unwrap_or(0);
+                if layout != self.layouts.current_idx && (layout as usize) < self.layouts.layouts.len() {
+                    (false, Some(layout))
+                } else {
+                    (false, None)
+                }
+            }
+            CompositorEvent::WindowClosed { id } => {
+                self.window_layouts.remove(id);
+                (false, None)
+            }
+            _ => (false, None),
+        }
+    }
+}
+
+/// Builds the layout list, giving each layout a short display code.
+/// Layouts that would share a code (like "English (US)" and "English (Dvorak)") get their variant appended.
+fn build_layouts(names: &[String], overrides: &HashMap<String, String>) -> Vec<KeyboardLayout> {
+    let codes = names.iter().map(|name| language_code(name)).collect::<Vec<_>>();
+
+    names.iter().zip(codes.iter()).map(|(name, code)| {
+        let short_name = if let Some(short_name) = overrides.get(name) {
+            short_name.clone()
+        } else if codes.iter().filter(|c| *c == code).count() > 1 {
+            match variant(name) {
+                Some(variant) => format!("{}-{}", code, variant.chars().take(3).collect::<String>().to_uppercase()),
+                None => code.clone(),
+            }
+        } else {
+            code.clone()
+        };
+
+        KeyboardLayout {
+            name: name.clone(),
+            short_name,
+        }
+    }).collect()
+}
+
+/// Guesses an ISO 639-1 style code from an XKB layout description like "German (Neo 2)".
+fn language_code(name: &str) -> String {
+    let language = name.split(" (").next().unwrap_or(name).trim();
+    let code = match language {
+        "Arabic" => "AR",
+        "Chinese" => "ZH",
+        "Czech" => "CS",
+        "Danish" => "DA",
+        "Dutch" => "NL",
+        "English" => "EN",
+        "Finnish" => "FI",
+        "French" => "FR",
+        "German" => "DE",
+        "Greek" => "EL",
+        "Hebrew" => "HE",
+        "Hungarian" => "HU",
+        "Italian" => "IT",
+        "Japanese" => "JA",
+        "Korean" => "KO",
+        "Norwegian" => "NO",
+        "Polish" => "PL",
+        "Portuguese" => "PT",
+        "Russian" => "RU",
+        "Spanish" => "ES",
+        "Swedish" => "SV",
+        "Turkish" => "TR",
+        "Ukrainian" => "UK",
+        _ => return language.chars().take(2).collect::<String>().to_uppercase(),
+    };
+    code.to_string()
+}
+
+/// Extracts the variant in parentheses from an XKB layout description, if any.
+fn variant(name: &str) -> Option<&str> {
+    let start = name.find('(')?;
+    let end = name.rfind(')')?;
+    (start < end).then(|| &name[start + 1..end])
+}
+
+impl BarHandler {
+    /// Updates the keyboard layout state from a compositor event, emitting changes to the frontend.
+    /// `action_connection` is lazily connected and used to restore per-window layouts.
+    pub(crate) fn handle_keyboard_layout_event<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        event: &CompositorEvent,
+        action_connection: &mut Option<Box<dyn Compositor>>,
+    ) {
+        let state = app_handle.state::<Mutex<KeyboardLayoutState>>();
+        let (changed, switch_to) = {
+            let mut state = state.lock().unwrap();
+            let (changed, switch_to) = state.handle_event(event);
+            (changed.then(|| state.layouts.clone()), switch_to)
+        };
+
+        if let Some(layouts) = changed {
+            let _ = app_handle.emit("keyboard_layouts_changed", layouts);
+        }
+
+        if let Some(idx) = switch_to {
+            if action_connection.is_none() {
+                *action_connection = compositor::connect()
+                    .map_err(|e| eprintln!("Failed to connect to compositor: {}", e))
+                    .ok();
+            }
+            if let Some(connection) = action_connection {
+                if let Err(e) = connection.switch_keyboard_layout(KeyboardLayoutTarget::Index(idx)) {
+                    eprintln!("Failed to restore keyboard layout {} for focused window: {}", idx, e);
+                    // Reconnect next time in case the socket was closed
+                    *action_connection = None;
+                }
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_keyboard_layouts(
+    state: State<'_, Mutex<KeyboardLayoutState>>,
+) -> Result<KeyboardLayouts, ()> {
+    Ok(state.lock().unwrap().layouts.clone())
+}
+
+#[tauri::command]
+pub(crate) async fn switch_keyboard_layout(
+    payload: KeyboardLayoutTarget,
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<(), String> {
+    let mut handler = handler.lock().unwrap();
+    let compositor = handler.compositor.as_mut().ok_or("No compositor connection")?;
+    compositor.switch_keyboard_layout(payload)
+}
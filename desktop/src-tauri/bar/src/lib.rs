@@ -4,44 +4,51 @@ use tauri::{
     Manager, Runtime,
     plugin::{Builder, TauriPlugin},
 };
-use zbus::fdo::PropertiesProxy;
 
-use crate::{networkmanager::get_networkmanager_state, niri::niri_request, systemtray::get_systray_items, upower::{create_upower_proxy, get_upower_properties}};
+use crate::{compositor::{focus_window, focus_workspace, get_compositor_kind, get_windows, get_workspaces, Compositor, CompositorKind}, config::BarConfig, displays::{apply_display_settings, confirm_display_settings, get_displays, revert_display_settings}, keyboard_layout::{get_keyboard_layouts, switch_keyboard_layout}, networkmanager::{activate_saved_connection, answer_secret_prompt, connect_wifi, deactivate_saved_connection, disconnect_wifi, get_hotspot, get_network_devices, get_network_throughput, get_networkmanager_state, get_radio_state, get_saved_connections, get_wifi_devices, request_wifi_scan, set_airplane_mode, set_networking_enabled, set_wireless_enabled, set_wwan_enabled, start_hotspot, stop_hotspot}, niri::niri_request, power_profiles::{get_power_profiles, set_power_profile}, systemtray::{activate_tray_item, get_systray_items, resync_systray_items, scroll_tray_item, serve_pixmap, set_symbolic_colors, tray_menu_closed, tray_menu_opened, SymbolicColors, PIXMAP_SCHEME}, upower::{apply_charge_threshold_preset, get_battery_attention, get_charge_thresholds, get_battery_history, get_power_supply_state, get_upower_devices, get_upower_properties, set_charge_thresholds}};
 
 mod upower;
+mod power_profiles;
 mod networkmanager;
 mod systemtray;
 mod niri;
+mod keyboard_layout;
+mod config;
+mod displays;
+mod compositor;
+mod rfkill;
 
 struct BarHandler {
+    config: BarConfig,
+    compositor: Option<Box<dyn Compositor>>,
+    /// A raw niri socket for the frontend's `niri_request` passthrough. Only connected when running under niri.
     socket: Option<Socket>,
-    upower_proxy: Option<PropertiesProxy<'static>>
 }
 
 impl BarHandler {
     pub async fn new() -> Self {
-        let mut socket = Socket::connect().ok();
+        let config = BarConfig::load();
+
+        let compositor = compositor::connect()
+            .map_err(|e| eprintln!("Failed to connect to compositor: {}", e))
+            .ok();
+
+        let is_niri = compositor.as_ref().is_some_and(|c| c.kind() == CompositorKind::Niri);
+        let mut socket = if is_niri { Socket::connect().ok() } else { None };
 
         if let Some(ref mut socket) = socket {
             // Start an event stream with Niri
             if !matches!(socket.send(Request::EventStream), Ok(Ok(Response::Handled))) {
                 eprintln!("Failed to start Niri event stream");
             }
-        } else {
+        } else if is_niri {
             eprintln!("Failed to connect to Niri IPC!");
         }
 
-        let upower_proxy = match create_upower_proxy().await {
-            Ok(p) => Some(p),
-            Err(e) => {
-                eprintln!("Failed to create UPower proxy: {}", e);
-                None
-            }
-        };
-
         Self {
+            config,
+            compositor,
             socket,
-            upower_proxy
         }
     }
 }
@@ -58,22 +65,95 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             niri_request,
             get_upower_properties,
             get_networkmanager_state,
-            get_systray_items
+            get_systray_items,
+            resync_systray_items,
+            get_keyboard_layouts,
+            switch_keyboard_layout,
+            get_displays,
+            apply_display_settings,
+            confirm_display_settings,
+            revert_display_settings,
+            get_compositor_kind,
+            get_workspaces,
+            get_windows,
+            focus_workspace,
+            focus_window,
+            get_upower_devices,
+            get_battery_history,
+            get_battery_attention,
+            get_power_profiles,
+            set_power_profile,
+            get_power_supply_state,
+            get_charge_thresholds,
+            set_charge_thresholds,
+            apply_charge_threshold_preset,
+            get_wifi_devices,
+            request_wifi_scan,
+            connect_wifi,
+            disconnect_wifi,
+            get_network_devices,
+            get_saved_connections,
+            activate_saved_connection,
+            deactivate_saved_connection,
+            answer_secret_prompt,
+            get_radio_state,
+            set_wireless_enabled,
+            set_wwan_enabled,
+            set_networking_enabled,
+            set_airplane_mode,
+            get_network_throughput,
+            get_hotspot,
+            start_hotspot,
+            stop_hotspot,
+            activate_tray_item,
+            scroll_tray_item,
+            tray_menu_opened,
+            tray_menu_closed,
+            set_symbolic_colors
         ])
+        .register_uri_scheme_protocol(PIXMAP_SCHEME, serve_pixmap)
         .setup(|app, _plugin_api| {
+            // Managed up front, since the frontend sends these as soon as it loads
+            app.manage(Mutex::new(SymbolicColors::default()));
+
             let app_ = app.app_handle().clone();
             tauri::async_runtime::spawn(async move {
                 let mut handler = BarHandler::new().await;
                 
-                handler
-                    .start_niri_event_thread(&app_)
-                    .expect("Failed to start Niri event listener thread");
+                // The raw niri event stream is only available when running under niri
+                if handler.start_niri_event_thread(&app_).is_none() {
+                    eprintln!("Not starting Niri event listener thread");
+                }
+
+                handler.start_compositor_event_thread(&app_);
+
+                handler.start_display_events(&app_);
 
                 handler.start_upower_events(&app_);
 
+                handler.start_upower_device_events(&app_);
+
+                handler.start_power_supply_events(&app_);
+
+                handler.start_power_profiles_events(&app_);
+
                 handler.start_networkmanager_events(&app_);
 
-                handler.start_system_tray_events(&app_).await;
+                handler.start_wifi_events(&app_);
+
+                handler.start_network_device_events(&app_);
+
+                handler.start_saved_connection_events(&app_);
+
+                handler.start_secret_agent(&app_);
+
+                handler.start_radio_events(&app_);
+
+                handler.start_throughput_events(&app_);
+
+                handler.start_hotspot_events(&app_);
+
+                handler.start_system_tray_events(&app_);
 
                 app_.manage(Mutex::new(handler));
             });
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use futures::{stream, FutureExt, StreamExt};
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::{message, Connection, MatchRule, MessageStream};
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+use crate::networkmanager::dbus::{
+    ActiveConnectionProxy, ConnectionSettings, NetworkManagerDbusProxy, SettingsConnectionProxy, SettingsProxy,
+    ACTIVE_CONNECTION_INTERFACE, DBUS_BUS, SETTINGS_CONNECTION_INTERFACE,
+};
+use crate::networkmanager::{ActivationState, COALESCE_DELAY};
+use crate::BarHandler;
+
+/// A connection profile saved in NetworkManager.
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/SavedConnections.ts")]
+pub struct SavedConnection {
+    pub uuid: String,
+    /// The user-visible connection name.
+    pub name: String,
+    /// The connection type, like "802-11-wireless" or "vpn".
+    pub connection_type: String,
+    /// The state of the active connection, or None if it isn't active.
+    pub state: Option<ActivationState>,
+    /// Whether NetworkManager connects automatically when it's available.
+    pub autoconnect: bool,
+}
+
+/// Saved connections grouped by type, each sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/SavedConnections.ts")]
+pub struct SavedConnections {
+    pub vpn: Vec<SavedConnection>,
+    pub wireguard: Vec<SavedConnection>,
+    pub ethernet: Vec<SavedConnection>,
+    pub wifi: Vec<SavedConnection>,
+    /// Everything else, like bridges or mobile broadband.
+    pub other: Vec<SavedConnection>,
+}
+
+impl SavedConnections {
+    fn group(&mut self, connection: SavedConnection) {
+        let group = match connection.connection_type.as_str() {
+            "vpn" => &mut self.vpn,
+            "wireguard" => &mut self.wireguard,
+            "802-3-ethernet" => &mut self.ethernet,
+            "802-11-wireless" => &mut self.wifi,
+            // NetworkManager manages "lo" as a connection since 1.42
+            "loopback" => return,
+            _ => &mut self.other,
+        };
+        group.push(connection);
+    }
+
+    fn sort(&mut self) {
+        for group in [&mut self.vpn, &mut self.wireguard, &mut self.ethernet, &mut self.wifi, &mut self.other] {
+            group.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SavedConnectionsState {
+    connections: SavedConnections,
+}
+
+fn setting<T: TryFrom<OwnedValue>>(settings: &ConnectionSettings, key: &str) -> Option<T> {
+    settings.get("connection")
+        .and_then(|connection| connection.get(key))
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
+
+/// Returns the active connections keyed by the UUID of the saved connection they were activated from.
+pub(super) async fn active_connections(connection: &Connection) -> zbus::Result<HashMap<String, (OwnedObjectPath, ActivationState)>> {
+    let network_manager = NetworkManagerDbusProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let mut active = HashMap::new();
+    for path in network_manager.active_connections().await? {
+        let proxy = ActiveConnectionProxy::builder(connection)
+            .path(path.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        // Active connections can vanish while we're reading them
+        let (Ok(uuid), Ok(state)) = (proxy.uuid().await, proxy.state().await) else {
+            continue;
+        };
+        active.insert(uuid, (path.into(), state.into()));
+    }
+    Ok(active)
+}
+
+/// The parts of a saved connection's settings that are shown.
+struct ConnectionProfile {
+    uuid: String,
+    name: String,
+    connection_type: String,
+    autoconnect: bool,
+}
+
+/// Reads one saved connection, or None if it's gone or missing the basics.
+async fn read_profile(connection: &Connection, path: &OwnedObjectPath) -> zbus::Result<Option<ConnectionProfile>> {
+    let settings_connection = SettingsConnectionProxy::builder(connection)
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    // Connections can vanish while we're reading them
+    let Ok(settings) = settings_connection.get_settings().await else {
+        return Ok(None);
+    };
+
+    let (Some(uuid), Some(name), Some(connection_type)) = (
+        setting::<String>(&settings, "uuid"),
+        setting::<String>(&settings, "id"),
+        setting::<String>(&settings, "type"),
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(ConnectionProfile {
+        autoconnect: setting(&settings, "autoconnect").unwrap_or(true),
+        uuid,
+        name,
+        connection_type,
+    }))
+}
+
+fn saved_connections(
+    profiles: &HashMap<OwnedObjectPath, ConnectionProfile>,
+    active: &HashMap<String, (OwnedObjectPath, ActivationState)>,
+) -> SavedConnections {
+    let mut connections = SavedConnections::default();
+    for profile in profiles.values() {
+        connections.group(SavedConnection {
+            uuid: profile.uuid.clone(),
+            name: profile.name.clone(),
+            connection_type: profile.connection_type.clone(),
+            state: active.get(&profile.uuid).map(|(_, state)| *state),
+            autoconnect: profile.autoconnect,
+        });
+    }
+    connections.sort();
+    connections
+}
+
+/// What a signal says needs reading again.
+pub(super) enum SavedConnectionChange {
+    /// A connection was added or its settings were updated.
+    Read(OwnedObjectPath),
+    Removed(OwnedObjectPath),
+    /// A connection was activated, deactivated or changed state.
+    Active,
+}
+
+/// Listens for signals from one interface and member on every NetworkManager object.
+pub(super) async fn signals_from_any_object(connection: &Connection, interface: &'static str, member: &'static str) -> zbus::Result<MessageStream> {
+    let rule = MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .sender(DBUS_BUS)?
+        .interface(interface)?
+        .member(member)?
+        .build();
+    MessageStream::for_match_rule(rule, connection, None).await
+}
+
+async fn publish_saved_connections<R: Runtime>(app_handle: &AppHandle<R>, connections: SavedConnections) {
+    let state = app_handle.state::<Mutex<SavedConnectionsState>>();
+    let mut state = state.lock().await;
+    if state.connections != connections {
+        state.connections = connections.clone();
+        let _ = app_handle.emit("saved_connections_changed", connections);
+    }
+}
+
+impl BarHandler {
+    /// Tracks saved connections and whether they're active.
+    pub fn start_saved_connection_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(SavedConnectionsState::default()));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                Self::run_saved_connection_events(&app_handle, &connection).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track saved connections: {}", e);
+            }
+        });
+    }
+
+    /// Reading a connection's settings is slow, so unlike the other NetworkManager state, each one is only
+    /// read again when it changes. Activation changes just re-read the active connections.
+    async fn run_saved_connection_events<R: Runtime>(app_handle: &AppHandle<R>, connection: &Connection) -> zbus::Result<()> {
+        let settings = SettingsProxy::new(connection).await?;
+        let network_manager = NetworkManagerDbusProxy::new(connection).await?;
+
+        // Subscribe before reading so we don't miss changes in between
+        let added = settings.receive_new_connection().await?.filter_map(|signal| async move {
+            Some(SavedConnectionChange::Read(signal.args().ok()?.connection.into()))
+        });
+        let removed = settings.receive_connection_removed().await?.filter_map(|signal| async move {
+            Some(SavedConnectionChange::Removed(signal.args().ok()?.connection.into()))
+        });
+        let updated = signals_from_any_object(connection, SETTINGS_CONNECTION_INTERFACE, "Updated").await?
+            .filter_map(|message| async move {
+                let path = message.ok()?.header().path()?.to_owned();
+                Some(SavedConnectionChange::Read(path.into()))
+            });
+        let activated = network_manager.receive_active_connections_changed().await
+            .map(|_| SavedConnectionChange::Active);
+        let state_changed = signals_from_any_object(connection, ACTIVE_CONNECTION_INTERFACE, "StateChanged").await?
+            .map(|_| SavedConnectionChange::Active);
+        let mut changes = stream::select_all([
+            added.boxed(),
+            removed.boxed(),
+            updated.boxed(),
+            activated.boxed(),
+            state_changed.boxed(),
+        ]);
+
+        let mut profiles = HashMap::new();
+        for path in settings.list_connections().await? {
+            if let Some(profile) = read_profile(connection, &path).await? {
+                profiles.insert(path, profile);
+            }
+        }
+        let mut active = active_connections(connection).await?;
+        publish_saved_connections(app_handle, saved_connections(&profiles, &active)).await;
+
+        while let Some(change) = changes.next().await {
+            // Activating a connection changes its state several times in quick succession
+            tokio::time::sleep(COALESCE_DELAY).await;
+            let mut pending = vec![change];
+            while let Some(Some(change)) = changes.next().now_or_never() {
+                pending.push(change);
+            }
+
+            // The last change to each connection wins
+            let mut reads = HashMap::new();
+            let mut read_active = false;
+            for change in pending {
+                match change {
+                    SavedConnectionChange::Read(path) => { reads.insert(path, true); }
+                    SavedConnectionChange::Removed(path) => { reads.insert(path, false); }
+                    SavedConnectionChange::Active => read_active = true,
+                }
+            }
+
+            for (path, read) in reads {
+                let profile = if read {
+                    read_profile(connection, &path).await.unwrap_or_else(|e| {
+                        eprintln!("Failed to read saved connection {}: {}", path.as_str(), e);
+                        None
+                    })
+                } else {
+                    None
+                };
+                match profile {
+                    Some(profile) => profiles.insert(path, profile),
+                    None => profiles.remove(&path),
+                };
+            }
+            if read_active {
+                match active_connections(connection).await {
+                    Ok(new_active) => active = new_active,
+                    Err(e) => eprintln!("Failed to read active connections: {}", e),
+                }
+            }
+
+            publish_saved_connections(app_handle, saved_connections(&profiles, &active)).await;
+        }
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub async fn get_saved_connections(state: State<'_, Mutex<SavedConnectionsState>>) -> Result<SavedConnections, ()> {
+    Ok(state.lock().await.connections.clone())
+}
+
+/// Activates a saved connection by UUID, letting NetworkManager pick the device.
+#[tauri::command]
+pub async fn activate_saved_connection(payload: String) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let settings = SettingsProxy::new(&connection).await.map_err(|e| e.to_string())?;
+    let network_manager = NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())?;
+
+    let path = settings.get_connection_by_uuid(&payload).await.map_err(|e| e.to_string())?;
+    let none = ObjectPath::from_static_str_unchecked("/");
+    network_manager.activate_connection(&path, &none, &none)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn deactivate_saved_connection(payload: String) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let network_manager = NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())?;
+
+    let active = active_connections(&connection).await.map_err(|e| e.to_string())?;
+    let (path, _) = active.get(&payload).ok_or_else(|| format!("Connection {} isn't active", payload))?;
+    network_manager.deactivate_connection(path).await.map_err(|e| e.to_string())
+}
@@ -1,9 +1,9 @@
+use std::collections::HashMap;
+
 use zbus::{Result, proxy};
-use zvariant::{ObjectPath, Str};
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Str, Value};
 
 pub(super) const DBUS_BUS: &str = "org.freedesktop.NetworkManager";
-pub(super) const DBUS_PATH: &str = "/org/freedesktop/NetworkManager";
-pub(super) const DBUS_INTERFACE: &str = "org.freedesktop.NetworkManager";
 
 #[proxy(
     default_service = "org.freedesktop.NetworkManager",
@@ -11,9 +11,39 @@ pub(super) const DBUS_INTERFACE: &str = "org.freedesktop.NetworkManager";
     default_path = "/org/freedesktop/NetworkManager"
 )]
 pub(super) trait NetworkManagerDbus {
+    /// Activates a saved connection. `specific_object` is the access point for Wi-Fi, or "/" to let NetworkManager pick.
+    fn activate_connection(
+        &self,
+        connection: &ObjectPath<'_>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> Result<OwnedObjectPath>;
+
+    /// Creates a connection from partial settings, filling in the rest from the device and access point, and activates it.
+    /// Returns the new connection and active connection paths.
+    fn add_and_activate_connection(
+        &self,
+        connection: HashMap<&str, HashMap<&str, Value<'_>>>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> Result<(OwnedObjectPath, OwnedObjectPath)>;
+
+    fn deactivate_connection(&self, active_connection: &ObjectPath<'_>) -> Result<()>;
+
+    /// Turns all networking on or off. NetworkingEnabled itself is read-only.
+    fn enable(&self, enable: bool) -> Result<()>;
+
     #[zbus(property)]
     fn active_connections(&self) -> Result<Vec<ObjectPath>>;
 
+    /// The connection being activated that will become the primary connection, or "/".
+    #[zbus(property)]
+    fn activating_connection(&self) -> Result<OwnedObjectPath>;
+
+    /// An NMConnectivityState: 0 unknown, 1 none, 2 portal, 3 limited, 4 full.
+    #[zbus(property)]
+    fn connectivity(&self) -> Result<u32>;
+
     #[zbus(property)]
     fn devices(&self) -> Result<Vec<ObjectPath>>;
 
@@ -28,4 +58,232 @@ pub(super) trait NetworkManagerDbus {
 
     #[zbus(property)]
     fn wireless_enabled(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn set_wireless_enabled(&self, value: bool) -> Result<()>;
+
+    /// False if a hardware switch blocks Wi-Fi.
+    #[zbus(property)]
+    fn wireless_hardware_enabled(&self) -> Result<bool>;
+
+    #[zbus(property)]
+    fn wwan_enabled(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn set_wwan_enabled(&self, value: bool) -> Result<()>;
+
+    /// False if a hardware switch blocks mobile broadband.
+    #[zbus(property)]
+    fn wwan_hardware_enabled(&self) -> Result<bool>;
+}
+
+/// See https://networkmanager.dev/docs/api/latest/gdbus-org.freedesktop.NetworkManager.Device.html
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Device"
+)]
+pub(super) trait Device {
+    fn disconnect(&self) -> Result<()>;
+
+    #[zbus(property)]
+    fn interface(&self) -> Result<String>;
+
+    /// The interface carrying IP traffic, which differs from `interface` for things like modems using ppp0.
+    #[zbus(property)]
+    fn ip_interface(&self) -> Result<String>;
+
+    /// An NMDeviceType, like 1 for ethernet or 2 for Wi-Fi.
+    #[zbus(property)]
+    fn device_type(&self) -> Result<u32>;
+
+    /// An NMDeviceState, like 100 for activated.
+    #[zbus(property)]
+    fn state(&self) -> Result<u32>;
+
+    #[zbus(property)]
+    fn active_connection(&self) -> Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn hw_address(&self) -> Result<String>;
+
+    /// The current IPv4 configuration, or "/" if the device isn't activated.
+    #[zbus(property)]
+    fn ip4_config(&self) -> Result<OwnedObjectPath>;
+
+    /// The current IPv6 configuration, or "/" if the device isn't activated.
+    #[zbus(property)]
+    fn ip6_config(&self) -> Result<OwnedObjectPath>;
+}
+
+pub(super) const DEVICE_TYPE_WIFI: u32 = 2;
+
+/// A list of dictionaries, like `[{ "address": "192.168.1.2", "prefix": 24 }]`.
+pub(super) type AddressData = Vec<HashMap<String, OwnedValue>>;
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.IP4Config"
+)]
+pub(super) trait Ip4Config {
+    #[zbus(property)]
+    fn address_data(&self) -> Result<AddressData>;
+
+    #[zbus(property)]
+    fn gateway(&self) -> Result<String>;
+
+    #[zbus(property)]
+    fn nameserver_data(&self) -> Result<AddressData>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.IP6Config"
+)]
+pub(super) trait Ip6Config {
+    #[zbus(property)]
+    fn address_data(&self) -> Result<AddressData>;
+
+    #[zbus(property)]
+    fn gateway(&self) -> Result<String>;
+
+    /// Each nameserver is 16 raw address bytes.
+    #[zbus(property)]
+    fn nameservers(&self) -> Result<Vec<Vec<u8>>>;
+}
+
+pub(super) const ACTIVE_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// See https://networkmanager.dev/docs/api/latest/gdbus-org.freedesktop.NetworkManager.Connection.Active.html
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Connection.Active"
+)]
+pub(super) trait ActiveConnection {
+    #[zbus(property)]
+    fn id(&self) -> Result<String>;
+
+    #[zbus(property)]
+    fn uuid(&self) -> Result<String>;
+
+    /// The connection type, like "802-11-wireless" or "vpn".
+    #[zbus(property)]
+    fn type_(&self) -> Result<String>;
+
+    /// An NMActiveConnectionState: 0 unknown, 1 activating, 2 activated, 3 deactivating, 4 deactivated.
+    #[zbus(property)]
+    fn state(&self) -> Result<u32>;
+
+    /// The access point for Wi-Fi connections, or "/".
+    #[zbus(property)]
+    fn specific_object(&self) -> Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn devices(&self) -> Result<Vec<OwnedObjectPath>>;
+
+    /// The saved connection this was activated from.
+    #[zbus(property)]
+    fn connection(&self) -> Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Device.Wireless"
+)]
+pub(super) trait WirelessDevice {
+    fn request_scan(&self, options: HashMap<&str, Value<'_>>) -> Result<()>;
+
+    #[zbus(property)]
+    fn access_points(&self) -> Result<Vec<OwnedObjectPath>>;
+
+    #[zbus(property)]
+    fn active_access_point(&self) -> Result<OwnedObjectPath>;
+
+    /// CLOCK_BOOTTIME milliseconds of the last finished scan, or -1 if it never scanned.
+    #[zbus(property)]
+    fn last_scan(&self) -> Result<i64>;
+}
+
+/// See https://networkmanager.dev/docs/api/latest/gdbus-org.freedesktop.NetworkManager.AccessPoint.html
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.AccessPoint"
+)]
+pub(super) trait AccessPoint {
+    #[zbus(property)]
+    fn ssid(&self) -> Result<Vec<u8>>;
+
+    /// Signal strength as a percentage.
+    #[zbus(property)]
+    fn strength(&self) -> Result<u8>;
+
+    /// Frequency in MHz.
+    #[zbus(property)]
+    fn frequency(&self) -> Result<u32>;
+
+    #[zbus(property)]
+    fn hw_address(&self) -> Result<String>;
+
+    /// NM80211ApFlags.
+    #[zbus(property)]
+    fn flags(&self) -> Result<u32>;
+
+    /// NM80211ApSecurityFlags for WPA.
+    #[zbus(property)]
+    fn wpa_flags(&self) -> Result<u32>;
+
+    /// NM80211ApSecurityFlags for WPA2/RSN.
+    #[zbus(property)]
+    fn rsn_flags(&self) -> Result<u32>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+pub(super) trait Settings {
+    fn list_connections(&self) -> Result<Vec<OwnedObjectPath>>;
+
+    fn get_connection_by_uuid(&self, uuid: &str) -> Result<OwnedObjectPath>;
+
+    /// Saves a new connection to disk and returns its path.
+    fn add_connection(&self, connection: HashMap<&str, HashMap<&str, Value<'_>>>) -> Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn new_connection(&self, connection: ObjectPath<'_>) -> Result<()>;
+
+    #[zbus(signal)]
+    fn connection_removed(&self, connection: ObjectPath<'_>) -> Result<()>;
+}
+
+/// Connection settings, keyed by setting name and then property, like `settings["connection"]["id"]`.
+pub(super) type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+pub(super) const SETTINGS_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Settings.Connection"
+)]
+pub(super) trait SettingsConnection {
+    /// Emitted after the settings change. Every connection has its own object, so listen with a match rule
+    /// on [`SETTINGS_CONNECTION_INTERFACE`] to hear about all of them.
+    #[zbus(signal)]
+    fn updated(&self) -> Result<()>;
+
+    /// Returns the settings without secrets.
+    fn get_settings(&self) -> Result<ConnectionSettings>;
+
+    /// Replaces all of the connection's settings and saves them to disk.
+    fn update(&self, properties: HashMap<&str, HashMap<&str, Value<'_>>>) -> Result<()>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.AgentManager",
+    default_path = "/org/freedesktop/NetworkManager/AgentManager"
+)]
+pub(super) trait AgentManager {
+    /// Registers the caller as a secret agent under the given identifier, replacing any earlier registration.
+    fn register(&self, identifier: &str) -> Result<()>;
+
+    fn unregister(&self) -> Result<()>;
 }
@@ -0,0 +1,259 @@
+use std::{collections::HashMap, net::Ipv6Addr};
+
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+use zvariant::{OwnedObjectPath, OwnedValue};
+
+use crate::networkmanager::dbus::{AddressData, DeviceProxy, Ip4ConfigProxy, Ip6ConfigProxy, NetworkManagerDbusProxy};
+use crate::networkmanager::follow_networkmanager;
+use crate::BarHandler;
+
+/// See NMDeviceType. Less common virtual device types are reported as `Other`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkDevices.ts")]
+pub enum NetworkDeviceType {
+    Unknown,
+    Ethernet,
+    Wifi,
+    Bluetooth,
+    Modem,
+    Bond,
+    Vlan,
+    Bridge,
+    Tun,
+    Veth,
+    Wireguard,
+    WifiP2p,
+    Loopback,
+    Other,
+}
+
+impl From<u32> for NetworkDeviceType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => NetworkDeviceType::Unknown,
+            1 => NetworkDeviceType::Ethernet,
+            2 => NetworkDeviceType::Wifi,
+            5 => NetworkDeviceType::Bluetooth,
+            8 => NetworkDeviceType::Modem,
+            10 => NetworkDeviceType::Bond,
+            11 => NetworkDeviceType::Vlan,
+            13 => NetworkDeviceType::Bridge,
+            16 => NetworkDeviceType::Tun,
+            20 => NetworkDeviceType::Veth,
+            29 => NetworkDeviceType::Wireguard,
+            30 => NetworkDeviceType::WifiP2p,
+            32 => NetworkDeviceType::Loopback,
+            _ => NetworkDeviceType::Other,
+        }
+    }
+}
+
+/// See NMDeviceState.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkDevices.ts")]
+pub enum NetworkDeviceState {
+    Unknown,
+    /// NetworkManager doesn't manage this device.
+    Unmanaged,
+    /// The device can't be activated, e.g. because there's no cable or the radio is off.
+    Unavailable,
+    Disconnected,
+    /// Preparing, configuring, or getting an IP address.
+    Connecting,
+    /// Waiting for a password or other secret.
+    NeedAuth,
+    Activated,
+    Deactivating,
+    Failed,
+}
+
+impl From<u32> for NetworkDeviceState {
+    fn from(value: u32) -> Self {
+        match value {
+            10 => NetworkDeviceState::Unmanaged,
+            20 => NetworkDeviceState::Unavailable,
+            30 => NetworkDeviceState::Disconnected,
+            40 | 50 | 70 | 80 | 90 => NetworkDeviceState::Connecting,
+            60 => NetworkDeviceState::NeedAuth,
+            100 => NetworkDeviceState::Activated,
+            110 => NetworkDeviceState::Deactivating,
+            120 => NetworkDeviceState::Failed,
+            _ => NetworkDeviceState::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkDevices.ts")]
+pub struct IpAddress {
+    pub address: String,
+    pub prefix: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkDevices.ts")]
+pub struct IpConfig {
+    pub addresses: Vec<IpAddress>,
+    pub gateway: Option<String>,
+    pub dns: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkDevices.ts")]
+pub struct NetworkDevice {
+    /// The D-Bus object path of the device.
+    pub path: String,
+    /// The interface name, like "wlan0" or "enp3s0".
+    pub interface: String,
+    pub device_type: NetworkDeviceType,
+    pub state: NetworkDeviceState,
+    /// The MAC address, if the device has one.
+    pub hw_address: Option<String>,
+    /// Only set while the device is activated.
+    pub ipv4: Option<IpConfig>,
+    /// Only set while the device is activated.
+    pub ipv6: Option<IpConfig>,
+}
+
+#[derive(Default)]
+pub struct NetworkDevicesState {
+    devices: Vec<NetworkDevice>,
+}
+
+fn dict_get<T: TryFrom<OwnedValue>>(entry: &HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    entry.get(key).and_then(|value| value.try_clone().ok()).and_then(|value| T::try_from(value).ok())
+}
+
+fn parse_addresses(data: AddressData) -> Vec<IpAddress> {
+    data.iter()
+        .filter_map(|entry| Some(IpAddress {
+            address: dict_get(entry, "address")?,
+            prefix: dict_get(entry, "prefix").unwrap_or(0),
+        }))
+        .collect()
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+async fn read_ip4_config(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<IpConfig> {
+    let config = Ip4ConfigProxy::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(IpConfig {
+        addresses: parse_addresses(config.address_data().await?),
+        gateway: non_empty(config.gateway().await?),
+        dns: config.nameserver_data().await?
+            .iter()
+            .filter_map(|entry| dict_get(entry, "address"))
+            .collect(),
+    })
+}
+
+async fn read_ip6_config(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<IpConfig> {
+    let config = Ip6ConfigProxy::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(IpConfig {
+        addresses: parse_addresses(config.address_data().await?),
+        gateway: non_empty(config.gateway().await?),
+        dns: config.nameservers().await?
+            .into_iter()
+            .filter_map(|bytes| <[u8; 16]>::try_from(bytes).ok())
+            .map(|bytes| Ipv6Addr::from(bytes).to_string())
+            .collect(),
+    })
+}
+
+async fn read_device(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<NetworkDevice> {
+    let device = DeviceProxy::builder(connection)
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let ip4_config = device.ip4_config().await?;
+    let ip6_config = device.ip6_config().await?;
+
+    Ok(NetworkDevice {
+        path: path.to_string(),
+        interface: device.interface().await?,
+        device_type: device.device_type().await?.into(),
+        state: device.state().await?.into(),
+        // Older NetworkManager versions don't have this on every device type
+        hw_address: device.hw_address().await.ok().and_then(non_empty),
+        ipv4: match ip4_config.as_str() {
+            "/" => None,
+            _ => Some(read_ip4_config(connection, ip4_config).await?),
+        },
+        ipv6: match ip6_config.as_str() {
+            "/" => None,
+            _ => Some(read_ip6_config(connection, ip6_config).await?),
+        },
+    })
+}
+
+async fn read_devices(connection: &Connection) -> zbus::Result<Vec<NetworkDevice>> {
+    let network_manager = NetworkManagerDbusProxy::new(connection).await?;
+
+    let mut devices = Vec::new();
+    for path in network_manager.devices().await? {
+        // Devices can vanish while we're reading them
+        match read_device(connection, path.into()).await {
+            Ok(device) if device.device_type != NetworkDeviceType::Loopback => devices.push(device),
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to read network device: {}", e),
+        }
+    }
+    devices.sort_by(|a, b| a.interface.cmp(&b.interface));
+    Ok(devices)
+}
+
+impl BarHandler {
+    /// Tracks every network device with its addresses.
+    pub fn start_network_device_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(NetworkDevicesState::default()));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                follow_networkmanager(&connection, "devices", || {
+                    let (connection, app_handle) = (&connection, &app_handle);
+                    async move {
+                        let devices = read_devices(connection).await?;
+
+                        let state = app_handle.state::<Mutex<NetworkDevicesState>>();
+                        let mut state = state.lock().await;
+                        if state.devices != devices {
+                            state.devices = devices.clone();
+                            let _ = app_handle.emit("network_devices_changed", devices);
+                        }
+                        Ok(())
+                    }
+                }).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track network devices: {}", e);
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn get_network_devices(state: State<'_, Mutex<NetworkDevicesState>>) -> Result<Vec<NetworkDevice>, ()> {
+    Ok(state.lock().await.devices.clone())
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions, Permissions},
+    io::{self, Read, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+use zvariant::{ObjectPath, Value};
+
+use crate::config::HotspotConfig;
+use crate::networkmanager::connections::active_connections;
+use crate::networkmanager::dbus::{
+    ActiveConnectionProxy, DeviceProxy, NetworkManagerDbusProxy, SettingsConnectionProxy, SettingsProxy,
+};
+use crate::networkmanager::{follow_networkmanager, ActivationState};
+use crate::BarHandler;
+
+/// NetworkManager doesn't signal new DHCP leases, so check for new clients this often.
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Characters for generated passwords, without ones that are easy to mix up when typing them on a phone.
+const PASSWORD_CHARACTERS: &[u8] = b"abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const PASSWORD_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Hotspot.ts")]
+pub struct HotspotClient {
+    pub mac_address: String,
+    pub ip_address: String,
+    pub hostname: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Hotspot.ts")]
+pub struct Hotspot {
+    /// The state of the hotspot connection, or None if it isn't active.
+    pub state: Option<ActivationState>,
+    /// The interface the hotspot is running on, while it's active.
+    pub interface: Option<String>,
+    /// None until a hotspot has been created.
+    pub ssid: Option<String>,
+    pub password: Option<String>,
+    /// Devices that got an address from the hotspot, or None if NetworkManager's lease file can't be read.
+    pub clients: Option<Vec<HotspotClient>>,
+}
+
+#[derive(Clone, Debug, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/Hotspot.ts")]
+pub struct HotspotRequest {
+    /// The D-Bus object path of the Wi-Fi device to run the hotspot on.
+    pub device: String,
+    /// Overrides the configured or remembered network name.
+    pub ssid: Option<String>,
+    /// Overrides the configured or remembered password.
+    pub password: Option<String>,
+}
+
+/// The hotspot connection we created, remembered between runs so it's reused rather than duplicated.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct HotspotProfile {
+    uuid: String,
+    ssid: String,
+    password: String,
+}
+
+impl HotspotProfile {
+    fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("desktop").join("hotspot.json"))
+    }
+
+    fn load() -> Option<Self> {
+        let path = Self::path()?;
+        let contents = fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&contents)
+            .map_err(|e| eprintln!("Failed to parse hotspot profile at {}: {}", path.display(), e))
+            .ok()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| write_private(&path, &json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Failed to save hotspot profile to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Writes a file only the user can read, since the profile holds the hotspot's password.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    // The mode only applies to new files, and older versions saved the profile readable by everyone
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+pub struct HotspotState {
+    config: HotspotConfig,
+    profile: Option<HotspotProfile>,
+    hotspot: Hotspot,
+}
+
+fn random_bytes<const N: usize>() -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn generate_password() -> Result<String, String> {
+    let bytes = random_bytes::<PASSWORD_LENGTH>().map_err(|e| format!("Failed to generate a password: {}", e))?;
+    Ok(bytes.iter()
+        .map(|byte| PASSWORD_CHARACTERS[*byte as usize % PASSWORD_CHARACTERS.len()] as char)
+        .collect())
+}
+
+/// A random (version 4) UUID for a new connection.
+fn generate_uuid() -> Result<String, String> {
+    let mut bytes = random_bytes::<16>().map_err(|e| format!("Failed to generate a UUID: {}", e))?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    Ok(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32]))
+}
+
+fn default_ssid() -> String {
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    match hostname.trim() {
+        "" => "Hotspot".to_string(),
+        hostname => format!("{}-hotspot", hostname),
+    }
+}
+
+/// Reads the DHCP leases handed out by the dnsmasq instance NetworkManager runs for shared connections.
+fn read_clients(interface: &str) -> Option<Vec<HotspotClient>> {
+    let leases = fs::read_to_string(format!("/var/lib/NetworkManager/dnsmasq-{}.leases", interface)).ok()?;
+
+    // Each line is "<expiry> <mac> <ip> <hostname or *> <client id>"
+    Some(leases.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            Some(HotspotClient {
+                mac_address: fields.next()?.to_string(),
+                ip_address: fields.next()?.to_string(),
+                hostname: fields.next().filter(|hostname| *hostname != "*").map(str::to_string),
+            })
+        })
+        .collect())
+}
+
+async fn read_hotspot(connection: &Connection, profile: Option<&HotspotProfile>) -> zbus::Result<Hotspot> {
+    let Some(profile) = profile else {
+        return Ok(Hotspot::default());
+    };
+
+    let mut hotspot = Hotspot {
+        ssid: Some(profile.ssid.clone()),
+        password: Some(profile.password.clone()),
+        ..Hotspot::default()
+    };
+
+    let active = active_connections(connection).await?;
+    let Some((path, state)) = active.get(&profile.uuid) else {
+        return Ok(hotspot);
+    };
+    hotspot.state = Some(*state);
+
+    let active = ActiveConnectionProxy::builder(connection)
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    if let Some(device) = active.devices().await?.into_iter().next() {
+        let device = DeviceProxy::builder(connection)
+            .path(device)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let interface = device.interface().await?;
+        hotspot.clients = read_clients(&interface);
+        hotspot.interface = Some(interface);
+    }
+    Ok(hotspot)
+}
+
+async fn publish_hotspot<R: Runtime>(app_handle: &AppHandle<R>, connection: &Connection) -> zbus::Result<()> {
+    let state = app_handle.state::<Mutex<HotspotState>>();
+    let profile = state.lock().await.profile.clone();
+    let hotspot = read_hotspot(connection, profile.as_ref()).await?;
+
+    let mut state = state.lock().await;
+    if state.hotspot != hotspot {
+        state.hotspot = hotspot.clone();
+        let _ = app_handle.emit("hotspot_changed", hotspot);
+    }
+    Ok(())
+}
+
+/// The full settings for an access point connection that shares our connection with its clients.
+fn hotspot_settings<'a>(
+    profile: &'a HotspotProfile,
+    interface: &'a str,
+    band: Option<&'a str>,
+) -> HashMap<&'static str, HashMap<&'static str, Value<'a>>> {
+    let mut wireless = HashMap::from([
+        ("ssid", Value::from(profile.ssid.as_bytes())),
+        ("mode", Value::from("ap")),
+    ]);
+    if let Some(band) = band {
+        wireless.insert("band", Value::from(band));
+    }
+
+    HashMap::from([
+        ("connection", HashMap::from([
+            ("id", Value::from("Hotspot")),
+            ("uuid", Value::from(profile.uuid.as_str())),
+            ("type", Value::from("802-11-wireless")),
+            ("interface-name", Value::from(interface)),
+            ("autoconnect", Value::from(false)),
+        ])),
+        ("802-11-wireless", wireless),
+        ("802-11-wireless-security", HashMap::from([
+            ("key-mgmt", Value::from("wpa-psk")),
+            ("psk", Value::from(profile.password.as_str())),
+            ("proto", Value::from(vec!["rsn"])),
+            ("pairwise", Value::from(vec!["ccmp"])),
+            ("group", Value::from(vec!["ccmp"])),
+        ])),
+        ("ipv4", HashMap::from([("method", Value::from("shared"))])),
+        ("ipv6", HashMap::from([("method", Value::from("ignore"))])),
+    ])
+}
+
+impl BarHandler {
+    /// Tracks the hotspot connection and its clients.
+    pub fn start_hotspot_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(HotspotState {
+            config: self.config.hotspot.clone(),
+            profile: HotspotProfile::load(),
+            hotspot: Hotspot::default(),
+        }));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+
+                let (app_handle_, connection_) = (app_handle.clone(), connection.clone());
+                tauri::async_runtime::spawn(async move {
+                    let mut interval = tokio::time::interval(CLIENT_POLL_INTERVAL);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = publish_hotspot(&app_handle_, &connection_).await {
+                            eprintln!("Failed to read hotspot: {}", e);
+                        }
+                    }
+                });
+
+                follow_networkmanager(&connection, "hotspot", || publish_hotspot(&app_handle, &connection)).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track hotspot: {}", e);
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn get_hotspot(state: State<'_, Mutex<HotspotState>>) -> Result<Hotspot, ()> {
+    Ok(state.lock().await.hotspot.clone())
+}
+
+/// Starts the hotspot on a device, creating its connection the first time.
+/// The network name and password come from the request, then the config, then the last hotspot.
+#[tauri::command]
+pub async fn start_hotspot(payload: HotspotRequest, state: State<'_, Mutex<HotspotState>>) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let device_path = ObjectPath::try_from(payload.device.as_str()).map_err(|e| e.to_string())?;
+    let device = DeviceProxy::builder(&connection)
+        .path(device_path.clone())
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+    let interface = device.interface().await.map_err(|e| e.to_string())?;
+
+    // Work out the profile under the lock, but don't hold it across the D-Bus calls
+    let (previous, ssid, password, band) = {
+        let state = state.lock().await;
+        let previous = state.profile.clone();
+        let password = match payload.password.or(state.config.password.clone()) {
+            Some(password) => password,
+            None => match &previous {
+                Some(previous) => previous.password.clone(),
+                None => generate_password()?,
+            },
+        };
+        let ssid = payload.ssid
+            .or(state.config.ssid.clone())
+            .or(previous.as_ref().map(|previous| previous.ssid.clone()))
+            .unwrap_or_else(default_ssid);
+        (previous, ssid, password, state.config.band.clone())
+    };
+    if !(8..=63).contains(&password.len()) {
+        return Err("The hotspot password has to be between 8 and 63 characters".to_string());
+    }
+
+    let settings_proxy = SettingsProxy::new(&connection).await.map_err(|e| e.to_string())?;
+    // The remembered connection may have been deleted outside the bar
+    let existing = match &previous {
+        Some(previous) => settings_proxy.get_connection_by_uuid(&previous.uuid).await.ok(),
+        None => None,
+    };
+
+    let profile = HotspotProfile {
+        uuid: match (&previous, &existing) {
+            (Some(previous), Some(_)) => previous.uuid.clone(),
+            _ => generate_uuid()?,
+        },
+        ssid,
+        password,
+    };
+    let settings = hotspot_settings(&profile, &interface, band.as_deref());
+
+    let connection_path = match existing {
+        Some(path) => {
+            let settings_connection = SettingsConnectionProxy::builder(&connection)
+                .path(path.clone())
+                .map_err(|e| e.to_string())?
+                .build()
+                .await
+                .map_err(|e| e.to_string())?;
+            settings_connection.update(settings).await.map_err(|e| e.to_string())?;
+            path
+        }
+        None => settings_proxy.add_connection(settings).await.map_err(|e| e.to_string())?,
+    };
+
+    profile.save();
+    state.lock().await.profile = Some(profile);
+
+    let network_manager = NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())?;
+    network_manager.activate_connection(&connection_path, &device_path, &ObjectPath::from_static_str_unchecked("/"))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_hotspot(state: State<'_, Mutex<HotspotState>>) -> Result<(), String> {
+    let Some(profile) = state.lock().await.profile.clone() else {
+        return Ok(());
+    };
+
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let active = active_connections(&connection).await.map_err(|e| e.to_string())?;
+    let Some((path, _)) = active.get(&profile.uuid) else {
+        return Ok(());
+    };
+
+    let network_manager = NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())?;
+    network_manager.deactivate_connection(path).await.map_err(|e| e.to_string())
+}
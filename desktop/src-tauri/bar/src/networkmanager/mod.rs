@@ -1,27 +1,83 @@
-use futures::StreamExt;
+use std::{future::Future, time::Duration};
+
+use futures::{FutureExt, StreamExt};
 use serde::Serialize;
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
 use tauri::async_runtime::Mutex;
 use ts_rs::TS;
-use zbus::export::ordered_stream::OrderedStreamExt;
-use zbus::fdo::PropertiesProxy;
+use zbus::proxy::CacheProperties;
 use zbus::{
     Connection,
-    names::InterfaceName
+    MatchRule,
+    MessageStream,
+    message,
 };
+use zvariant::OwnedObjectPath;
 
-use crate::networkmanager::dbus::{NetworkManagerDbusProxy, DBUS_BUS, DBUS_INTERFACE, DBUS_PATH};
+use crate::networkmanager::dbus::{AccessPointProxy, ActiveConnectionProxy, NetworkManagerDbusProxy, DBUS_BUS};
 use crate::BarHandler;
 
+mod connections;
 mod dbus;
+mod devices;
+mod hotspot;
+mod radios;
+mod secret_agent;
+mod throughput;
+mod wifi;
+
+pub use connections::{activate_saved_connection, deactivate_saved_connection, get_saved_connections};
+pub use devices::get_network_devices;
+pub use hotspot::{get_hotspot, start_hotspot, stop_hotspot};
+pub use radios::{get_radio_state, set_airplane_mode, set_networking_enabled, set_wireless_enabled, set_wwan_enabled};
+pub use secret_agent::answer_secret_prompt;
+pub use throughput::get_network_throughput;
+pub use wifi::{connect_wifi, disconnect_wifi, get_wifi_devices, request_wifi_scan};
+
+/// How long to wait for a burst of NetworkManager signals to settle before refreshing.
+const COALESCE_DELAY: Duration = Duration::from_millis(250);
+
+/// Calls `refresh` once up front and again after every burst of signals from NetworkManager.
+/// NetworkManager sends many small changes at once, like while scanning or activating a connection,
+/// so they're coalesced into a single refresh. Refresh errors are logged rather than ending the loop,
+/// since objects can disappear while they're being read.
+pub(super) async fn follow_networkmanager<F, Fut>(connection: &Connection, name: &str, mut refresh: F) -> zbus::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = zbus::Result<()>>,
+{
+    let rule = MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .sender(DBUS_BUS)?
+        .build();
+    let mut signals = MessageStream::for_match_rule(rule, connection, None).await?;
+
+    if let Err(e) = refresh().await {
+        eprintln!("Failed to read NetworkManager {}: {}", name, e);
+    }
+
+    while signals.next().await.is_some() {
+        tokio::time::sleep(COALESCE_DELAY).await;
+        while let Some(Some(_)) = signals.next().now_or_never() {}
 
-#[derive(Clone, Debug, Serialize, TS)]
+        if let Err(e) = refresh().await {
+            eprintln!("Failed to read NetworkManager {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
 #[ts(export, export_to="../../bindings/NetworkManagerState.ts")]
 pub struct NetworkManagerState {
     pub status: NetworkStatus,
+    /// Whether the internet is actually reachable over the primary connection.
+    pub connectivity: Connectivity,
+    /// The connection being activated or, if there isn't one, the primary connection.
+    pub connection: Option<ActiveConnectionInfo>,
 }
 
-#[derive(Clone, Debug, Serialize, TS)]
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
 #[ts(export, export_to="../../bindings/NetworkManagerState.ts")]
 pub enum NetworkStatus {
     WiredConnected,
@@ -33,10 +89,133 @@ pub enum NetworkStatus {
     Unknown,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkManagerState.ts")]
+pub enum Connectivity {
+    Unknown,
+    /// Not connected to any network.
+    None,
+    /// Behind a captive portal that needs a login.
+    Portal,
+    /// Connected to a network, but it can't reach the internet.
+    Limited,
+    Full,
+}
+
+impl From<u32> for Connectivity {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Connectivity::None,
+            2 => Connectivity::Portal,
+            3 => Connectivity::Limited,
+            4 => Connectivity::Full,
+            _ => Connectivity::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkManagerState.ts")]
+pub enum ActivationState {
+    Unknown,
+    Activating,
+    Activated,
+    Deactivating,
+    Deactivated,
+}
+
+impl From<u32> for ActivationState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => ActivationState::Activating,
+            2 => ActivationState::Activated,
+            3 => ActivationState::Deactivating,
+            4 => ActivationState::Deactivated,
+            _ => ActivationState::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkManagerState.ts")]
+pub struct ActiveConnectionInfo {
+    /// The user-visible connection name.
+    pub name: String,
+    /// The connection type, like "802-11-wireless" or "vpn".
+    pub connection_type: String,
+    pub state: ActivationState,
+    /// The network name, for Wi-Fi connections.
+    pub ssid: Option<String>,
+    /// Signal strength as a percentage, for Wi-Fi connections.
+    pub strength: Option<u8>,
+}
+
+async fn read_active_connection(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<ActiveConnectionInfo> {
+    let active = ActiveConnectionProxy::builder(connection)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let mut info = ActiveConnectionInfo {
+        name: active.id().await?,
+        connection_type: active.type_().await?,
+        state: active.state().await?.into(),
+        ssid: None,
+        strength: None,
+    };
+
+    let access_point = active.specific_object().await?;
+    if info.connection_type == "802-11-wireless" && access_point.as_str() != "/" {
+        let access_point = AccessPointProxy::builder(connection)
+            .path(access_point)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        info.ssid = Some(String::from_utf8_lossy(&access_point.ssid().await?).into_owned());
+        info.strength = Some(access_point.strength().await?);
+    }
+    Ok(info)
+}
+
+async fn read_state(connection: &Connection) -> zbus::Result<NetworkManagerState> {
+    let proxy = NetworkManagerDbusProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let primary_connection = proxy.primary_connection().await?;
+    let primary_connection_type = proxy.primary_connection_type().await?;
+    let wireless_enabled = proxy.wireless_enabled().await?;
+
+    // Show the connection being activated so the bar can say it's connecting
+    let activating_connection = proxy.activating_connection().await?;
+    let connection_path = if activating_connection.as_str() != "/" {
+        Some(activating_connection)
+    } else if primary_connection.as_str() != "/" {
+        Some(OwnedObjectPath::from(primary_connection.clone()))
+    } else {
+        None
+    };
+
+    let active_connection = match connection_path {
+        Some(path) => Some(read_active_connection(connection, path).await?),
+        None => None,
+    };
+
+    Ok(NetworkManagerState {
+        status: determine_state(&primary_connection, &primary_connection_type, wireless_enabled),
+        connectivity: proxy.connectivity().await?.into(),
+        connection: active_connection,
+    })
+}
+
 impl BarHandler {
     pub fn start_networkmanager_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
         let state = Mutex::new(NetworkManagerState {
             status: NetworkStatus::Unknown,
+            connectivity: Connectivity::Unknown,
+            connection: None,
         });
         app_handle.manage(state);
 
@@ -53,66 +232,25 @@ impl BarHandler {
 
     async fn start_networkmanager_handler<R: Runtime>(app_handle: AppHandle<R>) -> zbus::Result<()> {
         let dbus_connection = Connection::system().await?;
-        let interface_name = InterfaceName::from_static_str(DBUS_INTERFACE)?;
-        let props_proxy = PropertiesProxy::builder(&dbus_connection)
-            .destination(DBUS_BUS)?
-            .path(DBUS_PATH)?
-            .build()
-            .await?;
-        
-        let proxy = NetworkManagerDbusProxy::new(&dbus_connection).await?;
-
-        let mut primary_connection = proxy.primary_connection().await?;
-        let mut primary_connection_type = proxy.primary_connection_type().await?;
-        let mut wireless_enabled = proxy.wireless_enabled().await?;
-
-        {
-            let state = app_handle.state::<Mutex<NetworkManagerState>>();
-            let mut state = state.lock().await;
-            state.status = determine_state(
-                &primary_connection,
-                &primary_connection_type,
-                wireless_enabled,
-            );
-        }
 
-        let stream = props_proxy.receive_properties_changed().await?;
-        let mut stream = stream.into_stream();
-        
-        while let Some(change) = stream.next().await {
-            let args = change.args()?;
-            if args.interface_name != interface_name {
-                continue;
-            }
+        // Signal strength and activation progress live on other objects than the manager itself,
+        // so we re-read everything on any change. This also emits the initial state.
+        let mut first = true;
+        follow_networkmanager(&dbus_connection, "state", || {
+            let (dbus_connection, app_handle) = (&dbus_connection, &app_handle);
+            let first = std::mem::replace(&mut first, false);
+            async move {
+                let new_state = read_state(dbus_connection).await?;
 
-            let changed_props = &args.changed_properties;
-            let mut relevant_prop_changed = false;
-            
-            if changed_props.contains_key("PrimaryConnection") {
-                primary_connection = proxy.primary_connection().await?;
-                relevant_prop_changed = true;
-            }
-            if changed_props.contains_key("PrimaryConnectionType") {
-                primary_connection_type = proxy.primary_connection_type().await?;
-                relevant_prop_changed = true;
-            }
-            if changed_props.contains_key("WirelessEnabled") {
-                wireless_enabled = proxy.wireless_enabled().await?;
-                relevant_prop_changed = true;
-            }
-
-            if relevant_prop_changed {
                 let state = app_handle.state::<Mutex<NetworkManagerState>>();
                 let mut state = state.lock().await;
-                state.status = determine_state(
-                    &primary_connection,
-                    &primary_connection_type,
-                    wireless_enabled,
-                );
-                let _ = app_handle.emit("networkmanager_state_changed", state.clone());
+                if first || *state != new_state {
+                    *state = new_state;
+                    let _ = app_handle.emit("networkmanager_state_changed", state.clone());
+                }
+                Ok(())
             }
-        }
-        Ok(())
+        }).await
     }
 }
 
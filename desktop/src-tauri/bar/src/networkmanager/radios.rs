@@ -0,0 +1,162 @@
+use futures::channel::mpsc;
+use futures::StreamExt;
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+
+use crate::networkmanager::dbus::NetworkManagerDbusProxy;
+use crate::networkmanager::follow_networkmanager;
+use crate::rfkill::{self, RfkillDevice, RfkillType};
+use crate::BarHandler;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/RadioState.ts")]
+pub struct Radio {
+    pub enabled: bool,
+    /// False if a hardware switch blocks the radio, in which case enabling it does nothing.
+    pub hardware_enabled: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/RadioState.ts")]
+pub struct RadioState {
+    pub networking_enabled: bool,
+    pub wifi: Radio,
+    /// Mobile broadband.
+    pub wwan: Radio,
+    /// None if there's no Bluetooth adapter.
+    pub bluetooth: Option<Radio>,
+    /// Whether every radio is off.
+    pub airplane_mode: bool,
+    pub rfkill: Vec<RfkillDevice>,
+}
+
+fn bluetooth_radio(devices: &[RfkillDevice]) -> Option<Radio> {
+    let mut adapters = devices.iter().filter(|device| device.device_type == RfkillType::Bluetooth).peekable();
+    adapters.peek()?;
+
+    let mut radio = Radio { enabled: false, hardware_enabled: true };
+    for adapter in adapters {
+        radio.enabled |= !adapter.soft_blocked && !adapter.hard_blocked;
+        radio.hardware_enabled &= !adapter.hard_blocked;
+    }
+    Some(radio)
+}
+
+async fn read_radio_state(connection: &Connection) -> zbus::Result<RadioState> {
+    let network_manager = NetworkManagerDbusProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let rfkill = rfkill::read_devices();
+    let wifi = Radio {
+        enabled: network_manager.wireless_enabled().await?,
+        hardware_enabled: network_manager.wireless_hardware_enabled().await?,
+    };
+    let wwan = Radio {
+        enabled: network_manager.wwan_enabled().await?,
+        hardware_enabled: network_manager.wwan_hardware_enabled().await?,
+    };
+    let bluetooth = bluetooth_radio(&rfkill);
+
+    Ok(RadioState {
+        networking_enabled: network_manager.networking_enabled().await?,
+        airplane_mode: !wifi.enabled && !wwan.enabled && bluetooth.is_none_or(|bluetooth| !bluetooth.enabled),
+        wifi,
+        wwan,
+        bluetooth,
+        rfkill,
+    })
+}
+
+async fn publish_radio_state<R: Runtime>(app_handle: &AppHandle<R>, connection: &Connection) -> zbus::Result<()> {
+    let radios = read_radio_state(connection).await?;
+
+    let state = app_handle.state::<Mutex<RadioState>>();
+    let mut state = state.lock().await;
+    if *state != radios {
+        *state = radios.clone();
+        let _ = app_handle.emit("radio_state_changed", radios);
+    }
+    Ok(())
+}
+
+impl BarHandler {
+    /// Tracks NetworkManager's radio switches and the kernel's rfkill state.
+    pub fn start_radio_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(RadioState::default()));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+
+                // NetworkManager doesn't know about Bluetooth, so watch rfkill as well
+                let (sender, mut rfkill_events) = mpsc::unbounded();
+                tauri::async_runtime::spawn_blocking(move || {
+                    if let Err(e) = rfkill::watch(|| sender.unbounded_send(()).is_ok()) {
+                        eprintln!("Failed to watch rfkill: {}", e);
+                    }
+                });
+                let (app_handle_, connection_) = (app_handle.clone(), connection.clone());
+                tauri::async_runtime::spawn(async move {
+                    while rfkill_events.next().await.is_some() {
+                        if let Err(e) = publish_radio_state(&app_handle_, &connection_).await {
+                            eprintln!("Failed to read radio state: {}", e);
+                        }
+                    }
+                });
+
+                follow_networkmanager(&connection, "radios", || publish_radio_state(&app_handle, &connection)).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track radios: {}", e);
+            }
+        });
+    }
+}
+
+async fn network_manager() -> Result<NetworkManagerDbusProxy<'static>, String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_radio_state(state: State<'_, Mutex<RadioState>>) -> Result<RadioState, ()> {
+    Ok(state.lock().await.clone())
+}
+
+#[tauri::command]
+pub async fn set_wireless_enabled(payload: bool) -> Result<(), String> {
+    network_manager().await?.set_wireless_enabled(payload).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_wwan_enabled(payload: bool) -> Result<(), String> {
+    network_manager().await?.set_wwan_enabled(payload).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_networking_enabled(payload: bool) -> Result<(), String> {
+    network_manager().await?.enable(payload).await.map_err(|e| e.to_string())
+}
+
+/// Turns Wi-Fi, mobile broadband and Bluetooth off, or back on.
+#[tauri::command]
+pub async fn set_airplane_mode(payload: bool) -> Result<(), String> {
+    let network_manager = network_manager().await?;
+    network_manager.set_wireless_enabled(!payload).await.map_err(|e| e.to_string())?;
+    network_manager.set_wwan_enabled(!payload).await.map_err(|e| e.to_string())?;
+
+    // Without an adapter there's nothing to block, and /dev/rfkill may not even exist
+    if bluetooth_radio(&rfkill::read_devices()).is_some() {
+        rfkill::set_soft_blocked(RfkillType::Bluetooth, payload)
+            .map_err(|e| format!("Failed to block Bluetooth: {}", e))?;
+    }
+    Ok(())
+}
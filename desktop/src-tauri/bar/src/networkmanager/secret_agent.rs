@@ -0,0 +1,281 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures::channel::{mpsc, oneshot};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::export::ordered_stream::OrderedStreamExt;
+use zbus::fdo::DBusProxy;
+use zbus::{interface, Connection};
+use zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::networkmanager::dbus::{AgentManagerProxy, ConnectionSettings, DBUS_BUS};
+use crate::BarHandler;
+
+const AGENT_IDENTIFIER: &str = "desktop.bar";
+const AGENT_PATH: &str = "/org/freedesktop/NetworkManager/SecretAgent";
+
+/// NMSecretAgentGetSecretsFlags
+const ALLOW_INTERACTION: u32 = 0x1;
+const REQUEST_NEW: u32 = 0x2;
+
+/// A single value to ask the user for.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/SecretAgent.ts")]
+pub struct SecretField {
+    /// The setting property the answer is stored under, like "psk".
+    pub key: String,
+    pub label: String,
+    /// Whether the input should be hidden, which is false for usernames.
+    pub secret: bool,
+}
+
+/// NetworkManager needs secrets to activate a connection.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/SecretAgent.ts")]
+pub struct SecretPrompt {
+    /// Passed back with the answer.
+    pub id: u32,
+    /// The user-visible connection name.
+    pub connection_name: String,
+    /// The connection type, like "802-11-wireless" or "vpn".
+    pub connection_type: String,
+    /// The network name, for Wi-Fi connections.
+    pub ssid: Option<String>,
+    /// Whether the saved secrets were rejected, for example because the password was wrong.
+    pub retry: bool,
+    pub fields: Vec<SecretField>,
+}
+
+#[derive(Clone, Debug, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SecretAgent.ts")]
+pub struct SecretAnswer {
+    pub id: u32,
+    /// The values keyed by field, or None if the user cancelled.
+    pub secrets: Option<HashMap<String, String>>,
+}
+
+type Secrets = HashMap<String, String>;
+
+struct PendingPrompt {
+    id: u32,
+    connection_path: OwnedObjectPath,
+    setting_name: String,
+    answer: oneshot::Sender<Option<Secrets>>,
+}
+
+/// Prompts waiting for an answer from the frontend.
+#[derive(Default)]
+pub struct SecretPrompts {
+    next_id: u32,
+    pending: Vec<PendingPrompt>,
+}
+
+enum SecretAgentEvent {
+    Prompt(SecretPrompt),
+    Cancelled(u32),
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.NetworkManager.SecretAgent")]
+enum SecretAgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NoSecrets(String),
+    UserCanceled(String),
+    AgentCanceled(String),
+}
+
+fn setting<T: TryFrom<OwnedValue>>(settings: &ConnectionSettings, setting: &str, key: &str) -> Option<T> {
+    settings.get(setting)
+        .and_then(|setting| setting.get(key))
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
+
+fn field(key: &str, label: &str, secret: bool) -> SecretField {
+    SecretField { key: key.to_string(), label: label.to_string(), secret }
+}
+
+/// Works out which values to ask for. NetworkManager's hints name the properties it needs,
+/// but they're often empty, so fall back to what each kind of setting usually needs.
+fn secret_fields(settings: &ConnectionSettings, setting_name: &str, hints: &[String]) -> Vec<SecretField> {
+    // VPN plugins can pass messages for the user as hints, which aren't properties
+    let hints = hints.iter()
+        .filter(|hint| !hint.starts_with("x-vpn-message:"))
+        .map(|hint| field(hint, if hint == "password" { "Password" } else { hint }, true))
+        .collect::<Vec<_>>();
+    if !hints.is_empty() {
+        return hints;
+    }
+
+    match setting_name {
+        "802-11-wireless-security" => match setting::<String>(settings, setting_name, "key-mgmt").as_deref() {
+            Some("none") => vec![field("wep-key0", "Password", true)],
+            _ => vec![field("psk", "Password", true)],
+        },
+        "802-1x" => vec![field("identity", "Username", false), field("password", "Password", true)],
+        _ => vec![field("password", "Password", true)],
+    }
+}
+
+/// Puts the answers where NetworkManager expects them. VPN secrets are a nested dictionary
+/// rather than properties of the setting.
+fn secrets_to_settings(setting_name: &str, secrets: Secrets) -> HashMap<String, HashMap<String, Value<'static>>> {
+    let setting = if setting_name == "vpn" {
+        HashMap::from([("secrets".to_string(), Value::from(secrets))])
+    } else {
+        secrets.into_iter().map(|(key, value)| (key, Value::from(value))).collect()
+    };
+    HashMap::from([(setting_name.to_string(), setting)])
+}
+
+/// Implements NetworkManager's secret agent interface by forwarding requests to the frontend.
+/// See https://networkmanager.dev/docs/api/latest/gdbus-org.freedesktop.NetworkManager.SecretAgent.html
+struct SecretAgent {
+    prompts: Arc<Mutex<SecretPrompts>>,
+    events: mpsc::UnboundedSender<SecretAgentEvent>,
+}
+
+#[interface(name = "org.freedesktop.NetworkManager.SecretAgent")]
+impl SecretAgent {
+    async fn get_secrets(
+        &self,
+        connection: ConnectionSettings,
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+        hints: Vec<String>,
+        flags: u32,
+    ) -> Result<HashMap<String, HashMap<String, Value<'static>>>, SecretAgentError> {
+        // We don't store secrets ourselves, so there's nothing to give without asking
+        if flags & ALLOW_INTERACTION == 0 {
+            return Err(SecretAgentError::NoSecrets("Secrets can only be entered interactively".to_string()));
+        }
+
+        let (answer, receiver) = oneshot::channel();
+        let prompt = {
+            let mut prompts = self.prompts.lock().await;
+            let id = prompts.next_id;
+            prompts.next_id = prompts.next_id.wrapping_add(1);
+            prompts.pending.push(PendingPrompt {
+                id,
+                connection_path,
+                setting_name: setting_name.clone(),
+                answer,
+            });
+
+            SecretPrompt {
+                id,
+                connection_name: setting(&connection, "connection", "id").unwrap_or_default(),
+                connection_type: setting(&connection, "connection", "type").unwrap_or_default(),
+                ssid: setting::<Vec<u8>>(&connection, "802-11-wireless", "ssid")
+                    .map(|ssid| String::from_utf8_lossy(&ssid).into_owned()),
+                retry: flags & REQUEST_NEW != 0,
+                fields: secret_fields(&connection, &setting_name, &hints),
+            }
+        };
+        let _ = self.events.unbounded_send(SecretAgentEvent::Prompt(prompt));
+
+        match receiver.await {
+            Ok(Some(secrets)) => Ok(secrets_to_settings(&setting_name, secrets)),
+            Ok(None) => Err(SecretAgentError::UserCanceled("The user cancelled the prompt".to_string())),
+            // The sender is dropped without an answer when NetworkManager cancels the request
+            Err(_) => Err(SecretAgentError::AgentCanceled("The request was cancelled".to_string())),
+        }
+    }
+
+    async fn cancel_get_secrets(&self, connection_path: OwnedObjectPath, setting_name: String) {
+        let mut prompts = self.prompts.lock().await;
+        prompts.pending.retain(|prompt| {
+            let cancelled = prompt.connection_path == connection_path && prompt.setting_name == setting_name;
+            if cancelled {
+                let _ = self.events.unbounded_send(SecretAgentEvent::Cancelled(prompt.id));
+            }
+            !cancelled
+        });
+    }
+
+    /// NetworkManager keeps secrets in its own storage, so there's nothing to save.
+    async fn save_secrets(&self, _connection: ConnectionSettings, _connection_path: OwnedObjectPath) {}
+
+    async fn delete_secrets(&self, _connection: ConnectionSettings, _connection_path: OwnedObjectPath) {}
+}
+
+/// Registers the agent, and registers it again whenever NetworkManager restarts since it forgets its agents.
+async fn register_agent(connection: &Connection) -> zbus::Result<()> {
+    let dbus = DBusProxy::new(connection).await?;
+    let agent_manager = AgentManagerProxy::new(connection).await?;
+
+    // Subscribe before registering so we can't miss a restart in between
+    let mut owner_changes = dbus
+        .receive_name_owner_changed_with_args(&[(0, DBUS_BUS)])
+        .await?
+        .into_stream();
+
+    if let Err(e) = agent_manager.register(AGENT_IDENTIFIER).await {
+        eprintln!("Failed to register NetworkManager secret agent: {}", e);
+    }
+
+    while let Some(change) = owner_changes.next().await {
+        let Ok(args) = change.args() else {
+            continue;
+        };
+        if args.new_owner.is_none() {
+            continue;
+        }
+        if let Err(e) = agent_manager.register(AGENT_IDENTIFIER).await {
+            eprintln!("Failed to register NetworkManager secret agent: {}", e);
+        }
+    }
+    Ok(())
+}
+
+impl BarHandler {
+    /// Answers NetworkManager's requests for passwords by prompting in the bar.
+    pub fn start_secret_agent<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let prompts = Arc::new(Mutex::new(SecretPrompts::default()));
+        app_handle.manage(prompts.clone());
+
+        let (events, mut receiver) = mpsc::unbounded();
+        let app_handle_ = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = receiver.next().await {
+                let _ = match event {
+                    SecretAgentEvent::Prompt(prompt) => app_handle_.emit("secret_prompt", prompt),
+                    SecretAgentEvent::Cancelled(id) => app_handle_.emit("secret_prompt_cancelled", id),
+                };
+            }
+        });
+
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                connection.object_server().at(AGENT_PATH, SecretAgent { prompts, events }).await?;
+                register_agent(&connection).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to run NetworkManager secret agent: {}", e);
+            }
+        });
+    }
+}
+
+/// Passes the user's answer to a prompt back to NetworkManager.
+#[tauri::command]
+pub async fn answer_secret_prompt(
+    payload: SecretAnswer,
+    prompts: State<'_, Arc<Mutex<SecretPrompts>>>,
+) -> Result<(), String> {
+    let mut prompts = prompts.lock().await;
+    let index = prompts.pending.iter()
+        .position(|prompt| prompt.id == payload.id)
+        .ok_or_else(|| format!("No secret prompt with id {}", payload.id))?;
+    let prompt = prompts.pending.remove(index);
+
+    // NetworkManager may have given up on the request in the meantime
+    let _ = prompt.answer.send(payload.secrets);
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+
+use crate::networkmanager::dbus::{ActiveConnectionProxy, DeviceProxy, NetworkManagerDbusProxy};
+use crate::networkmanager::follow_networkmanager;
+use crate::BarHandler;
+
+/// Transfer rates in bytes per second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkThroughput.ts")]
+pub struct ThroughputSample {
+    pub rx: u32,
+    pub tx: u32,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/NetworkThroughput.ts")]
+pub struct NetworkThroughput {
+    /// The interface of the primary connection, or None if there isn't one.
+    pub interface: Option<String>,
+    /// Recent rates, oldest first.
+    pub history: Vec<ThroughputSample>,
+}
+
+#[derive(Clone, Copy)]
+struct Counters {
+    rx: u64,
+    tx: u64,
+}
+
+/// Turns an interface's byte counters into rates averaged over a window.
+pub struct ThroughputSampler {
+    root: PathBuf,
+    window: Duration,
+    interface: Option<String>,
+    counters: VecDeque<(Instant, Counters)>,
+}
+
+impl ThroughputSampler {
+    pub fn new(root: PathBuf, window: Duration) -> Self {
+        Self { root, window, interface: None, counters: VecDeque::new() }
+    }
+
+    fn read_counter(&self, interface: &str, counter: &str) -> io::Result<u64> {
+        let path = self.root.join(interface).join("statistics").join(counter);
+        fs::read_to_string(path)?
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads the interface's counters and returns the rates since the start of the window.
+    /// Rates are zero until there are two readings to compare.
+    pub fn sample(&mut self, interface: &str, now: Instant) -> io::Result<ThroughputSample> {
+        if self.interface.as_deref() != Some(interface) {
+            self.interface = Some(interface.to_string());
+            self.counters.clear();
+        }
+
+        let counters = Counters {
+            rx: self.read_counter(interface, "rx_bytes")?,
+            tx: self.read_counter(interface, "tx_bytes")?,
+        };
+
+        // The counters start over when the interface is recreated
+        if self.counters.back().is_some_and(|(_, last)| counters.rx < last.rx || counters.tx < last.tx) {
+            self.counters.clear();
+        }
+        self.counters.push_back((now, counters));
+
+        // Keep the newest reading from before the window so the rate covers all of it
+        while self.counters.get(1).is_some_and(|(time, _)| now.duration_since(*time) >= self.window) {
+            self.counters.pop_front();
+        }
+
+        let (Some((start, first)), Some((end, last))) = (self.counters.front(), self.counters.back()) else {
+            return Ok(ThroughputSample::default());
+        };
+        let elapsed = end.duration_since(*start).as_secs_f64();
+        if elapsed == 0.0 {
+            return Ok(ThroughputSample::default());
+        }
+
+        Ok(ThroughputSample {
+            rx: ((last.rx - first.rx) as f64 / elapsed) as u32,
+            tx: ((last.tx - first.tx) as f64 / elapsed) as u32,
+        })
+    }
+}
+
+pub struct ThroughputState {
+    interface: Option<String>,
+    history: VecDeque<ThroughputSample>,
+    history_len: usize,
+}
+
+impl ThroughputState {
+    fn snapshot(&self) -> NetworkThroughput {
+        NetworkThroughput {
+            interface: self.interface.clone(),
+            history: self.history.iter().copied().collect(),
+        }
+    }
+}
+
+/// Finds the interface carrying the primary connection's traffic.
+async fn primary_interface(connection: &Connection) -> zbus::Result<Option<String>> {
+    let network_manager = NetworkManagerDbusProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let primary_connection = network_manager.primary_connection().await?;
+    if primary_connection.as_str() == "/" {
+        return Ok(None);
+    }
+
+    let active = ActiveConnectionProxy::builder(connection)
+        .path(primary_connection)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let Some(device) = active.devices().await?.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let device = DeviceProxy::builder(connection)
+        .path(device)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let interface = device.ip_interface().await?;
+    Ok((!interface.is_empty()).then_some(interface))
+}
+
+impl BarHandler {
+    /// Samples the primary interface's traffic for the throughput sparkline.
+    pub fn start_throughput_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let config = self.config.throughput.clone();
+        app_handle.manage(Mutex::new(ThroughputState {
+            interface: None,
+            history: VecDeque::new(),
+            history_len: config.history_len,
+        }));
+
+        let app_handle_ = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                follow_networkmanager(&connection, "primary interface", || {
+                    let (connection, app_handle) = (&connection, &app_handle_);
+                    async move {
+                        let interface = primary_interface(connection).await?;
+
+                        let state = app_handle.state::<Mutex<ThroughputState>>();
+                        let mut state = state.lock().await;
+                        if state.interface != interface {
+                            state.interface = interface;
+                            state.history.clear();
+                            let _ = app_handle.emit("network_throughput_reset", state.snapshot());
+                        }
+                        Ok(())
+                    }
+                }).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track primary network interface: {}", e);
+            }
+        });
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut sampler = ThroughputSampler::new(config.sysfs_root, Duration::from_secs(config.window_secs));
+            // A zero period would make the interval panic
+            let mut interval = tokio::time::interval(Duration::from_millis(config.sample_interval_ms.max(1)));
+            loop {
+                interval.tick().await;
+
+                let state = app_handle.state::<Mutex<ThroughputState>>();
+                let mut state = state.lock().await;
+                let Some(interface) = state.interface.clone() else {
+                    continue;
+                };
+
+                let sample = match sampler.sample(&interface, Instant::now()) {
+                    Ok(sample) => sample,
+                    Err(e) => {
+                        eprintln!("Failed to read counters for {}: {}", interface, e);
+                        continue;
+                    }
+                };
+
+                state.history.push_back(sample);
+                while state.history.len() > state.history_len {
+                    state.history.pop_front();
+                }
+                let _ = app_handle.emit("network_throughput", sample);
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn get_network_throughput(state: State<'_, Mutex<ThroughputState>>) -> Result<NetworkThroughput, ()> {
+    Ok(state.lock().await.snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Sets an interface's byte counters in a fake sysfs tree.
+    fn set_counters(root: &TempDir, interface: &str, rx: u64, tx: u64) {
+        let statistics = root.path().join(interface).join("statistics");
+        fs::create_dir_all(&statistics).unwrap();
+        fs::write(statistics.join("rx_bytes"), format!("{}\n", rx)).unwrap();
+        fs::write(statistics.join("tx_bytes"), format!("{}\n", tx)).unwrap();
+    }
+
+    fn sample(rx: u32, tx: u32) -> ThroughputSample {
+        ThroughputSample { rx, tx }
+    }
+
+    fn sampler(root: &TempDir) -> (ThroughputSampler, Instant) {
+        (ThroughputSampler::new(root.path().to_path_buf(), Duration::from_secs(3)), Instant::now())
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn first_sample_is_zero() {
+        let root = TempDir::new().unwrap();
+        let (mut sampler, start) = sampler(&root);
+        set_counters(&root, "eth0", 5000, 7000);
+        assert_eq!(sampler.sample("eth0", start).unwrap(), sample(0, 0));
+        // A second reading at the same instant has nothing to divide by
+        assert_eq!(sampler.sample("eth0", start).unwrap(), sample(0, 0));
+    }
+
+    #[test]
+    fn averages_over_window() {
+        let root = TempDir::new().unwrap();
+        let (mut sampler, start) = sampler(&root);
+
+        let readings = [(0, 0), (1000, 100), (3000, 200), (6000, 300), (6000, 300)];
+        let mut rates = Vec::new();
+        for (second, (rx, tx)) in readings.into_iter().enumerate() {
+            set_counters(&root, "eth0", rx, tx);
+            rates.push(sampler.sample("eth0", start + secs(second as u64)).unwrap());
+        }
+
+        assert_eq!(rates, vec![
+            sample(0, 0),
+            sample(1000, 100),
+            sample(1500, 100),
+            sample(2000, 100),
+            // The first reading has left the window
+            sample(1666, 66),
+        ]);
+    }
+
+    #[test]
+    fn counters_reset_when_interface_is_recreated() {
+        let root = TempDir::new().unwrap();
+        let (mut sampler, start) = sampler(&root);
+
+        set_counters(&root, "wlan0", 5000, 5000);
+        sampler.sample("wlan0", start).unwrap();
+        set_counters(&root, "wlan0", 6000, 5500);
+        assert_eq!(sampler.sample("wlan0", start + secs(1)).unwrap(), sample(1000, 500));
+
+        set_counters(&root, "wlan0", 100, 100);
+        assert_eq!(sampler.sample("wlan0", start + secs(2)).unwrap(), sample(0, 0));
+        set_counters(&root, "wlan0", 600, 300);
+        assert_eq!(sampler.sample("wlan0", start + secs(3)).unwrap(), sample(500, 200));
+    }
+
+    #[test]
+    fn switching_interfaces_starts_over() {
+        let root = TempDir::new().unwrap();
+        let (mut sampler, start) = sampler(&root);
+
+        set_counters(&root, "eth0", 0, 0);
+        sampler.sample("eth0", start).unwrap();
+        set_counters(&root, "eth0", 2000, 1000);
+        assert_eq!(sampler.sample("eth0", start + secs(1)).unwrap(), sample(2000, 1000));
+
+        // wlan0's counters are higher, but they aren't comparable to eth0's
+        set_counters(&root, "wlan0", 90000, 90000);
+        assert_eq!(sampler.sample("wlan0", start + secs(2)).unwrap(), sample(0, 0));
+        set_counters(&root, "wlan0", 90400, 90100);
+        assert_eq!(sampler.sample("wlan0", start + secs(3)).unwrap(), sample(400, 100));
+    }
+
+    #[test]
+    fn missing_interface_is_an_error() {
+        let root = TempDir::new().unwrap();
+        let (mut sampler, start) = sampler(&root);
+        assert!(sampler.sample("eth0", start).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::proxy::CacheProperties;
+use zbus::Connection;
+use zvariant::{ObjectPath, OwnedObjectPath, Value};
+
+use crate::networkmanager::connections::{signals_from_any_object, SavedConnectionChange};
+use crate::networkmanager::dbus::{
+    AccessPointProxy, DeviceProxy, NetworkManagerDbusProxy, SettingsConnectionProxy, SettingsProxy,
+    WirelessDeviceProxy, DEVICE_TYPE_WIFI, SETTINGS_CONNECTION_INTERFACE,
+};
+use crate::networkmanager::follow_networkmanager;
+use crate::BarHandler;
+
+// NM80211ApFlags and NM80211ApSecurityFlags
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+const AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+const AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+const AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+const AP_SEC_KEY_MGMT_OWE: u32 = 0x800;
+const AP_SEC_KEY_MGMT_OWE_TM: u32 = 0x1000;
+const AP_SEC_KEY_MGMT_EAP_SUITE_B_192: u32 = 0x2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Wifi.ts")]
+pub enum WifiSecurity {
+    Open,
+    /// Opportunistic Wireless Encryption; encrypted, but doesn't need a password.
+    Owe,
+    Wep,
+    WpaPersonal,
+    /// WPA3 personal (SAE).
+    Wpa3Personal,
+    /// 802.1X; these need to be set up in NetworkManager directly.
+    WpaEnterprise,
+}
+
+impl WifiSecurity {
+    fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        let security = wpa_flags | rsn_flags;
+        if security & (AP_SEC_KEY_MGMT_802_1X | AP_SEC_KEY_MGMT_EAP_SUITE_B_192) != 0 {
+            WifiSecurity::WpaEnterprise
+        } else if rsn_flags & AP_SEC_KEY_MGMT_SAE != 0 && security & AP_SEC_KEY_MGMT_PSK == 0 {
+            WifiSecurity::Wpa3Personal
+        } else if security & (AP_SEC_KEY_MGMT_PSK | AP_SEC_KEY_MGMT_SAE) != 0 {
+            WifiSecurity::WpaPersonal
+        } else if security & (AP_SEC_KEY_MGMT_OWE | AP_SEC_KEY_MGMT_OWE_TM) != 0 {
+            WifiSecurity::Owe
+        } else if flags & AP_FLAGS_PRIVACY != 0 {
+            WifiSecurity::Wep
+        } else {
+            WifiSecurity::Open
+        }
+    }
+}
+
+/// A network visible to a wireless device. Access points sharing an SSID are merged into the strongest one.
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Wifi.ts")]
+pub struct WifiAccessPoint {
+    /// The D-Bus object path of the access point, used to connect to it.
+    pub path: String,
+    pub ssid: String,
+    pub bssid: String,
+    /// Signal strength as a percentage.
+    pub strength: u8,
+    /// Frequency in MHz.
+    pub frequency: u32,
+    pub security: WifiSecurity,
+    /// Whether NetworkManager has a saved connection for this SSID.
+    pub is_saved: bool,
+    /// Whether the device is currently connected to this network.
+    pub is_active: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/Wifi.ts")]
+pub struct WifiDevice {
+    /// The D-Bus object path of the device.
+    pub path: String,
+    /// The interface name, like "wlan0".
+    pub interface: String,
+    /// The active network first, then the rest by strength.
+    pub access_points: Vec<WifiAccessPoint>,
+    /// CLOCK_BOOTTIME milliseconds of the last finished scan, or -1 if it never scanned.
+    pub last_scan: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/Wifi.ts")]
+pub struct WifiConnectRequest {
+    /// The path of the wireless device to connect with.
+    pub device: String,
+    /// The path of the access point to connect to.
+    pub access_point: String,
+    /// Only used when there isn't a saved connection for the network yet.
+    pub password: Option<String>,
+}
+
+#[derive(Default)]
+pub struct WifiState {
+    devices: Vec<WifiDevice>,
+    /// The SSID of each saved Wi-Fi connection. Reading a connection's settings is slow,
+    /// so each one is only read again when NetworkManager says it changed.
+    saved: HashMap<OwnedObjectPath, Vec<u8>>,
+}
+
+impl WifiState {
+    fn saved_connection(&self, ssid: &[u8]) -> Option<OwnedObjectPath> {
+        self.saved.iter()
+            .find(|(_, saved)| saved.as_slice() == ssid)
+            .map(|(path, _)| path.clone())
+    }
+
+    /// Marks the networks that have a saved connection.
+    fn mark_saved(&self, devices: &mut [WifiDevice]) {
+        let saved = self.saved.values().map(|ssid| String::from_utf8_lossy(ssid)).collect::<HashSet<_>>();
+        for access_point in devices.iter_mut().flat_map(|device| device.access_points.iter_mut()) {
+            access_point.is_saved = saved.contains(access_point.ssid.as_str());
+        }
+    }
+
+    /// Stores newly read devices, returning whether they changed.
+    fn set_devices(&mut self, mut devices: Vec<WifiDevice>) -> bool {
+        self.mark_saved(&mut devices);
+        let changed = self.devices != devices;
+        self.devices = devices;
+        changed
+    }
+}
+
+/// Reads the SSID of a saved connection, or None if it isn't a Wi-Fi connection or it's gone.
+async fn read_saved_ssid(connection: &Connection, path: &OwnedObjectPath) -> zbus::Result<Option<Vec<u8>>> {
+    let settings_connection = SettingsConnectionProxy::builder(connection)
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    // Connections can vanish while we're reading them
+    let Ok(settings) = settings_connection.get_settings().await else {
+        return Ok(None);
+    };
+
+    Ok(settings.get("802-11-wireless")
+        .and_then(|wireless| wireless.get("ssid"))
+        .and_then(|ssid| ssid.try_clone().ok())
+        .and_then(|ssid| Vec::<u8>::try_from(ssid).ok()))
+}
+
+async fn read_access_point(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<(Vec<u8>, WifiAccessPoint)> {
+    let ap = AccessPointProxy::builder(connection)
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let ssid = ap.ssid().await?;
+    let access_point = WifiAccessPoint {
+        path: path.to_string(),
+        ssid: String::from_utf8_lossy(&ssid).into_owned(),
+        bssid: ap.hw_address().await?,
+        strength: ap.strength().await?,
+        frequency: ap.frequency().await?,
+        security: WifiSecurity::from_flags(ap.flags().await?, ap.wpa_flags().await?, ap.rsn_flags().await?),
+        is_saved: false,
+        is_active: false,
+    };
+    Ok((ssid, access_point))
+}
+
+/// Reads the devices and their networks. `is_saved` is filled in from the saved connections afterwards.
+async fn read_wifi_devices(connection: &Connection) -> zbus::Result<Vec<WifiDevice>> {
+    let network_manager = NetworkManagerDbusProxy::new(connection).await?;
+
+    let mut devices = Vec::new();
+    for path in network_manager.devices().await? {
+        let device = DeviceProxy::builder(connection)
+            .path(path.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        if device.device_type().await? != DEVICE_TYPE_WIFI {
+            continue;
+        }
+
+        let wireless = WirelessDeviceProxy::builder(connection)
+            .path(path.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let active_path = wireless.active_access_point().await?;
+
+        let mut by_ssid: HashMap<Vec<u8>, WifiAccessPoint> = HashMap::new();
+        for ap_path in wireless.access_points().await? {
+            let is_active = ap_path == active_path;
+            // Access points can vanish while we're reading them
+            let Ok((ssid, mut access_point)) = read_access_point(connection, ap_path).await else {
+                continue;
+            };
+            // Hidden networks don't broadcast an SSID, so there's nothing to show
+            if ssid.is_empty() {
+                continue;
+            }
+
+            access_point.is_active = is_active;
+
+            let existing = by_ssid.get(&ssid);
+            let replace = existing.is_none_or(|existing| {
+                !existing.is_active && (access_point.is_active || access_point.strength > existing.strength)
+            });
+            if replace {
+                by_ssid.insert(ssid, access_point);
+            }
+        }
+
+        let mut access_points = by_ssid.into_values().collect::<Vec<_>>();
+        // Break ties by SSID, so the order doesn't change between reads when nothing else did
+        access_points.sort_by(|a, b| {
+            b.is_active.cmp(&a.is_active).then(b.strength.cmp(&a.strength)).then_with(|| a.ssid.cmp(&b.ssid))
+        });
+
+        devices.push(WifiDevice {
+            path: path.to_string(),
+            interface: device.interface().await?,
+            access_points,
+            last_scan: wireless.last_scan().await?,
+        });
+    }
+    Ok(devices)
+}
+
+impl BarHandler {
+    /// Tracks wireless devices and the networks they can see.
+    pub fn start_wifi_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(WifiState::default()));
+
+        let app_handle_ = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                follow_networkmanager(&connection, "Wi-Fi devices", || {
+                    let (connection, app_handle) = (&connection, &app_handle_);
+                    async move {
+                        let devices = read_wifi_devices(connection).await?;
+
+                        let state = app_handle.state::<Mutex<WifiState>>();
+                        let mut state = state.lock().await;
+                        if state.set_devices(devices) {
+                            let _ = app_handle.emit("wifi_devices_changed", state.devices.clone());
+                        }
+                        Ok(())
+                    }
+                }).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track Wi-Fi devices: {}", e);
+            }
+        });
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = Connection::system().await?;
+                Self::run_saved_wifi_events(&app_handle, &connection).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track saved Wi-Fi connections: {}", e);
+            }
+        });
+    }
+
+    /// Keeps the SSIDs of the saved connections up to date, re-reading only the connections that changed.
+    async fn run_saved_wifi_events<R: Runtime>(app_handle: &AppHandle<R>, connection: &Connection) -> zbus::Result<()> {
+        let settings = SettingsProxy::new(connection).await?;
+
+        // Subscribe before reading so we don't miss changes in between
+        let added = settings.receive_new_connection().await?.filter_map(|signal| async move {
+            Some(SavedConnectionChange::Read(signal.args().ok()?.connection.into()))
+        });
+        let removed = settings.receive_connection_removed().await?.filter_map(|signal| async move {
+            Some(SavedConnectionChange::Removed(signal.args().ok()?.connection.into()))
+        });
+        let updated = signals_from_any_object(connection, SETTINGS_CONNECTION_INTERFACE, "Updated").await?
+            .filter_map(|message| async move {
+                let path = message.ok()?.header().path()?.to_owned();
+                Some(SavedConnectionChange::Read(path.into()))
+            });
+        let mut changes = stream::select_all([added.boxed(), removed.boxed(), updated.boxed()]);
+
+        let mut saved = HashMap::new();
+        for path in settings.list_connections().await? {
+            if let Some(ssid) = read_saved_ssid(connection, &path).await? {
+                saved.insert(path, ssid);
+            }
+        }
+        let state = app_handle.state::<Mutex<WifiState>>();
+        state.lock().await.saved = saved;
+        publish_saved(app_handle).await;
+
+        while let Some(change) = changes.next().await {
+            let (path, ssid) = match change {
+                SavedConnectionChange::Read(path) => {
+                    let ssid = read_saved_ssid(connection, &path).await.unwrap_or_else(|e| {
+                        eprintln!("Failed to read saved connection {}: {}", path.as_str(), e);
+                        None
+                    });
+                    (path, ssid)
+                }
+                SavedConnectionChange::Removed(path) => (path, None),
+                SavedConnectionChange::Active => continue,
+            };
+
+            let mut state = state.lock().await;
+            match ssid {
+                Some(ssid) => state.saved.insert(path, ssid),
+                None => state.saved.remove(&path),
+            };
+            drop(state);
+            publish_saved(app_handle).await;
+        }
+        Ok(())
+    }
+}
+
+/// Re-marks the saved networks and emits the devices if that changed anything.
+async fn publish_saved<R: Runtime>(app_handle: &AppHandle<R>) {
+    let state = app_handle.state::<Mutex<WifiState>>();
+    let mut state = state.lock().await;
+    let devices = state.devices.clone();
+    if state.set_devices(devices) {
+        let _ = app_handle.emit("wifi_devices_changed", state.devices.clone());
+    }
+}
+
+#[tauri::command]
+pub async fn get_wifi_devices(state: State<'_, Mutex<WifiState>>) -> Result<Vec<WifiDevice>, ()> {
+    Ok(state.lock().await.devices.clone())
+}
+
+/// Asks every wireless device to scan. Results arrive through `wifi_devices_changed`.
+#[tauri::command]
+pub async fn request_wifi_scan(state: State<'_, Mutex<WifiState>>) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let paths = state.lock().await.devices.iter().map(|device| device.path.clone()).collect::<HashSet<_>>();
+
+    for path in paths {
+        let wireless = WirelessDeviceProxy::builder(&connection)
+            .path(path)
+            .map_err(|e| e.to_string())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| e.to_string())?;
+        // NetworkManager refuses to scan too often, which isn't worth surfacing
+        if let Err(e) = wireless.request_scan(HashMap::new()).await {
+            eprintln!("Failed to request Wi-Fi scan: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Connects to a network, using its saved connection if there is one.
+/// Otherwise, a new connection is created with the given password and NetworkManager fills in the rest.
+#[tauri::command]
+pub async fn connect_wifi(payload: WifiConnectRequest, state: State<'_, Mutex<WifiState>>) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let network_manager = NetworkManagerDbusProxy::new(&connection).await.map_err(|e| e.to_string())?;
+
+    let device = ObjectPath::try_from(payload.device.as_str()).map_err(|e| e.to_string())?;
+    let ap_path = ObjectPath::try_from(payload.access_point.as_str()).map_err(|e| e.to_string())?;
+    let (ssid, access_point) = read_access_point(&connection, ap_path.clone().into())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let saved_connection = state.lock().await.saved_connection(&ssid);
+    if let Some(saved_connection) = saved_connection {
+        network_manager.activate_connection(&saved_connection, &device, &ap_path)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let mut settings: HashMap<&str, HashMap<&str, Value>> = HashMap::new();
+    match (access_point.security, payload.password) {
+        (WifiSecurity::Open | WifiSecurity::Owe, _) => {}
+        (WifiSecurity::WpaEnterprise, _) => {
+            return Err("Enterprise networks have to be set up in NetworkManager".to_string());
+        }
+        (_, None) => return Err(format!("{} needs a password", access_point.ssid)),
+        (WifiSecurity::Wep, Some(password)) => {
+            settings.insert("802-11-wireless-security", HashMap::from([
+                ("key-mgmt", Value::from("none")),
+                ("wep-key0", Value::from(password)),
+            ]));
+        }
+        (security, Some(password)) => {
+            let key_mgmt = if security == WifiSecurity::Wpa3Personal { "sae" } else { "wpa-psk" };
+            settings.insert("802-11-wireless-security", HashMap::from([
+                ("key-mgmt", Value::from(key_mgmt)),
+                ("psk", Value::from(password)),
+            ]));
+        }
+    }
+
+    network_manager.add_and_activate_connection(settings, &device, &ap_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn disconnect_wifi(payload: String) -> Result<(), String> {
+    let connection = Connection::system().await.map_err(|e| e.to_string())?;
+    let device = DeviceProxy::builder(&connection)
+        .path(payload)
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+    device.disconnect().await.map_err(|e| e.to_string())
+}
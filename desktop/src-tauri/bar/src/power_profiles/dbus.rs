@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use zbus::{Result, proxy};
+use zvariant::OwnedValue;
+
+/// See https://freedesktop-team.pages.debian.net/power-profiles-daemon/gdbus-net.hadess.PowerProfiles.html
+#[proxy(
+    interface = "net.hadess.PowerProfiles",
+    default_service = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles"
+)]
+pub trait PowerProfiles {
+    /// Holds a profile until it's released or the calling connection goes away.
+    /// Returns a cookie used to release it.
+    fn hold_profile(&self, profile: &str, reason: &str, application_id: &str) -> Result<u32>;
+
+    fn release_profile(&self, cookie: u32) -> Result<()>;
+
+    /// Emitted when a hold is released, including when the user picks another profile.
+    #[zbus(signal)]
+    fn profile_released(&self, cookie: u32) -> Result<()>;
+
+    #[zbus(property)]
+    fn active_profile(&self) -> Result<String>;
+    #[zbus(property)]
+    fn set_active_profile(&self, value: &str) -> Result<()>;
+
+    /// Why the performance profile is degraded, or an empty string if it isn't.
+    #[zbus(property)]
+    fn performance_degraded(&self) -> Result<String>;
+
+    /// Each profile is a dictionary with at least "Profile" and "Driver" keys.
+    #[zbus(property)]
+    fn profiles(&self) -> Result<Vec<HashMap<String, OwnedValue>>>;
+
+    #[zbus(property)]
+    fn active_profile_holds(&self) -> Result<Vec<HashMap<String, OwnedValue>>>;
+}
@@ -0,0 +1,457 @@
+mod dbus;
+
+use dbus::PowerProfilesProxy;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+
+use crate::config::PowerProfilesConfig;
+use crate::BarHandler;
+
+/// The profile we hold while the battery is low.
+const POWER_SAVER: &str = "power-saver";
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/PowerProfiles.ts")]
+pub struct PowerProfile {
+    /// The profile name, like "power-saver", "balanced", or "performance".
+    pub name: String,
+    /// The driver implementing the profile, like "platform_profile" or "amd_pstate".
+    pub driver: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, TS)]
+#[ts(export, export_to="../../bindings/PowerProfiles.ts")]
+pub struct PowerProfiles {
+    /// Whether power-profiles-daemon is running. Everything else is empty if it isn't.
+    pub available: bool,
+    pub active: String,
+    pub profiles: Vec<PowerProfile>,
+    /// Why the performance profile is degraded, like "lap-detected" or "high-operating-temperature".
+    /// None if it isn't degraded.
+    pub performance_degraded: Option<String>,
+    /// Whether we're holding power-saver because the battery is low.
+    pub low_battery_hold: bool,
+}
+
+pub struct PowerProfilesState {
+    config: PowerProfilesConfig,
+    proxy: Option<PowerProfilesProxy<'static>>,
+    profiles: PowerProfiles,
+    /// The cookie of our low-battery power-saver hold, if there is one.
+    hold: Option<u32>,
+    /// Whether we've already applied the low-battery hold this discharge cycle,
+    /// so we don't override the user if they switch away from it.
+    hold_applied: bool,
+}
+
+/// Reads the current profiles from power-profiles-daemon.
+/// Only needs a proxy, so it works the same against a mock service on a private bus.
+pub async fn read_power_profiles(proxy: &PowerProfilesProxy<'_>) -> zbus::Result<PowerProfiles> {
+    let profiles = proxy.profiles().await?.into_iter().filter_map(|profile| {
+        let get = |key: &str| profile.get(key).and_then(|value| String::try_from(value.try_clone().ok()?).ok());
+        Some(PowerProfile {
+            name: get("Profile")?,
+            driver: get("Driver").unwrap_or_default(),
+        })
+    }).collect();
+
+    let performance_degraded = proxy.performance_degraded().await?;
+
+    Ok(PowerProfiles {
+        available: true,
+        active: proxy.active_profile().await?,
+        profiles,
+        performance_degraded: (!performance_degraded.is_empty()).then_some(performance_degraded),
+        low_battery_hold: false,
+    })
+}
+
+/// Re-reads the profiles and stores them, returning what was stored.
+/// The lock is only taken once the daemon has answered, so commands aren't held up by a slow daemon.
+async fn refresh(state: &Mutex<PowerProfilesState>, proxy: &PowerProfilesProxy<'_>) -> PowerProfiles {
+    let mut profiles = match read_power_profiles(proxy).await {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            eprintln!("Failed to read power profiles: {}", e);
+            PowerProfiles::default()
+        }
+    };
+
+    let mut state = state.lock().await;
+    profiles.low_battery_hold = state.hold.is_some();
+    state.profiles = profiles.clone();
+    profiles
+}
+
+/// Holds power-saver while the battery is discharging below the configured threshold,
+/// and releases it once the battery charges again. Called by the upower backend on every update.
+pub async fn handle_battery_update<R: Runtime>(app_handle: &AppHandle<R>, discharging: bool, percentage: f64) {
+    let Some(state) = app_handle.try_state::<Mutex<PowerProfilesState>>() else {
+        return;
+    };
+    if !update_low_battery_hold(&state, discharging, percentage).await {
+        return;
+    }
+
+    // Releasing the hold restores the previous profile, which we'll pick up as a property change
+    let profiles = state.lock().await.profiles.clone();
+    let _ = app_handle.emit("power_profiles_changed", profiles);
+}
+
+/// Takes or releases the low-battery hold, returning whether `low_battery_hold` changed.
+/// The lock isn't held during the D-Bus call, so the profiles can still be read meanwhile.
+async fn update_low_battery_hold(state: &Mutex<PowerProfilesState>, discharging: bool, percentage: f64) -> bool {
+    let mut locked = state.lock().await;
+    let (Some(threshold), Some(proxy)) = (locked.config.power_saver_below, locked.proxy.clone()) else {
+        return false;
+    };
+
+    if discharging {
+        if percentage > threshold || locked.hold_applied {
+            return false;
+        }
+        locked.hold_applied = true;
+        drop(locked);
+
+        let cookie = match proxy.hold_profile(POWER_SAVER, "Battery is low", "desktop").await {
+            Ok(cookie) => Some(cookie),
+            Err(e) => {
+                eprintln!("Failed to hold power-saver profile: {}", e);
+                None
+            }
+        };
+        let mut locked = state.lock().await;
+        locked.hold = cookie;
+        set_low_battery_hold(&mut locked)
+    } else {
+        locked.hold_applied = false;
+        let Some(cookie) = locked.hold.take() else {
+            return false;
+        };
+        let changed = set_low_battery_hold(&mut locked);
+        drop(locked);
+
+        if let Err(e) = proxy.release_profile(cookie).await {
+            eprintln!("Failed to release power-saver profile: {}", e);
+        }
+        changed
+    }
+}
+
+fn set_low_battery_hold(state: &mut PowerProfilesState) -> bool {
+    let low_battery_hold = state.hold.is_some();
+    let changed = state.profiles.low_battery_hold != low_battery_hold;
+    state.profiles.low_battery_hold = low_battery_hold;
+    changed
+}
+
+impl BarHandler {
+    pub fn start_power_profiles_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(PowerProfilesState {
+            config: self.config.power_profiles.clone(),
+            proxy: None,
+            profiles: PowerProfiles::default(),
+            hold: None,
+            hold_applied: false,
+        }));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = zbus::Connection::system().await?;
+                Self::run_power_profiles_events(app_handle, &connection).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track power profiles: {}", e);
+            }
+        });
+    }
+
+    async fn run_power_profiles_events<R: Runtime>(app_handle: AppHandle<R>, connection: &zbus::Connection) -> zbus::Result<()> {
+        let proxy = PowerProfilesProxy::new(connection).await?;
+        let state = app_handle.state::<Mutex<PowerProfilesState>>();
+        follow_power_profiles(proxy, &state, |profiles| {
+            let _ = app_handle.emit("power_profiles_changed", profiles);
+        }).await
+    }
+}
+
+/// Keeps the state in sync with power-profiles-daemon, passing the profiles to `changed` after every refresh.
+async fn follow_power_profiles(
+    proxy: PowerProfilesProxy<'static>,
+    state: &Mutex<PowerProfilesState>,
+    mut changed: impl FnMut(PowerProfiles),
+) -> zbus::Result<()> {
+    // Subscribe before reading so we don't miss changes in between
+    let active = proxy.receive_active_profile_changed().await;
+    let degraded = proxy.receive_performance_degraded_changed().await;
+    let profiles = proxy.receive_profiles_changed().await;
+    let released = proxy.receive_profile_released().await?;
+
+    state.lock().await.proxy = Some(proxy.clone());
+    changed(refresh(state, &proxy).await);
+
+    let changes = stream::select(
+        stream::select(active.map(|_| None), degraded.map(|_| None)),
+        stream::select(
+            profiles.map(|_| None),
+            released.filter_map(|signal| async move { signal.args().ok().map(|args| Some(args.cookie)) }),
+        ),
+    );
+    let mut changes = Box::pin(changes);
+
+    while let Some(released_cookie) = changes.next().await {
+        if let Some(cookie) = released_cookie {
+            let mut state = state.lock().await;
+            if state.hold == Some(cookie) {
+                state.hold = None;
+            }
+        }
+        changed(refresh(state, &proxy).await);
+    }
+    Ok(())
+}
+
+/// Switches profiles. The lock isn't held during the call, so a property change can be picked up meanwhile.
+async fn set_active_profile(state: &Mutex<PowerProfilesState>, profile: &str) -> Result<(), String> {
+    let proxy = state.lock().await.proxy.clone().ok_or("power-profiles-daemon isn't available")?;
+    proxy.set_active_profile(profile).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_power_profiles(state: State<'_, Mutex<PowerProfilesState>>) -> Result<PowerProfiles, ()> {
+    Ok(state.lock().await.profiles.clone())
+}
+
+#[tauri::command]
+pub async fn set_power_profile(payload: String, state: State<'_, Mutex<PowerProfilesState>>) -> Result<(), String> {
+    set_active_profile(&state, &payload).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use std::time::Duration;
+
+    use futures::channel::mpsc;
+    use futures::future::{self, Either};
+    use zbus::connection::Builder;
+    use zbus::proxy::CacheProperties;
+    use zvariant::{OwnedValue, Value};
+
+    use super::*;
+
+    /// Stands in for power-profiles-daemon, recording the holds and switches it's asked for.
+    #[derive(Clone)]
+    struct MockPowerProfiles {
+        calls: Arc<StdMutex<Vec<String>>>,
+        active: String,
+    }
+
+    impl Default for MockPowerProfiles {
+        fn default() -> Self {
+            Self { calls: Arc::default(), active: "balanced".to_string() }
+        }
+    }
+
+    #[zbus::interface(name = "net.hadess.PowerProfiles")]
+    impl MockPowerProfiles {
+        fn hold_profile(&self, profile: &str, reason: &str, application_id: &str) -> u32 {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(format!("hold {} ({}, {})", profile, reason, application_id));
+            calls.len() as u32
+        }
+
+        fn release_profile(&self, cookie: u32) {
+            self.calls.lock().unwrap().push(format!("release {}", cookie));
+        }
+
+        #[zbus(property)]
+        fn active_profile(&self) -> String {
+            self.active.clone()
+        }
+
+        /// zbus emits PropertiesChanged after this, like the daemon does.
+        #[zbus(property)]
+        fn set_active_profile(&mut self, profile: String) {
+            self.calls.lock().unwrap().push(format!("set {}", profile));
+            self.active = profile;
+        }
+
+        #[zbus(property)]
+        fn performance_degraded(&self) -> String {
+            "lap-detected".to_string()
+        }
+
+        #[zbus(property)]
+        fn profiles(&self) -> Vec<HashMap<String, OwnedValue>> {
+            ["power-saver", "balanced", "performance"].into_iter().map(|profile| {
+                let value = |value: &str| OwnedValue::try_from(Value::from(value)).unwrap();
+                HashMap::from([
+                    ("Profile".to_string(), value(profile)),
+                    ("Driver".to_string(), value("platform_profile")),
+                ])
+            }).collect()
+        }
+    }
+
+    /// Connects a proxy to the mock over a private peer-to-peer bus.
+    /// The server connection has to be kept alive for as long as the proxy is used.
+    /// Properties aren't cached, so reads always reach the mock.
+    async fn connect(mock: MockPowerProfiles) -> (PowerProfilesProxy<'static>, zbus::Connection) {
+        connect_caching(mock, CacheProperties::No).await
+    }
+
+    /// Property change streams only work on a caching proxy, like the one we use against the daemon.
+    async fn connect_caching(mock: MockPowerProfiles, cache: CacheProperties) -> (PowerProfilesProxy<'static>, zbus::Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (client, server) = futures::try_join!(
+            Builder::unix_stream(client).p2p().build(),
+            Builder::unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/net/hadess/PowerProfiles", mock)
+                .unwrap()
+                .build(),
+        ).unwrap();
+
+        let proxy = PowerProfilesProxy::builder(&client)
+            .cache_properties(cache)
+            .build()
+            .await
+            .unwrap();
+        (proxy, server)
+    }
+
+    fn state(proxy: Option<PowerProfilesProxy<'static>>) -> Mutex<PowerProfilesState> {
+        Mutex::new(PowerProfilesState {
+            config: PowerProfilesConfig { power_saver_below: Some(20.0) },
+            proxy,
+            profiles: PowerProfiles::default(),
+            hold: None,
+            hold_applied: false,
+        })
+    }
+
+    #[test]
+    fn hold_and_release_cycle() {
+        tauri::async_runtime::block_on(async {
+            let mock = MockPowerProfiles::default();
+            let (proxy, _server) = connect(mock.clone()).await;
+            let state = state(Some(proxy));
+
+            // Above the threshold nothing happens
+            assert!(!update_low_battery_hold(&state, true, 50.0).await);
+            assert!(mock.calls.lock().unwrap().is_empty());
+
+            assert!(update_low_battery_hold(&state, true, 20.0).await);
+            assert_eq!(state.lock().await.hold, Some(1));
+            assert!(state.lock().await.profiles.low_battery_hold);
+
+            // Only held once per discharge cycle, even after the user switches away
+            state.lock().await.hold = None;
+            assert!(!update_low_battery_hold(&state, true, 15.0).await);
+            state.lock().await.hold = Some(1);
+
+            assert!(update_low_battery_hold(&state, false, 16.0).await);
+            assert_eq!(state.lock().await.hold, None);
+            assert!(!state.lock().await.profiles.low_battery_hold);
+            // Charging without a hold doesn't change anything
+            assert!(!update_low_battery_hold(&state, false, 17.0).await);
+
+            assert_eq!(*mock.calls.lock().unwrap(), vec![
+                "hold power-saver (Battery is low, desktop)".to_string(),
+                "release 1".to_string(),
+            ]);
+        });
+    }
+
+    #[test]
+    fn nothing_held_without_daemon() {
+        tauri::async_runtime::block_on(async {
+            let state = state(None);
+            assert!(!update_low_battery_hold(&state, true, 10.0).await);
+            assert!(!state.lock().await.hold_applied);
+        });
+    }
+
+    #[test]
+    fn reads_profiles() {
+        tauri::async_runtime::block_on(async {
+            let (proxy, _server) = connect(MockPowerProfiles::default()).await;
+            let profiles = read_power_profiles(&proxy).await.unwrap();
+
+            assert!(profiles.available);
+            assert_eq!(profiles.active, "balanced");
+            assert_eq!(profiles.performance_degraded.as_deref(), Some("lap-detected"));
+            let names = profiles.profiles.iter().map(|profile| profile.name.as_str()).collect::<Vec<_>>();
+            assert_eq!(names, vec!["power-saver", "balanced", "performance"]);
+            assert_eq!(profiles.profiles[0].driver, "platform_profile");
+        });
+    }
+
+    #[test]
+    fn refresh_keeps_hold() {
+        tauri::async_runtime::block_on(async {
+            let (proxy, _server) = connect(MockPowerProfiles::default()).await;
+            let state = state(Some(proxy.clone()));
+            state.lock().await.hold = Some(3);
+
+            let profiles = refresh(&state, &proxy).await;
+            assert!(profiles.low_battery_hold);
+            assert_eq!(state.lock().await.profiles.active, "balanced");
+        });
+    }
+
+    #[test]
+    fn set_follows_property_change() {
+        tauri::async_runtime::block_on(async {
+            let mock = MockPowerProfiles::default();
+            let (proxy, _server) = connect_caching(mock.clone(), CacheProperties::Lazily).await;
+            let state = state(None);
+
+            let (sender, mut receiver) = mpsc::unbounded();
+            let follow = follow_power_profiles(proxy, &state, move |profiles| {
+                let _ = sender.unbounded_send(profiles);
+            });
+            let check = async {
+                assert_eq!(receiver.next().await.unwrap().active, "balanced");
+                set_active_profile(&state, "performance").await.unwrap();
+
+                // The switch is only picked up through PropertiesChanged, not by set_active_profile itself
+                loop {
+                    if receiver.next().await.unwrap().active == "performance" {
+                        break;
+                    }
+                }
+                assert_eq!(state.lock().await.profiles.active, "performance");
+            };
+
+            let check = tokio::time::timeout(Duration::from_secs(5), check);
+            futures::pin_mut!(follow, check);
+            match future::select(follow, check).await {
+                Either::Left((result, _)) => panic!("Stopped following power profiles: {:?}", result),
+                Either::Right((result, _)) => result.expect("Timed out waiting for the property change"),
+            }
+            assert_eq!(*mock.calls.lock().unwrap(), vec!["set performance".to_string()]);
+        });
+    }
+
+    #[test]
+    fn set_without_daemon() {
+        tauri::async_runtime::block_on(async {
+            let state = state(None);
+            assert_eq!(
+                set_active_profile(&state, "performance").await,
+                Err("power-profiles-daemon isn't available".to_string()),
+            );
+        });
+    }
+}
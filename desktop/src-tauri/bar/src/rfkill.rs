@@ -0,0 +1,124 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+use ts_rs::TS;
+
+const RFKILL_SYSFS: &str = "/sys/class/rfkill";
+const RFKILL_DEVICE: &str = "/dev/rfkill";
+
+/// RFKILL_OP_CHANGE_ALL from linux/rfkill.h
+const OP_CHANGE_ALL: u8 = 3;
+
+/// See `enum rfkill_type` in linux/rfkill.h.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/RadioState.ts")]
+pub enum RfkillType {
+    Wlan,
+    Bluetooth,
+    Uwb,
+    Wimax,
+    Wwan,
+    Gps,
+    Fm,
+    Nfc,
+    Other,
+}
+
+impl RfkillType {
+    fn from_sysfs(value: &str) -> Self {
+        match value {
+            "wlan" => RfkillType::Wlan,
+            "bluetooth" => RfkillType::Bluetooth,
+            "uwb" => RfkillType::Uwb,
+            "wimax" => RfkillType::Wimax,
+            "wwan" => RfkillType::Wwan,
+            "gps" => RfkillType::Gps,
+            "fm" => RfkillType::Fm,
+            "nfc" => RfkillType::Nfc,
+            _ => RfkillType::Other,
+        }
+    }
+
+    /// The kernel's numeric type, for the types we can block.
+    fn id(self) -> Option<u8> {
+        match self {
+            RfkillType::Wlan => Some(1),
+            RfkillType::Bluetooth => Some(2),
+            RfkillType::Uwb => Some(3),
+            RfkillType::Wimax => Some(4),
+            RfkillType::Wwan => Some(5),
+            RfkillType::Gps => Some(6),
+            RfkillType::Fm => Some(7),
+            RfkillType::Nfc => Some(8),
+            RfkillType::Other => None,
+        }
+    }
+}
+
+/// A radio the kernel can block.
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/RadioState.ts")]
+pub struct RfkillDevice {
+    /// The driver's name for the radio, like "phy0" or "hci0".
+    pub name: String,
+    pub device_type: RfkillType,
+    /// Blocked in software, which we can undo.
+    pub soft_blocked: bool,
+    /// Blocked by a hardware switch, which only the user can undo.
+    pub hard_blocked: bool,
+}
+
+fn read_flag(device: &Path, file: &str) -> bool {
+    fs::read_to_string(device.join(file)).is_ok_and(|value| value.trim() == "1")
+}
+
+/// Reads every rfkill device, sorted by name.
+pub fn read_devices() -> Vec<RfkillDevice> {
+    let Ok(entries) = fs::read_dir(RFKILL_SYSFS) else {
+        return Vec::new();
+    };
+
+    let mut devices = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| Some(RfkillDevice {
+            name: fs::read_to_string(path.join("name")).ok()?.trim().to_string(),
+            device_type: RfkillType::from_sysfs(fs::read_to_string(path.join("type")).ok()?.trim()),
+            soft_blocked: read_flag(&path, "soft"),
+            hard_blocked: read_flag(&path, "hard"),
+        }))
+        .collect::<Vec<_>>();
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    devices
+}
+
+/// Soft-blocks or unblocks every radio of a type. This goes through /dev/rfkill, which logind
+/// gives the active session access to, since the sysfs files are only writable by root.
+pub fn set_soft_blocked(device_type: RfkillType, blocked: bool) -> io::Result<()> {
+    let Some(type_id) = device_type.id() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Can't block unknown radio types"));
+    };
+
+    // struct rfkill_event { __u32 idx; __u8 type; __u8 op; __u8 soft; __u8 hard; }
+    // The index is ignored when changing all radios of a type.
+    let event = [0, 0, 0, 0, type_id, OP_CHANGE_ALL, blocked as u8, 0];
+
+    OpenOptions::new().write(true).open(RFKILL_DEVICE)?.write_all(&event)
+}
+
+/// Calls `changed` for every rfkill event until it returns false or the device can't be read.
+/// Blocks, so this should run on its own thread.
+pub fn watch(mut changed: impl FnMut() -> bool) -> io::Result<()> {
+    let mut device = File::open(RFKILL_DEVICE)?;
+    // Each read returns a single event, whose size depends on the kernel version
+    let mut event = [0u8; 32];
+    loop {
+        if device.read(&mut event)? == 0 || !changed() {
+            return Ok(());
+        }
+    }
+}
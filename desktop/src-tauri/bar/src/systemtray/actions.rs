@@ -0,0 +1,92 @@
+use system_tray::client::ActivateRequest;
+use tauri::State;
+use zbus::Connection;
+use zvariant::{OwnedObjectPath, Value};
+
+use crate::systemtray::dbus::{DBusMenuProxy, StatusNotifierItemProxy, StatusNotifierWatcherProxy};
+use crate::systemtray::types::{SystrayActivateRequest, SystrayMenuRequest, SystrayScrollOrientation, SystrayScrollRequest};
+use crate::systemtray::SystemTrayState;
+
+/// The object path used by items that register with just their bus name.
+const DEFAULT_ITEM_PATH: &str = "/StatusNotifierItem";
+
+fn session(state: &SystemTrayState) -> Result<&Connection, String> {
+    state.connection.as_ref().ok_or_else(|| "Not connected to the session bus".to_string())
+}
+
+/// Finds an item's object path from the watcher, since it isn't part of the item's address.
+async fn item_path(connection: &Connection, address: &str) -> Result<OwnedObjectPath, String> {
+    let watcher = StatusNotifierWatcherProxy::new(connection).await.map_err(|e| e.to_string())?;
+    let registered = watcher.registered_status_notifier_items().await.map_err(|e| e.to_string())?;
+
+    let path = registered.iter()
+        .filter_map(|item| item.strip_prefix(address))
+        .find(|path| path.is_empty() || path.starts_with('/'))
+        .filter(|path| !path.is_empty())
+        .unwrap_or(DEFAULT_ITEM_PATH);
+    OwnedObjectPath::try_from(path).map_err(|e| e.to_string())
+}
+
+async fn menu_proxy<'a>(connection: &'a Connection, request: &'a SystrayMenuRequest) -> Result<DBusMenuProxy<'a>, String> {
+    DBusMenuProxy::builder(connection)
+        .destination(request.address.as_str())
+        .and_then(|builder| builder.path(request.menu_path.as_str()))
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Activates an item or one of its menu items.
+#[tauri::command]
+pub async fn activate_tray_item(payload: SystrayActivateRequest, state: State<'_, SystemTrayState>) -> Result<(), String> {
+    let request = match payload {
+        SystrayActivateRequest::MenuItem { address, menu_path, submenu_id } => {
+            ActivateRequest::MenuItem { address, menu_path, submenu_id }
+        }
+        SystrayActivateRequest::Primary { address, x, y } => ActivateRequest::Default { address, x, y },
+        SystrayActivateRequest::Secondary { address, x, y } => ActivateRequest::Secondary { address, x, y },
+    };
+    state.client.activate(request).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn scroll_tray_item(payload: SystrayScrollRequest, state: State<'_, SystemTrayState>) -> Result<(), String> {
+    let connection = session(&state)?;
+    let path = item_path(connection, &payload.address).await?;
+    let item = StatusNotifierItemProxy::builder(connection)
+        .destination(payload.address.as_str())
+        .and_then(|builder| builder.path(path))
+        .map_err(|e| e.to_string())?
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let orientation = match payload.orientation {
+        SystrayScrollOrientation::Vertical => "vertical",
+        SystrayScrollOrientation::Horizontal => "horizontal",
+    };
+    item.scroll(payload.delta, orientation).await.map_err(|e| e.to_string())
+}
+
+/// Lets the application know a menu is being shown. Some applications, like Steam and Discord,
+/// only fill in their menus once they've been told this.
+#[tauri::command]
+pub async fn tray_menu_opened(payload: SystrayMenuRequest, state: State<'_, SystemTrayState>) -> Result<(), String> {
+    let connection = session(&state)?;
+    let menu = menu_proxy(connection, &payload).await?;
+
+    // The new layout arrives through the client's menu updates, so the result doesn't matter here.
+    // Not every application implements this, so errors are only logged.
+    if let Err(e) = menu.about_to_show(payload.submenu_id).await {
+        eprintln!("Failed to call AboutToShow on {}{}: {}", payload.address, payload.menu_path, e);
+    }
+    menu.event(payload.submenu_id, "opened", &Value::I32(0), 0).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn tray_menu_closed(payload: SystrayMenuRequest, state: State<'_, SystemTrayState>) -> Result<(), String> {
+    let connection = session(&state)?;
+    let menu = menu_proxy(connection, &payload).await?;
+    menu.event(payload.submenu_id, "closed", &Value::I32(0), 0).await.map_err(|e| e.to_string())
+}
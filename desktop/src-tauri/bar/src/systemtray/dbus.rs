@@ -0,0 +1,31 @@
+use zbus::{Result, proxy};
+use zvariant::Value;
+
+/// See https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/StatusNotifierItem/
+#[proxy(interface = "org.kde.StatusNotifierItem")]
+pub(super) trait StatusNotifierItem {
+    /// `orientation` is "vertical" or "horizontal".
+    fn scroll(&self, delta: i32, orientation: &str) -> Result<()>;
+}
+
+#[proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+pub(super) trait StatusNotifierWatcher {
+    /// Each item is its bus name followed by its object path, or just a bus name if it uses the default path.
+    #[zbus(property)]
+    fn registered_status_notifier_items(&self) -> Result<Vec<String>>;
+}
+
+/// See https://github.com/AyatanaIndicators/libdbusmenu/blob/master/libdbusmenu-glib/dbus-menu.xml
+#[proxy(interface = "com.canonical.dbusmenu")]
+pub(super) trait DBusMenu {
+    /// Tells the application a menu is about to be shown, so it can fill it in.
+    /// Returns whether the layout needs to be updated.
+    fn about_to_show(&self, id: i32) -> Result<bool>;
+
+    /// Sends an event like "clicked", "hovered", "opened" or "closed" for a menu item.
+    fn event(&self, id: i32, event_id: &str, data: &Value<'_>, timestamp: u32) -> Result<()>;
+}
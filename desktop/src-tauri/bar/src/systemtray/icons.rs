@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use launcher::{desktop_files::DesktopFiles, freedesktop_icon_lookup::Cache};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::systemtray::symbolic::{self, SymbolicColors};
+
+/// Formats the webview can draw.
+const ICON_EXTENSIONS: [&str; 2] = ["svg", "png"];
+/// Custom theme paths are a theme or two of size directories, so there's no need to go deeper.
+const MAX_THEME_PATH_DEPTH: usize = 4;
+
+/// How long to wait for the launcher to manage its icon cache. The plugins are set up one after another,
+/// so if it's loaded at all, it shows up almost immediately.
+const LAUNCHER_WAIT: Duration = Duration::from_secs(2);
+const LAUNCHER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits until the launcher's desktop files are managed, or until it's clear the launcher isn't loaded.
+/// The bar plugin is set up before the launcher, so otherwise the first icons could miss the user's theme.
+pub async fn wait_for_launcher<R: Runtime>(app_handle: &AppHandle<R>) {
+    let start = Instant::now();
+    while app_handle.try_state::<DesktopFiles>().is_none() && start.elapsed() < LAUNCHER_WAIT {
+        tokio::time::sleep(LAUNCHER_POLL_INTERVAL).await;
+    }
+}
+
+/// Resolves icon names to files, using the launcher's icon cache for the user's theme.
+pub struct IconResolver {
+    /// None if the launcher isn't loaded, in which case only custom theme paths are searched.
+    cache: Option<Arc<tokio::sync::Mutex<Cache>>>,
+    symbolic_colors: SymbolicColors,
+}
+
+impl IconResolver {
+    pub fn new<R: Runtime>(app_handle: &AppHandle<R>) -> Self {
+        let cache = app_handle.try_state::<DesktopFiles>()
+            .map(|desktop_files| desktop_files.icon_cache.clone());
+        let symbolic_colors = app_handle.try_state::<Mutex<SymbolicColors>>()
+            .map(|colors| colors.lock().expect("Failed to lock symbolic colours").clone())
+            .unwrap_or_default();
+        Self { cache, symbolic_colors }
+    }
+
+    /// Finds the file for an icon name, searching the item's `IconThemePath` before the user's theme
+    /// and the themes it inherits from. Symbolic icons come back recoloured to match the bar.
+    pub fn resolve(&mut self, name: &str, theme_path: Option<&Path>) -> Option<PathBuf> {
+        let path = self.find(name, theme_path)?;
+        if !symbolic::is_symbolic(&path) {
+            return Some(path);
+        }
+
+        match symbolic::recolor(&path, &self.symbolic_colors) {
+            Ok(recolored) => Some(recolored),
+            Err(e) => {
+                eprintln!("Failed to recolour {}: {}", path.display(), e);
+                Some(path)
+            }
+        }
+    }
+
+    fn find(&self, name: &str, theme_path: Option<&Path>) -> Option<PathBuf> {
+        if name.is_empty() {
+            return None;
+        }
+
+        // Some items send a path instead of a name
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+
+        theme_path
+            .and_then(|theme_path| search_theme_path(theme_path, name))
+            .or_else(|| self.lookup(name))
+    }
+
+    /// Looks an icon up in the user's theme. The launcher's cache is only held for the lookup,
+    /// so launcher searches aren't kept waiting while a whole tray event is handled.
+    fn lookup(&self, name: &str) -> Option<PathBuf> {
+        let cache = self.cache.as_ref()?;
+        // The cache's lock is async, but the items are resolved while holding a sync lock
+        let mut cache = match cache.try_lock() {
+            Ok(cache) => cache,
+            Err(_) => tokio::task::block_in_place(|| cache.blocking_lock()),
+        };
+        cache.lookup(name, None)
+    }
+}
+
+/// Ranks a candidate file: scalable icons first, then larger ones.
+/// Sized directories are named like "22x22" or "22x22@2".
+fn rank(path: &Path) -> Option<(bool, u32)> {
+    let extension = path.extension()?.to_str()?;
+    if !ICON_EXTENSIONS.contains(&extension) {
+        return None;
+    }
+
+    let size = path.parent()
+        .into_iter()
+        .flat_map(|parent| parent.components().rev())
+        .find_map(|component| component.as_os_str().to_str()?.split(['x', '@']).next()?.parse().ok())
+        .unwrap_or(0);
+    Some((extension == "svg", size))
+}
+
+/// The icons in an item's own icon directory, by name. These don't always have an index.theme,
+/// so rather than treating it as a theme, every file in it is indexed.
+struct ThemePathIndex {
+    /// Every directory that was read, with when it was last modified.
+    directories: Vec<(PathBuf, Option<SystemTime>)>,
+    icons: HashMap<String, ((bool, u32), PathBuf)>,
+}
+
+/// Indexes by theme path, so each is only walked once rather than on every icon change.
+static THEME_PATHS: LazyLock<Mutex<HashMap<PathBuf, ThemePathIndex>>> = LazyLock::new(Default::default);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl ThemePathIndex {
+    fn build(root: &Path) -> Self {
+        let mut index = ThemePathIndex { directories: Vec::new(), icons: HashMap::new() };
+        let mut directories = vec![(root.to_path_buf(), 0)];
+
+        while let Some((directory, depth)) = directories.pop() {
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+            index.directories.push((directory.clone(), modified(&directory)));
+
+            for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                if path.is_dir() {
+                    if depth < MAX_THEME_PATH_DEPTH {
+                        directories.push((path, depth + 1));
+                    }
+                    continue;
+                }
+
+                let (Some(name), Some(rank)) = (path.file_stem().and_then(|stem| stem.to_str()), rank(&path)) else {
+                    continue;
+                };
+                if index.icons.get(name).is_none_or(|(best_rank, _)| rank > *best_rank) {
+                    index.icons.insert(name.to_string(), (rank, path));
+                }
+            }
+        }
+        index
+    }
+
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        self.icons.get(name).map(|(_, path)| path).filter(|path| path.is_file()).cloned()
+    }
+
+    /// Whether files were added or removed since the index was built. Adding or removing a file
+    /// changes its directory's modification time, so this is much cheaper than walking it again.
+    fn is_stale(&self) -> bool {
+        self.directories.iter().any(|(directory, built)| modified(directory) != *built)
+    }
+}
+
+/// Searches an item's own icon directory.
+fn search_theme_path(root: &Path, name: &str) -> Option<PathBuf> {
+    let mut indexes = THEME_PATHS.lock().expect("Failed to lock theme path indexes");
+    let index = indexes.entry(root.to_path_buf()).or_insert_with(|| ThemePathIndex::build(root));
+    if let Some(path) = index.find(name) {
+        return Some(path);
+    }
+
+    // Some items write new icons there as they go, so look again if anything changed.
+    // Most misses are for icons in the user's theme, which only costs a stat per directory.
+    if !index.is_stale() {
+        return None;
+    }
+    *index = ThemePathIndex::build(root);
+    index.find(name)
+}
@@ -1,129 +1,227 @@
-use std::{collections::HashMap, ops::Deref, sync::Mutex, time::Duration};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
 
-use system_tray::client::{self, Client, UpdateEvent};
+use system_tray::client::{self, Client};
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::{systemtray::{debouncer::Debouncer, types::{SystemTrayItem, SystemTrayItems, SystrayIcon, SystrayMenu, SystrayTooltip}}, BarHandler};
+use crate::{systemtray::{icons::{wait_for_launcher, IconResolver}, rules::TrayRules, types::{SystemTrayItem, SystemTrayItems, SystrayItemPatch, SystrayItemsChange, SystrayItemsEvent, SystrayItemsSnapshot, SystrayLayout}}, BarHandler};
 
+mod actions;
+mod dbus;
+mod icons;
+mod pixmaps;
+mod rules;
+mod symbolic;
 mod types;
-mod debouncer;
+
+pub use actions::{activate_tray_item, scroll_tray_item, tray_menu_closed, tray_menu_opened};
+pub use pixmaps::{serve_pixmap, PIXMAP_SCHEME};
+pub use symbolic::SymbolicColors;
 
 pub struct SystemTrayState {
     client: Client,
-    current_items: Mutex<SystemTrayItems>
+    /// For the calls the client doesn't make for us, like scrolling and menu events.
+    connection: Option<zbus::Connection>,
+    rules: TrayRules,
+    items: Mutex<TrayItems>
 }
 
-impl BarHandler {
-    pub async fn start_system_tray_events<R: Runtime>(&mut self, app_handle: &AppHandle<R>) {
-        let client = Client::new().await.expect("Failed to create system tray client");
+/// Every item the client knows about, and what the frontend was last sent.
+struct TrayItems {
+    version: u32,
+    /// Items by address as the client sees them, before any rules, in the order they appeared.
+    all: Vec<(String, SystemTrayItem)>,
+    /// The items that aren't hidden, with the rules applied.
+    shown: SystemTrayItems,
+    layout: SystrayLayout
+}
+
+impl TrayItems {
+    fn get_mut(&mut self, address: &str) -> Option<&mut SystemTrayItem> {
+        self.all.iter_mut().find(|(item_address, _)| item_address == address).map(|(_, item)| item)
+    }
+
+    fn snapshot(&self) -> SystrayItemsSnapshot {
+        SystrayItemsSnapshot {
+            version: self.version,
+            items: self.shown.clone(),
+            layout: self.layout.clone()
+        }
+    }
+
+    /// Re-applies the rules to an item after it changed, returning the change as the frontend sees it.
+    /// Changes to hidden items return None.
+    fn refresh(&mut self, rules: &TrayRules, icons: &mut IconResolver, address: &str, patch: Option<SystrayItemPatch>) -> Option<SystrayItemsChange> {
+        let item = self.all.iter()
+            .find(|(item_address, _)| item_address == address)
+            .and_then(|(_, item)| rules.apply(icons, item));
+        let address = address.to_string();
+
+        let Some(item) = item else {
+            return self.shown.remove(&address).map(|_| SystrayItemsChange::Removed { address });
+        };
+        let was_shown = self.shown.insert(address.clone(), item.clone()).is_some();
+        match patch {
+            Some(patch) if was_shown => Some(SystrayItemsChange::Updated { patch: patch.reread(&item), address }),
+            _ => Some(SystrayItemsChange::Added { address, item })
+        }
+    }
+
+    /// Keeps the pixmaps the items show in the cache, however many frames animated icons send.
+    fn pin_pixmaps(&self) {
+        pixmaps::pin_pixmaps(self.all.iter().flat_map(|(_, item)| item.pixmap_urls()));
+    }
 
-        // Most of the time, this doesn't have initial items, but it seems like it sometimes does?
-        let tray_items = client.items();
+    /// Recomputes the layout, returning it if it changed.
+    fn relayout(&mut self, rules: &TrayRules) -> Option<SystrayLayout> {
+        let shown = self.all.iter().filter_map(|(address, _)| self.shown.get_key_value(address));
+        let layout = rules.layout(shown);
+        if layout == self.layout {
+            return None;
+        }
+        self.layout = layout.clone();
+        Some(layout)
+    }
+}
+
+impl SystemTrayState {
+    /// Rebuilds every item from the client's own copy, bumping the version so the frontend
+    /// drops any changes it has queued from before.
+    fn resync(&self, icons: &mut IconResolver) -> SystrayItemsSnapshot {
+        let tray_items = self.client.items();
         let tray_items = tray_items.lock().unwrap();
+        let mut current = self.items.lock().expect("Failed to lock system tray state");
 
-        let mut items = HashMap::new();
-        for (id, item) in tray_items.iter() {
-            let (item, menu) = item;
-            items.insert(id.clone(), SystemTrayItem::new(item, menu));
+        // Keep the items we already had where they were
+        let mut all = Vec::new();
+        for (address, _) in current.all.iter() {
+            if let Some((item, menu)) = tray_items.get(address) {
+                all.push((address.clone(), SystemTrayItem::new(icons, item, menu)));
+            }
+        }
+        for (address, (item, menu)) in tray_items.iter() {
+            if !all.iter().any(|(known, _)| known == address) {
+                all.push((address.clone(), SystemTrayItem::new(icons, item, menu)));
+            }
         }
-        
-        let state: SystemTrayState = SystemTrayState { client, current_items: Mutex::new(SystemTrayItems(items)) };
 
-        let mut event_stream = state.client.subscribe();
-        app_handle.manage(state);
+        current.shown = SystemTrayItems(all.iter()
+            .filter_map(|(address, item)| Some((address.clone(), self.rules.apply(icons, item)?)))
+            .collect());
+        current.all = all;
+        current.pin_pixmaps();
+        current.relayout(&self.rules);
+        current.version = current.version.wrapping_add(1);
+        current.snapshot()
+    }
+
+    /// Applies an event from the client, returning the change to send to the frontend, if any.
+    fn handle_event(&self, icons: &mut IconResolver, event: client::Event) -> Option<SystrayItemsEvent> {
+        let mut current = self.items.lock().expect("Failed to lock system tray state");
+        let (address, patch) = match event {
+            client::Event::Add(address, item) => {
+                let item = SystemTrayItem::new(icons, &item, &None);
+                match current.get_mut(&address) {
+                    Some(existing) => *existing = item,
+                    None => current.all.push((address.clone(), item))
+                }
+                (address, None)
+            }
+            client::Event::Remove(address) => {
+                current.all.retain(|(item_address, _)| *item_address != address);
+                (address, None)
+            }
+            client::Event::Update(address, event) => {
+                let Some(item) = current.get_mut(&address) else {
+                    eprintln!("Ignoring an update for unknown tray item {}", address);
+                    return None;
+                };
+                let patch = item.apply_update(icons, event);
+                (address, Some(patch))
+            }
+        };
+        current.pin_pixmaps();
+
+        // Nothing to send if the item is hidden
+        let change = current.refresh(&self.rules, icons, &address, patch)?;
+        let layout = current.relayout(&self.rules);
 
+        current.version = current.version.wrapping_add(1);
+        Some(SystrayItemsEvent { version: current.version, change, layout })
+    }
+}
+
+/// How long to wait before trying to create the client again. Doubles after each failure, up to the maximum.
+const CLIENT_RETRY_DELAY: Duration = Duration::from_secs(1);
+const CLIENT_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+async fn create_client() -> Client {
+    let mut delay = CLIENT_RETRY_DELAY;
+    loop {
+        match Client::new().await {
+            Ok(client) => return client,
+            Err(e) => {
+                eprintln!("Failed to create system tray client, retrying in {}s: {}", delay.as_secs(), e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(CLIENT_RETRY_MAX_DELAY);
+            }
+        }
+    }
+}
+
+impl BarHandler {
+    pub fn start_system_tray_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let config = self.config.systray.clone();
         let app_handle = app_handle.clone();
         tauri::async_runtime::spawn(async move {
-            let app_handle_2 = app_handle.clone();
-            let mut debouncer = Debouncer::<SystemTrayItems>::new(
-                Duration::from_millis(100),
-                move |items| {
-                    // TODO: Diffing or something? We should at least only update the item that changed.
-                    app_handle_2.emit("update_tray_items", items).unwrap();
-                }
-            );
+            // The tray commands aren't available until this works, but everything else is
+            let client = create_client().await;
+            let connection = zbus::Connection::session().await
+                .map_err(|e| eprintln!("Failed to connect to the session bus: {}", e))
+                .ok();
 
-            while let Ok(event) = event_stream.recv().await {
+            // Subscribe before reading the items, so nothing is missed in between.
+            // Applying an event the items already include is harmless.
+            let mut event_stream = client.subscribe();
+            let state: SystemTrayState = SystemTrayState {
+                client,
+                connection,
+                rules: TrayRules::new(config),
+                items: Mutex::new(TrayItems {
+                    version: 0,
+                    all: Vec::new(),
+                    shown: SystemTrayItems(HashMap::new()),
+                    layout: SystrayLayout::default()
+                })
+            };
+            // Theme icons can't be found until the launcher's icon cache is managed
+            wait_for_launcher(&app_handle).await;
+            // Most of the time, this doesn't have initial items, but it seems like it sometimes does?
+            let snapshot = state.resync(&mut IconResolver::new(&app_handle));
+            app_handle.manage(state);
+            // The frontend may have asked for the items before there was anything to answer with
+            let _ = app_handle.emit("tray_items_resynced", snapshot);
+
+            loop {
                 let state = app_handle.state::<SystemTrayState>();
-                let items = &mut state.current_items.lock().expect("Failed to lock system tray state");
-                match event {
-                    client::Event::Add(id, item) => {
-                        items.insert(id, SystemTrayItem::new(&item, &None));
-                    }
-                    client::Event::Remove(id) => {
-                        items.remove(&id);
+                let event = match event_stream.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("Missed {} system tray events; resyncing", skipped);
+                        let snapshot = state.resync(&mut IconResolver::new(&app_handle));
+                        let _ = app_handle.emit("tray_items_resynced", snapshot);
+                        continue;
                     }
-                    client::Event::Update(id, event) => {
-                        let Some(item) = items.get_mut(&id) else { return };
-                        let theme = if let SystrayIcon::FreedesktopIcon { theme, .. } = &item.icon {
-                            Some(theme.clone())
-                        } else {
-                            None
-                        };
-                        match event {
-                            UpdateEvent::Tooltip(tooltip) => {
-                                item.tooltip = tooltip.map(|tooltip| {
-                                    SystrayTooltip::new(tooltip, theme)
-                                });
-                            }
-                            UpdateEvent::Title(title) => {
-                                item.title = title;
-                            }
-                            UpdateEvent::Status(status) => {
-                                item.status = status.into();
-                            }
-                            UpdateEvent::Icon { icon_name, icon_pixmap } => {
-                                item.icon = SystrayIcon::from_data_default(theme, icon_name, icon_pixmap);
-                            }
-                            UpdateEvent::OverlayIcon(name) => {
-                                item.overlay_icon = SystrayIcon::from_data(
-                                    theme.clone(),
-                                    name,
-                                    None
-                                );
-                            }
-                            UpdateEvent::AttentionIcon(name) => {
-                                item.attention_icon = SystrayIcon::from_data(
-                                    theme.clone(),
-                                    name,
-                                    None
-                                );
-                            }
-                            UpdateEvent::Menu(full_menu) => {
-                                match item.menu {
-                                    Some(ref mut menu) => {
-                                        menu.update(full_menu);
-                                    }
-                                    None => {
-                                        item.menu = Some(SystrayMenu::new(full_menu, None));
-                                    }
-                                }
-                            }
-                            UpdateEvent::MenuConnect(dbus_name) => {
-                                match item.menu {
-                                    Some(ref mut menu) => {
-                                        menu.dbus_path = Some(dbus_name);
-                                    }
-                                    None => {
-                                        item.menu = Some(SystrayMenu::partial(dbus_name));
-                                    }
-                                }
-                            }
-                            UpdateEvent::MenuDiff(diffs) => {
-                                for diff in diffs {
-                                    if let Some(menu) = &mut item.menu {
-                                        menu.apply_diff(diff);
-                                    } else {
-                                        println!("Menu diff event received for tray item without menu: {:?}", diff);
-                                    }
-                                }
-                            }
-                        }
+                    Err(RecvError::Closed) => {
+                        eprintln!("System tray client stopped sending events");
+                        break;
                     }
-                }
+                };
 
-                // Send the updated state to the frontend
-                debouncer.call((*items).clone());
+                let mut icons = IconResolver::new(&app_handle);
+                if let Some(event) = state.handle_event(&mut icons, event) {
+                    let _ = app_handle.emit("tray_items_changed", event);
+                }
             }
         });
     }
@@ -132,7 +230,42 @@ impl BarHandler {
 #[tauri::command]
 pub async fn get_systray_items(
     state: State<'_, SystemTrayState>
-) -> Result<SystemTrayItems, ()> {
-    let items = state.current_items.lock().expect("Failed to lock system tray state");
-    Ok(items.deref().clone())
-}
\ No newline at end of file
+) -> Result<SystrayItemsSnapshot, ()> {
+    let items = state.items.lock().expect("Failed to lock system tray state");
+    Ok(items.snapshot())
+}
+
+/// Rebuilds the items from scratch, for when the frontend missed a change.
+#[tauri::command]
+pub async fn resync_systray_items<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, SystemTrayState>
+) -> Result<SystrayItemsSnapshot, ()> {
+    Ok(state.resync(&mut IconResolver::new(&app_handle)))
+}
+
+/// Sets the colours symbolic icons are drawn in, and redraws the items if they changed.
+#[tauri::command]
+pub async fn set_symbolic_colors<R: Runtime>(
+    app_handle: AppHandle<R>,
+    payload: SymbolicColors,
+    colors: State<'_, Mutex<SymbolicColors>>
+) -> Result<(), String> {
+    if let Err(e) = symbolic::remove_other_colors(&payload) {
+        eprintln!("Failed to remove old recoloured icons: {}", e);
+    }
+    {
+        let mut colors = colors.lock().expect("Failed to lock symbolic colours");
+        if *colors == payload {
+            return Ok(());
+        }
+        *colors = payload;
+    }
+
+    // If the tray isn't up yet, it picks up the new colours when it is
+    if let Some(state) = app_handle.try_state::<SystemTrayState>() {
+        let snapshot = state.resync(&mut IconResolver::new(&app_handle));
+        let _ = app_handle.emit("tray_items_resynced", snapshot);
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use system_tray::item::IconPixmap;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Runtime, UriSchemeContext};
+
+/// The URI scheme the frontend loads tray pixmaps from.
+pub const PIXMAP_SCHEME: &str = "traypixmap";
+/// Pixmap URLs are this followed by the hash.
+const URL_PREFIX: &str = "traypixmap://localhost/";
+
+/// Animated icons send a new pixmap every frame, so only keep the most recent ones.
+/// Pixmaps that items are showing are kept regardless.
+const MAX_CACHED_PIXMAPS: usize = 256;
+
+struct EncodedPixmap {
+    width: u32,
+    height: u32,
+    png: Vec<u8>,
+}
+
+#[derive(Default)]
+struct PixmapCache {
+    /// Every size an item sent for one icon, keyed by the hash of their contents.
+    pixmaps: HashMap<String, Arc<Vec<EncodedPixmap>>>,
+    /// Hashes from least to most recently used.
+    recent: VecDeque<String>,
+    /// Hashes of the pixmaps the current items show, which are never evicted.
+    pinned: HashSet<String>,
+}
+
+impl PixmapCache {
+    /// Evicts the least recently used pixmaps that aren't pinned until there's room, never evicting `keep`.
+    fn evict(&mut self, keep: Option<&str>) {
+        while self.pixmaps.len() > MAX_CACHED_PIXMAPS {
+            let evictable = |hash: &String| Some(hash.as_str()) != keep && !self.pinned.contains(hash);
+            let Some(index) = self.recent.iter().position(evictable) else {
+                // Everything left is in use
+                return;
+            };
+            if let Some(oldest) = self.recent.remove(index) {
+                self.pixmaps.remove(&oldest);
+            }
+        }
+    }
+}
+
+static CACHE: LazyLock<Mutex<PixmapCache>> = LazyLock::new(Default::default);
+
+fn content_hash(pixmaps: &[IconPixmap]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for pixmap in pixmaps {
+        (pixmap.width, pixmap.height, &pixmap.pixels).hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Encodes an ARGB32 pixmap in network byte order as an RGBA PNG. PNGs have straight alpha,
+/// like the pixmaps, so this only reorders the channels.
+/// Returns None if the pixel data doesn't match the size.
+fn encode_png(pixmap: &IconPixmap) -> Option<EncodedPixmap> {
+    let width = u32::try_from(pixmap.width).ok().filter(|width| *width > 0)?;
+    let height = u32::try_from(pixmap.height).ok().filter(|height| *height > 0)?;
+    if pixmap.pixels.len() != width as usize * height as usize * 4 {
+        return None;
+    }
+
+    let rgba = pixmap.pixels
+        .chunks_exact(4)
+        .flat_map(|argb| [argb[1], argb[2], argb[3], argb[0]])
+        .collect::<Vec<_>>();
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let result = encoder.write_header().and_then(|mut writer| {
+        writer.write_image_data(&rgba)?;
+        writer.finish()
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to encode tray pixmap: {}", e);
+        return None;
+    }
+
+    Some(EncodedPixmap { width, height, png })
+}
+
+/// Converts an item's pixmaps to PNGs, if they aren't cached already, and returns the URL
+/// they're served from.
+pub fn cache_pixmaps(pixmaps: &[IconPixmap]) -> String {
+    let hash = content_hash(pixmaps);
+    let mut cache = CACHE.lock().expect("Failed to lock tray pixmap cache");
+
+    if cache.pixmaps.contains_key(&hash) {
+        cache.recent.retain(|recent| *recent != hash);
+    } else {
+        let encoded = pixmaps.iter().filter_map(encode_png).collect();
+        cache.pixmaps.insert(hash.clone(), Arc::new(encoded));
+    }
+    cache.recent.push_back(hash.clone());
+    // The item this is for isn't pinned yet
+    cache.evict(Some(&hash));
+
+    format!("{}{}", URL_PREFIX, hash)
+}
+
+/// Pins the pixmaps behind these URLs, unpinning all others. Called with every pixmap URL
+/// the items use after they change, so an item's icon is never evicted while it's shown.
+pub fn pin_pixmaps<'a>(urls: impl Iterator<Item = &'a str>) {
+    let pinned = urls.filter_map(|url| url.strip_prefix(URL_PREFIX)).map(str::to_string).collect();
+    let mut cache = CACHE.lock().expect("Failed to lock tray pixmap cache");
+    cache.pinned = pinned;
+    cache.evict(None);
+}
+
+/// The smallest pixmap at least `size` pixels wide, or the largest one if they're all smaller.
+fn best_size(pixmaps: &[EncodedPixmap], size: Option<u32>) -> Option<&EncodedPixmap> {
+    let largest = pixmaps.iter().max_by_key(|pixmap| pixmap.width * pixmap.height);
+    let Some(size) = size else {
+        return largest;
+    };
+
+    pixmaps.iter()
+        .filter(|pixmap| pixmap.width >= size && pixmap.height >= size)
+        .min_by_key(|pixmap| pixmap.width * pixmap.height)
+        .or(largest)
+}
+
+/// Serves `traypixmap://localhost/<hash>?size=<pixels>`, where the size is the icon's size
+/// in device pixels.
+pub fn serve_pixmap<R: Runtime>(_context: UriSchemeContext<'_, R>, request: Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+    let hash = request.uri().path().trim_start_matches('/');
+    let size = request.uri().query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("size=")))
+        .and_then(|size| size.parse().ok());
+
+    let pixmaps = CACHE.lock().expect("Failed to lock tray pixmap cache").pixmaps.get(hash).cloned();
+    let png = pixmaps.as_deref().and_then(|pixmaps| best_size(pixmaps, size)).map(|pixmap| pixmap.png.clone());
+
+    let response = match png {
+        Some(png) => Response::builder()
+            .header(header::CONTENT_TYPE, "image/png")
+            // The URL changes with the contents, so this never goes stale
+            .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+            .body(Cow::Owned(png)),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Cow::Borrowed(&[][..])),
+    };
+    response.expect("Failed to build tray pixmap response")
+}
@@ -0,0 +1,77 @@
+use crate::config::{SystrayConfig, SystrayRule};
+use crate::systemtray::icons::IconResolver;
+use crate::systemtray::types::{SystemTrayItem, SystrayIcon, SystrayItemStatus, SystrayLayout, SystrayTooltip};
+
+/// Applies the user's tray rules from the config, so the frontend only sees what it should show.
+pub struct TrayRules {
+    config: SystrayConfig,
+}
+
+impl TrayRules {
+    pub fn new(config: SystrayConfig) -> Self {
+        Self { config }
+    }
+
+    fn rule_for(&self, item: &SystemTrayItem) -> Option<&SystrayRule> {
+        self.config.rules.iter().find(|rule| {
+            (rule.id.is_some() || rule.title.is_some())
+                && rule.id.as_ref().is_none_or(|id| *id == item.id)
+                && rule.title.as_ref().is_none_or(|title| item.title.as_ref() == Some(title))
+        })
+    }
+
+    /// The item as the frontend should see it, or None if it's hidden.
+    pub fn apply(&self, icons: &mut IconResolver, item: &SystemTrayItem) -> Option<SystemTrayItem> {
+        let Some(rule) = self.rule_for(item) else {
+            return Some(item.clone());
+        };
+        if rule.hide {
+            return None;
+        }
+
+        let mut item = item.clone();
+        if let Some(name) = &rule.icon {
+            item.icon = SystrayIcon::FreedesktopIcon {
+                name: name.clone(),
+                path: icons.resolve(name, item.theme_path()),
+            };
+        }
+        if let Some(title) = &rule.tooltip {
+            let tooltip = item.tooltip.get_or_insert_with(|| SystrayTooltip {
+                icon: item.icon.clone(),
+                title: String::new(),
+                description: String::new(),
+            });
+            tooltip.title = title.clone();
+        }
+        Some(item)
+    }
+
+    /// Orders the shown items and picks out the ones that go in the overflow.
+    /// `items` should be in the order they appeared, which is kept for items that aren't pinned.
+    pub fn layout<'a>(&self, items: impl Iterator<Item = (&'a String, &'a SystemTrayItem)>) -> SystrayLayout {
+        let mut placed = items
+            .enumerate()
+            .map(|(index, (address, item))| {
+                let rule = self.rule_for(item);
+                let position = rule.and_then(|rule| rule.position);
+                let overflow_passive = rule
+                    .and_then(|rule| rule.overflow_passive)
+                    .unwrap_or(self.config.overflow_passive);
+                let overflow = overflow_passive && matches!(item.status, SystrayItemStatus::Passive);
+                ((position.is_none(), position, index), address.clone(), overflow)
+            })
+            .collect::<Vec<_>>();
+        placed.sort_by_key(|(order, _, _)| *order);
+
+        let mut layout = SystrayLayout::default();
+        for (_, address, overflow) in placed {
+            if overflow {
+                layout.overflow.push(address);
+            } else {
+                layout.shown.push(address);
+            }
+        }
+        layout
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+use ts_rs::TS;
+
+/// The colours symbolic icons are drawn in. The frontend sends these from the bar's stylesheet,
+/// so they match the rest of the bar. Any CSS colour works.
+#[derive(Clone, Debug, Hash, PartialEq, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SymbolicColors {
+    pub foreground: String,
+    pub success: String,
+    pub warning: String,
+    pub error: String,
+}
+
+/// The stylesheet's defaults, for icons resolved before the frontend sends its colours.
+impl Default for SymbolicColors {
+    fn default() -> Self {
+        Self {
+            foreground: "#ffffff".to_string(),
+            success: "#33d17a".to_string(),
+            warning: "#f6d32d".to_string(),
+            error: "#e01b24".to_string(),
+        }
+    }
+}
+
+/// The recoloured copy of each icon for the current colours, so they're only read and hashed once.
+#[derive(Default)]
+struct Recolored {
+    colors: SymbolicColors,
+    /// By source file, with the source's modification time when it was copied.
+    copies: HashMap<PathBuf, (Option<SystemTime>, PathBuf)>,
+}
+
+static RECOLORED: LazyLock<Mutex<Recolored>> = LazyLock::new(Default::default);
+
+/// Symbolic icons are drawn in a single placeholder colour and expect the toolkit to fill in the
+/// theme's, so they come out black if they're shown as-is.
+pub fn is_symbolic(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "svg")
+        && path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.ends_with("-symbolic"))
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("desktop").join("symbolic-icons"))
+}
+
+/// Each set of colours gets its own directory, so the copies for old colours are easy to remove.
+fn colors_dir_name(colors: &SymbolicColors) -> String {
+    let mut hasher = DefaultHasher::new();
+    colors.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// The stylesheet GTK applies to symbolic icons, with our colours.
+fn stylesheet(colors: &SymbolicColors) -> String {
+    format!(
+        "<style type=\"text/css\">\
+            rect,circle,path,.foreground-fill{{fill:{fg}!important;}}\
+            .success{{fill:{success}!important;}}\
+            .warning{{fill:{warning}!important;}}\
+            .error{{fill:{error}!important;}}\
+            .foreground-stroke{{stroke:{fg}!important;}}\
+            .success-stroke{{stroke:{success}!important;}}\
+            .warning-stroke{{stroke:{warning}!important;}}\
+            .error-stroke{{stroke:{error}!important;}}\
+        </style>",
+        fg = colors.foreground,
+        success = colors.success,
+        warning = colors.warning,
+        error = colors.error,
+    )
+}
+
+/// Inserts the stylesheet as the first child of the root element, so it applies to everything.
+fn recolor_svg(svg: &str, colors: &SymbolicColors) -> Option<String> {
+    let root = svg.find("<svg")?;
+    let content = root + svg[root..].find('>')? + 1;
+    Some(format!("{}{}{}", &svg[..content], stylesheet(colors), &svg[content..]))
+}
+
+/// Returns a copy of a symbolic icon drawn in the given colours. Copies are written to the cache
+/// directory, named by the icon's contents, in a directory for the colours.
+pub fn recolor(path: &Path, colors: &SymbolicColors) -> io::Result<PathBuf> {
+    let modified = fs::metadata(path)?.modified().ok();
+    let mut recolored = RECOLORED.lock().expect("Failed to lock recoloured icons");
+    if recolored.colors != *colors {
+        recolored.colors = colors.clone();
+        recolored.copies.clear();
+    }
+    if let Some((copied, copy)) = recolored.copies.get(path) {
+        if *copied == modified && copy.exists() {
+            return Ok(copy.clone());
+        }
+    }
+
+    let svg = fs::read_to_string(path)?;
+    let mut hasher = DefaultHasher::new();
+    svg.hash(&mut hasher);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("icon");
+
+    let dir = cache_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No cache directory"))?
+        .join(colors_dir_name(colors));
+    let copy = dir.join(format!("{}-{:016x}.svg", stem, hasher.finish()));
+    if !copy.exists() {
+        let svg = recolor_svg(&svg, colors)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not an SVG"))?;
+        fs::create_dir_all(&dir)?;
+        // Write to a temporary file first so a half-written icon is never picked up
+        let temp_path = copy.with_extension("svg.tmp");
+        fs::write(&temp_path, svg)?;
+        fs::rename(&temp_path, &copy)?;
+    }
+
+    recolored.copies.insert(path.to_path_buf(), (modified, copy.clone()));
+    Ok(copy)
+}
+
+/// Removes the copies made for any other colours, which won't be used again.
+pub fn remove_other_colors(colors: &SymbolicColors) -> io::Result<()> {
+    let Some(dir) = cache_dir() else {
+        return Ok(());
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let keep = colors_dir_name(colors);
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_str() == Some(keep.as_str()) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,11 @@
-use std::{collections::HashMap, ops::{Deref, DerefMut}};
+use std::{collections::HashMap, ops::{Deref, DerefMut}, path::{Path, PathBuf}};
 
 use serde::{Deserialize, Serialize};
-use system_tray::{item::{IconPixmap, Status, StatusNotifierItem, Tooltip}, menu::{Disposition, MenuDiff, MenuItem, MenuType, ToggleState, ToggleType, TrayMenu}};
+use system_tray::{client::UpdateEvent, item::{IconPixmap, Status, StatusNotifierItem, Tooltip}, menu::{Disposition, MenuDiff, MenuItem, MenuType, ToggleState, ToggleType, TrayMenu}};
 use ts_rs::TS;
 
+use crate::systemtray::{icons::IconResolver, pixmaps};
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
 pub enum SystrayItemStatus {
@@ -24,67 +26,52 @@ impl From<Status> for SystrayItemStatus {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
-#[ts(export, export_to="../../bindings/SystemTray.ts")]
-pub struct SystrayPixmap {
-    width: i32,
-    height: i32,
-    /// ARGB32 binary representation of the icon in network byte order.
-    /// The format of icon data is described in Section Icons
-    pixels: Vec<u8>
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
 pub enum SystrayIcon {
     FreedesktopIcon {
-        theme: String,
-        name: String
+        name: String,
+        /// The icon's file, or None if it isn't in the item's theme path or any installed theme.
+        path: Option<PathBuf>
     },
-    Pixmaps {
-        /// The icons in this pixmap icon.
-        /// Applications can provide multiple icons either for animations or
-        /// multiple resolutions of the same icon.
+    Pixmap {
+        /// Where the icon is served as a PNG. Applications can provide multiple resolutions
+        /// of the same icon, so append `?size=<pixels>` to get the one closest to that size.
         /// See https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/Icons/
-        /// Note that the spec doesn't technically forbid including multiple icons _and_ animations.
-        icons: Vec<SystrayPixmap>
+        url: String
     }
 }
 
 impl SystrayIcon {
-    pub fn from_data(theme_path: Option<String>, icon_name: Option<String>, pixmap: Option<Vec<IconPixmap>>) -> Option<Self> {
-        match (theme_path, icon_name, pixmap) {
+    pub fn from_data(icons: &mut IconResolver, theme_path: Option<&Path>, icon_name: Option<String>, pixmap: Option<Vec<IconPixmap>>) -> Option<Self> {
+        let icon_name = icon_name.filter(|name| !name.is_empty());
+        let path = icon_name.as_deref().and_then(|name| icons.resolve(name, theme_path));
+        match (icon_name, path, pixmap) {
             // Prefer the theme icon
-            (Some(theme), Some(name), _) => Some(SystrayIcon::FreedesktopIcon {
-                theme,
-                name
+            (Some(name), Some(path), _) => Some(SystrayIcon::FreedesktopIcon {
+                name,
+                path: Some(path)
             }),
-            // If we don't have a theme icon, use the pixmap data
-            (_, _, Some(pixmap)) => {
-                let icons = pixmap.into_iter().map(|p| SystrayPixmap {
-                    width: p.width,
-                    height: p.height,
-                    pixels: p.pixels
-                }).collect();
-                Some(SystrayIcon::Pixmaps {
-                    icons
-                })
-            },
-            // Fallback to assume "hicolor" themep) {
-            (None, Some(name), _) => Some(SystrayIcon::FreedesktopIcon {
-                theme: "hicolor".to_string(),
-                name
+            // If we can't find the theme icon, use the pixmap data
+            (_, _, Some(pixmap)) if !pixmap.is_empty() => Some(SystrayIcon::Pixmap {
+                url: pixmaps::cache_pixmaps(&pixmap)
+            }),
+            // Keep the name so the frontend can show a placeholder
+            (Some(name), None, _) => Some(SystrayIcon::FreedesktopIcon {
+                name,
+                path: None
             }),
             // If we don't have the required data, don't return the data
             _ => None
         }
     }
 
-    pub fn from_data_default(theme_path: Option<String>, icon_name: Option<String>, pixmap: Option<Vec<IconPixmap>>) -> Self {
-        Self::from_data(theme_path, icon_name, pixmap).unwrap_or({
+    pub fn from_data_default(icons: &mut IconResolver, theme_path: Option<&Path>, icon_name: Option<String>, pixmap: Option<Vec<IconPixmap>>) -> Self {
+        Self::from_data(icons, theme_path, icon_name, pixmap).unwrap_or_else(|| {
+            let name = "application-x-executable".to_string();
             SystrayIcon::FreedesktopIcon {
-                theme: "Adwaita".to_string(),
-                name: "application-x-executable".to_string()
+                path: icons.resolve(&name, None),
+                name
             }
         })
     }
@@ -93,16 +80,17 @@ impl SystrayIcon {
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
 pub struct SystrayTooltip {
-    icon: SystrayIcon,
-    title: String,
-    description: String,
+    pub icon: SystrayIcon,
+    pub title: String,
+    pub description: String,
 }
 
 impl SystrayTooltip {
-    pub fn new(tooltip: Tooltip, theme: Option<String>) -> Self {
+    pub fn new(icons: &mut IconResolver, tooltip: Tooltip, theme_path: Option<&Path>) -> Self {
         SystrayTooltip {
             icon: SystrayIcon::from_data_default(
-                theme,
+                icons,
+                theme_path,
                 Some(tooltip.icon_name),
                 Some(tooltip.icon_data)
             ),
@@ -117,15 +105,18 @@ impl SystrayTooltip {
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
 pub enum SystrayMenuIcon {
     FreedesktopIcon {
-        name: String
+        name: String,
+        /// The icon's file, or None if it isn't in the item's theme path or any installed theme.
+        path: Option<PathBuf>
     },
     PNGData(Vec<u8>)
 }
 
 impl SystrayMenuIcon {
-    pub fn from_data(icon_name: Option<String>, icon_data: Option<Vec<u8>>) -> Option<Self> {
+    pub fn from_data(icons: &mut IconResolver, theme_path: Option<&Path>, icon_name: Option<String>, icon_data: Option<Vec<u8>>) -> Option<Self> {
         match (icon_name, icon_data) {
             (Some(name), _) => Some(SystrayMenuIcon::FreedesktopIcon {
+                path: icons.resolve(&name, theme_path),
                 name
             }),
             (_, Some(data)) => Some(SystrayMenuIcon::PNGData(data)),
@@ -247,7 +238,7 @@ pub enum SystrayMenuItem {
 }
 
 impl SystrayMenuItem {
-    pub fn new(item: MenuItem) -> Self {
+    pub fn new(icons: &mut IconResolver, theme_path: Option<&Path>, item: MenuItem) -> Self {
         match item.menu_type {
             MenuType::Separator => SystrayMenuItem::Separator {
                 id: item.id,
@@ -256,7 +247,7 @@ impl SystrayMenuItem {
             MenuType::Standard => {
                 let submenu = if item.children_display == Some("submenu".to_string()) {
                     Some(item.submenu.into_iter().map(|sub_item| {
-                        SystrayMenuItem::new(sub_item)
+                        SystrayMenuItem::new(icons, theme_path, sub_item)
                     }).collect())
                 } else { None };
                 SystrayMenuItem::Item {
@@ -265,6 +256,8 @@ impl SystrayMenuItem {
                     enabled: item.enabled,
                     visible: item.visible,
                     icon: SystrayMenuIcon::from_data(
+                        icons,
+                        theme_path,
                         item.icon_name,
                         item.icon_data
                     ),
@@ -277,7 +270,7 @@ impl SystrayMenuItem {
         }
     }
 
-    pub fn apply_diff(&mut self, diff: MenuDiff) {
+    pub fn apply_diff(&mut self, icons: &mut IconResolver, theme_path: Option<&Path>, diff: MenuDiff) {
         for item in diff.remove {
             // This is kind of a gross reborrow in my opinion, but Rust requires it.
             match (item.as_str(), &mut *self) {
@@ -286,8 +279,9 @@ impl SystrayMenuItem {
                 ("label", SystrayMenuItem::Item { label, .. }) => *label = None,
                 ("enabled", SystrayMenuItem::Item { enabled, .. }) => *enabled = false,
                 ("icon_name", SystrayMenuItem::Item { icon, .. }) => {
-                    if let Some(SystrayMenuIcon::FreedesktopIcon { name, .. }) = icon {
+                    if let Some(SystrayMenuIcon::FreedesktopIcon { name, path }) = icon {
                         *name = String::new();
+                        *path = None;
                     }
                 }
                 ("icon_data", SystrayMenuItem::Item { icon, .. }) => {
@@ -334,6 +328,8 @@ impl SystrayMenuItem {
                 }
                 if let Some(new_icon_name) = update.icon_name {
                     *icon = SystrayMenuIcon::from_data(
+                        icons,
+                        theme_path,
                         new_icon_name,
                         icon.as_ref().and_then(|icon| {
                             if let SystrayMenuIcon::PNGData(data) = icon {
@@ -346,6 +342,8 @@ impl SystrayMenuItem {
                 }
                 if let Some(new_icon_data) = update.icon_data {
                     *icon = SystrayMenuIcon::from_data(
+                        icons,
+                        theme_path,
                         icon.as_ref().and_then(|icon| {
                             if let SystrayMenuIcon::FreedesktopIcon { name, .. } = icon {
                                 Some(name.clone())
@@ -386,24 +384,24 @@ impl SystrayMenu {
         }
     }
 
-    pub fn update(&mut self, tray_menu: TrayMenu) {
+    pub fn update(&mut self, icons: &mut IconResolver, theme_path: Option<&Path>, tray_menu: TrayMenu) {
         self.id = tray_menu.id;
         self.items = tray_menu.submenus.into_iter().map(|item| {
-            SystrayMenuItem::new(item)
+            SystrayMenuItem::new(icons, theme_path, item)
         }).collect();
     }
 
-    pub fn new(tray_menu: TrayMenu, dbus_path: Option<String>) -> Self {
+    pub fn new(icons: &mut IconResolver, theme_path: Option<&Path>, tray_menu: TrayMenu, dbus_path: Option<String>) -> Self {
         Self {
             id: tray_menu.id,
             dbus_path,
             items: tray_menu.submenus.into_iter().map(|item| {
-                SystrayMenuItem::new(item)
+                SystrayMenuItem::new(icons, theme_path, item)
             }).collect(),
         }
     }
 
-    pub fn apply_diff(&mut self, diff: MenuDiff) {
+    pub fn apply_diff(&mut self, icons: &mut IconResolver, theme_path: Option<&Path>, diff: MenuDiff) {
         // This could be optimized, but meh.
         // BFS to find the correct menu item
         let mut queue = self.items.iter_mut().collect::<Vec<_>>();
@@ -411,7 +409,7 @@ impl SystrayMenu {
             match item {
                 SystrayMenuItem::Item { id, .. } => {
                     if *id == diff.id {
-                        item.apply_diff(diff);
+                        item.apply_diff(icons, theme_path, diff);
                         return;
                     }
 
@@ -422,7 +420,7 @@ impl SystrayMenu {
                 }
                 SystrayMenuItem::Separator { id, .. } => {
                     if *id == diff.id {
-                        item.apply_diff(diff);
+                        item.apply_diff(icons, theme_path, diff);
                         return;
                     }
                 }
@@ -460,47 +458,219 @@ pub struct SystemTrayItem {
     pub tooltip: Option<SystrayTooltip>,
 
     /// If this exists, we should prefer showing the menu instead of activating the top-level item.
-    pub menu: Option<SystrayMenu>
+    pub menu: Option<SystrayMenu>,
+
+    /// Where the item keeps icons that aren't in the user's theme, for resolving later updates.
+    #[serde(skip)]
+    #[ts(skip)]
+    theme_path: Option<PathBuf>
 }
 
 impl SystemTrayItem {
-    pub fn new(item: &StatusNotifierItem, menu: &Option<TrayMenu>) -> Self {
-        let theme = item.icon_theme_path.clone().map(|t| if t.trim().is_empty() {
-            "hicolor".to_string()
-        } else {
-            t.to_string()
-        });
+    pub fn new(icons: &mut IconResolver, item: &StatusNotifierItem, menu: &Option<TrayMenu>) -> Self {
+        let theme_path = item.icon_theme_path.as_deref()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        let theme = theme_path.as_deref();
         SystemTrayItem {
             id: item.id.clone(),
             title: item.title.clone(),
             status: item.status.into(),
             icon: SystrayIcon::from_data_default(
-                theme.clone(),
+                icons,
+                theme,
                 item.icon_name.clone(),
                 item.icon_pixmap.clone()
             ),
             // Temporary placeholder data
             overlay_icon: SystrayIcon::from_data(
-                theme.clone(),
+                icons,
+                theme,
                 item.overlay_icon_name.clone(),
                 item.overlay_icon_pixmap.clone()
             ),  
             attention_icon: SystrayIcon::from_data(
-                theme.clone(),
+                icons,
+                theme,
                 item.attention_icon_name.clone(),
                 item.attention_icon_pixmap.clone()
             ),
             tooltip: item.tool_tip.clone().map(|tooltip| SystrayTooltip::new(
+                icons,
                 tooltip.clone(),
-                theme.clone()
+                theme
             )),
             menu: menu.clone().map(|menu| {
-                SystrayMenu::new(menu, item.menu.clone())
+                SystrayMenu::new(icons, theme, menu, item.menu.clone())
+            }),
+            theme_path
+        }
+    }
+
+    /// Where the item keeps icons that aren't in the user's theme.
+    pub fn theme_path(&self) -> Option<&Path> {
+        self.theme_path.as_deref()
+    }
+
+    /// The URLs of every pixmap the item shows.
+    pub fn pixmap_urls(&self) -> impl Iterator<Item = &str> {
+        [Some(&self.icon), self.overlay_icon.as_ref(), self.attention_icon.as_ref(), self.tooltip.as_ref().map(|tooltip| &tooltip.icon)]
+            .into_iter()
+            .flatten()
+            .filter_map(|icon| match icon {
+                SystrayIcon::Pixmap { url } => Some(url.as_str()),
+                SystrayIcon::FreedesktopIcon { .. } => None,
             })
+    }
+
+    /// Applies an update from the tray client and returns the part of the item that changed.
+    pub fn apply_update(&mut self, icons: &mut IconResolver, event: UpdateEvent) -> SystrayItemPatch {
+        let theme = self.theme_path.as_deref();
+        match event {
+            UpdateEvent::Tooltip(tooltip) => {
+                self.tooltip = tooltip.map(|tooltip| {
+                    SystrayTooltip::new(icons, tooltip, theme)
+                });
+                SystrayItemPatch::Tooltip(self.tooltip.clone())
+            }
+            UpdateEvent::Title(title) => {
+                self.title = title;
+                SystrayItemPatch::Title(self.title.clone())
+            }
+            UpdateEvent::Status(status) => {
+                self.status = status.into();
+                SystrayItemPatch::Status(self.status.clone())
+            }
+            UpdateEvent::Icon { icon_name, icon_pixmap } => {
+                self.icon = SystrayIcon::from_data_default(icons, theme, icon_name, icon_pixmap);
+                SystrayItemPatch::Icon(self.icon.clone())
+            }
+            UpdateEvent::OverlayIcon(name) => {
+                self.overlay_icon = SystrayIcon::from_data(
+                    icons,
+                    theme,
+                    name,
+                    None
+                );
+                SystrayItemPatch::OverlayIcon(self.overlay_icon.clone())
+            }
+            UpdateEvent::AttentionIcon(name) => {
+                self.attention_icon = SystrayIcon::from_data(
+                    icons,
+                    theme,
+                    name,
+                    None
+                );
+                SystrayItemPatch::AttentionIcon(self.attention_icon.clone())
+            }
+            UpdateEvent::Menu(full_menu) => {
+                match self.menu {
+                    Some(ref mut menu) => {
+                        menu.update(icons, theme, full_menu);
+                    }
+                    None => {
+                        self.menu = Some(SystrayMenu::new(icons, theme, full_menu, None));
+                    }
+                }
+                SystrayItemPatch::Menu(self.menu.clone())
+            }
+            UpdateEvent::MenuConnect(dbus_name) => {
+                match self.menu {
+                    Some(ref mut menu) => {
+                        menu.dbus_path = Some(dbus_name);
+                    }
+                    None => {
+                        self.menu = Some(SystrayMenu::partial(dbus_name));
+                    }
+                }
+                SystrayItemPatch::Menu(self.menu.clone())
+            }
+            UpdateEvent::MenuDiff(diffs) => {
+                for diff in diffs {
+                    if let Some(menu) = &mut self.menu {
+                        menu.apply_diff(icons, theme, diff);
+                    } else {
+                        println!("Menu diff event received for tray item without menu: {:?}", diff);
+                    }
+                }
+                SystrayItemPatch::Menu(self.menu.clone())
+            }
+        }
+    }
+}
+
+/// The part of a tray item that changed. Only that part is sent, so an animated icon
+/// doesn't re-send every other item with it.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub enum SystrayItemPatch {
+    Title(Option<String>),
+    Status(SystrayItemStatus),
+    Icon(SystrayIcon),
+    OverlayIcon(Option<SystrayIcon>),
+    AttentionIcon(Option<SystrayIcon>),
+    Tooltip(Option<SystrayTooltip>),
+    Menu(Option<SystrayMenu>),
+}
+
+impl SystrayItemPatch {
+    /// The same part of another copy of the item, like the one with the rules applied.
+    pub fn reread(&self, item: &SystemTrayItem) -> Self {
+        match self {
+            SystrayItemPatch::Title(_) => SystrayItemPatch::Title(item.title.clone()),
+            SystrayItemPatch::Status(_) => SystrayItemPatch::Status(item.status.clone()),
+            SystrayItemPatch::Icon(_) => SystrayItemPatch::Icon(item.icon.clone()),
+            SystrayItemPatch::OverlayIcon(_) => SystrayItemPatch::OverlayIcon(item.overlay_icon.clone()),
+            SystrayItemPatch::AttentionIcon(_) => SystrayItemPatch::AttentionIcon(item.attention_icon.clone()),
+            SystrayItemPatch::Tooltip(_) => SystrayItemPatch::Tooltip(item.tooltip.clone()),
+            SystrayItemPatch::Menu(_) => SystrayItemPatch::Menu(item.menu.clone()),
         }
     }
 }
 
+/// A change to the tray items, keyed by the item's address.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub enum SystrayItemsChange {
+    Added { address: String, item: SystemTrayItem },
+    Removed { address: String },
+    Updated { address: String, patch: SystrayItemPatch },
+}
+
+/// The order to show items in, by address. Hidden items aren't in either list.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SystrayLayout {
+    /// Items shown on the bar.
+    pub shown: Vec<String>,
+    /// Passive items tucked away until the overflow is opened.
+    pub overflow: Vec<String>,
+}
+
+/// Emitted as "tray_items_changed".
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SystrayItemsEvent {
+    /// One more than the version before this change. If the frontend sees a gap, it missed a change
+    /// and should resync.
+    pub version: u32,
+    pub change: SystrayItemsChange,
+    /// The new layout, if the change moved any items.
+    pub layout: Option<SystrayLayout>,
+}
+
+/// All shown tray items as of a version. Also emitted as "tray_items_resynced" when the backend
+/// rebuilds the items on its own.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SystrayItemsSnapshot {
+    pub version: u32,
+    /// Every item that isn't hidden, with the user's rules applied.
+    pub items: SystemTrayItems,
+    pub layout: SystrayLayout,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
 pub enum SystrayActivateRequest {
@@ -515,6 +685,32 @@ pub enum SystrayActivateRequest {
     Secondary { address: String, x: i32, y: i32 },
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub enum SystrayScrollOrientation {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SystrayScrollRequest {
+    pub address: String,
+    /// The number of steps scrolled. Positive values scroll down or right.
+    pub delta: i32,
+    pub orientation: SystrayScrollOrientation,
+}
+
+/// Identifies a menu, or a submenu within it, that the frontend opened or closed.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/SystemTray.ts")]
+pub struct SystrayMenuRequest {
+    pub address: String,
+    pub menu_path: String,
+    /// The menu item whose submenu is shown, or 0 for the root menu.
+    pub submenu_id: i32,
+}
+
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to="../../bindings/SystemTray.ts")]
@@ -0,0 +1,297 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime, State};
+use ts_rs::TS;
+
+use crate::config::{ChargeThresholdPreset, ChargeThresholdsConfig};
+use crate::BarHandler;
+
+const START_THRESHOLD: &str = "charge_control_start_threshold";
+const END_THRESHOLD: &str = "charge_control_end_threshold";
+
+/// The charge limits of a single battery, in percent.
+/// A threshold is None if the hardware doesn't support it; many laptops only support an end threshold.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/ChargeThresholds.ts")]
+pub struct BatteryChargeThresholds {
+    /// The power supply name, like "BAT0".
+    pub name: String,
+    pub start: Option<u8>,
+    pub end: Option<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/ChargeThresholds.ts")]
+pub struct ChargeThresholds {
+    /// Whether any battery supports charge thresholds.
+    pub supported: bool,
+    pub batteries: Vec<BatteryChargeThresholds>,
+    pub presets: Vec<ChargeThresholdPreset>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/ChargeThresholds.ts")]
+pub struct ChargeThresholdSettings {
+    /// Ignored on batteries without a start threshold.
+    pub start: u8,
+    pub end: u8,
+}
+
+fn read_threshold(battery: &Path, file: &str) -> Option<u8> {
+    fs::read_to_string(battery.join(file)).ok()?.trim().parse().ok()
+}
+
+/// Finds every battery under the sysfs root that exposes at least one charge threshold.
+fn find_batteries(root: &Path) -> Vec<(PathBuf, BatteryChargeThresholds)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut batteries = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| fs::read_to_string(path.join("type")).is_ok_and(|kind| kind.trim() == "Battery"))
+        .filter_map(|path| {
+            let thresholds = BatteryChargeThresholds {
+                name: path.file_name()?.to_string_lossy().into_owned(),
+                start: read_threshold(&path, START_THRESHOLD),
+                end: read_threshold(&path, END_THRESHOLD),
+            };
+            (thresholds.start.is_some() || thresholds.end.is_some()).then_some((path, thresholds))
+        })
+        .collect::<Vec<_>>();
+    batteries.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    batteries
+}
+
+fn read_charge_thresholds(config: &ChargeThresholdsConfig) -> ChargeThresholds {
+    let batteries = find_batteries(&config.sysfs_root)
+        .into_iter()
+        .map(|(_, thresholds)| thresholds)
+        .collect::<Vec<_>>();
+
+    ChargeThresholds {
+        supported: !batteries.is_empty(),
+        batteries,
+        presets: config.presets.clone(),
+    }
+}
+
+/// Writes each value to its file, going through the privilege helper if we aren't allowed to write directly.
+fn write_files(config: &ChargeThresholdsConfig, writes: &[(PathBuf, u8)]) -> Result<(), String> {
+    // A udev rule may have made the files writable for us
+    let direct = writes.iter().try_for_each(|(path, value)| fs::write(path, value.to_string()));
+    match direct {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+        Err(e) => return Err(format!("Failed to write charge threshold: {}", e)),
+    }
+
+    let Some((helper, helper_args)) = config.privilege_helper.split_first() else {
+        return Err("Not allowed to write charge thresholds and no privilege helper is configured".to_string());
+    };
+
+    // Paths and values are passed as arguments rather than interpolated into the script
+    let mut command = Command::new(helper);
+    command
+        .args(helper_args)
+        .args(["sh", "-c", r#"while [ $# -gt 0 ]; do printf %s "$2" > "$1" || exit 1; shift 2; done"#, "sh"]);
+    for (path, value) in writes {
+        command.arg(path).arg(value.to_string());
+    }
+
+    let status = command.status().map_err(|e| format!("Failed to run {}: {}", helper, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed to write charge thresholds: {}", helper, status))
+    }
+}
+
+/// Validates the settings and works out which files to write them to, in order.
+fn plan_writes(root: &Path, settings: ChargeThresholdSettings) -> Result<Vec<(PathBuf, u8)>, String> {
+    if settings.end > 100 || settings.start >= settings.end {
+        return Err(format!("Invalid charge thresholds {}-{}%", settings.start, settings.end));
+    }
+
+    let batteries = find_batteries(root);
+    if batteries.is_empty() {
+        return Err("No battery supports charge thresholds".to_string());
+    }
+
+    let mut writes = Vec::new();
+    for (path, current) in batteries {
+        let start = current.start.is_some().then(|| (path.join(START_THRESHOLD), settings.start));
+        let end = current.end.is_some().then(|| (path.join(END_THRESHOLD), settings.end));
+
+        // The kernel rejects a start threshold above the end threshold, so order the writes
+        // so the pair stays valid in between.
+        if current.end.is_some_and(|end| settings.start >= end) {
+            writes.extend(end.into_iter().chain(start));
+        } else {
+            writes.extend(start.into_iter().chain(end));
+        }
+    }
+    Ok(writes)
+}
+
+fn write_thresholds(config: &ChargeThresholdsConfig, settings: ChargeThresholdSettings) -> Result<(), String> {
+    let writes = plan_writes(&config.sysfs_root, settings)?;
+    write_files(config, &writes)
+}
+
+fn charge_thresholds_config(handler: &State<'_, Mutex<BarHandler>>) -> ChargeThresholdsConfig {
+    handler.lock().unwrap().config.charge_thresholds.clone()
+}
+
+/// Applies the settings off the async runtime, since the privilege helper may wait for the user to authenticate.
+async fn apply<R: Runtime>(
+    app_handle: AppHandle<R>,
+    config: ChargeThresholdsConfig,
+    settings: ChargeThresholdSettings,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        write_thresholds(&config, settings)?;
+        let _ = app_handle.emit("charge_thresholds_changed", read_charge_thresholds(&config));
+        Ok(())
+    }).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub(crate) async fn get_charge_thresholds(handler: State<'_, Mutex<BarHandler>>) -> Result<ChargeThresholds, ()> {
+    Ok(read_charge_thresholds(&charge_thresholds_config(&handler)))
+}
+
+#[tauri::command]
+pub(crate) async fn set_charge_thresholds<R: Runtime>(
+    app_handle: AppHandle<R>,
+    payload: ChargeThresholdSettings,
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<(), String> {
+    let config = charge_thresholds_config(&handler);
+    apply(app_handle, config, payload).await
+}
+
+#[tauri::command]
+pub(crate) async fn apply_charge_threshold_preset<R: Runtime>(
+    app_handle: AppHandle<R>,
+    payload: String,
+    handler: State<'_, Mutex<BarHandler>>,
+) -> Result<(), String> {
+    let config = charge_thresholds_config(&handler);
+    let preset = config.presets.iter()
+        .find(|preset| preset.name == payload)
+        .ok_or_else(|| format!("No charge threshold preset named {}", payload))?;
+    let settings = ChargeThresholdSettings { start: preset.start, end: preset.end };
+    apply(app_handle, config, settings).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Adds a power supply to a fake sysfs tree, with only the thresholds it supports.
+    fn power_supply(root: &Path, name: &str, kind: &str, start: Option<u8>, end: Option<u8>) -> PathBuf {
+        let path = root.join(name);
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("type"), format!("{}\n", kind)).unwrap();
+        if let Some(start) = start {
+            fs::write(path.join(START_THRESHOLD), format!("{}\n", start)).unwrap();
+        }
+        if let Some(end) = end {
+            fs::write(path.join(END_THRESHOLD), format!("{}\n", end)).unwrap();
+        }
+        path
+    }
+
+    fn config(root: &TempDir) -> ChargeThresholdsConfig {
+        ChargeThresholdsConfig {
+            sysfs_root: root.path().to_path_buf(),
+            privilege_helper: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    fn settings(start: u8, end: u8) -> ChargeThresholdSettings {
+        ChargeThresholdSettings { start, end }
+    }
+
+    #[test]
+    fn finds_batteries_with_thresholds() {
+        let root = TempDir::new().unwrap();
+        power_supply(root.path(), "BAT1", "Battery", None, Some(80));
+        power_supply(root.path(), "BAT0", "Battery", Some(75), Some(90));
+        power_supply(root.path(), "AC", "Mains", Some(75), Some(90));
+        // Peripheral batteries usually don't have thresholds
+        power_supply(root.path(), "hidpp_battery_0", "Battery", None, None);
+
+        let thresholds = read_charge_thresholds(&config(&root));
+        assert!(thresholds.supported);
+        let batteries = thresholds.batteries.iter()
+            .map(|battery| (battery.name.as_str(), battery.start, battery.end))
+            .collect::<Vec<_>>();
+        assert_eq!(batteries, vec![("BAT0", Some(75), Some(90)), ("BAT1", None, Some(80))]);
+    }
+
+    #[test]
+    fn unsupported_hardware() {
+        let root = TempDir::new().unwrap();
+        power_supply(root.path(), "AC", "Mains", None, None);
+        power_supply(root.path(), "BAT0", "Battery", None, None);
+
+        let config = config(&root);
+        assert!(!read_charge_thresholds(&config).supported);
+        assert_eq!(write_thresholds(&config, settings(60, 80)), Err("No battery supports charge thresholds".to_string()));
+
+        let missing = ChargeThresholdsConfig { sysfs_root: root.path().join("missing"), ..config };
+        assert!(read_charge_thresholds(&missing).batteries.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_thresholds() {
+        let root = TempDir::new().unwrap();
+        let battery = power_supply(root.path(), "BAT0", "Battery", Some(75), Some(90));
+
+        for (start, end) in [(80, 80), (90, 60), (50, 101)] {
+            assert_eq!(
+                plan_writes(root.path(), settings(start, end)),
+                Err(format!("Invalid charge thresholds {}-{}%", start, end)),
+            );
+        }
+        assert_eq!(read_threshold(&battery, START_THRESHOLD), Some(75));
+        assert_eq!(read_threshold(&battery, END_THRESHOLD), Some(90));
+    }
+
+    #[test]
+    fn orders_writes_to_stay_valid() {
+        let root = TempDir::new().unwrap();
+        let battery = power_supply(root.path(), "BAT0", "Battery", Some(75), Some(90));
+        let start = battery.join(START_THRESHOLD);
+        let end = battery.join(END_THRESHOLD);
+
+        // Moving down, the start goes first so it's never above the old end
+        assert_eq!(plan_writes(root.path(), settings(40, 60)), Ok(vec![(start.clone(), 40), (end.clone(), 60)]));
+        // Moving the start past the old end, the end goes first
+        assert_eq!(plan_writes(root.path(), settings(95, 100)), Ok(vec![(end.clone(), 100), (start.clone(), 95)]));
+
+        write_thresholds(&config(&root), settings(95, 100)).unwrap();
+        assert_eq!(read_threshold(&battery, START_THRESHOLD), Some(95));
+        assert_eq!(read_threshold(&battery, END_THRESHOLD), Some(100));
+    }
+
+    #[test]
+    fn skips_missing_start_threshold() {
+        let root = TempDir::new().unwrap();
+        let battery = power_supply(root.path(), "BAT0", "Battery", None, Some(80));
+
+        assert_eq!(plan_writes(root.path(), settings(60, 90)), Ok(vec![(battery.join(END_THRESHOLD), 90)]));
+    }
+}
@@ -1,9 +1,9 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use zbus::{Result, proxy};
 use zvariant::OwnedValue;
 
-#[derive(Debug, Copy, Clone, Serialize, OwnedValue, TS)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, OwnedValue, TS)]
 #[repr(u32)]
 #[ts(export_to="../../bindings/UpowerProperties.ts")]
 pub enum BatteryState {
@@ -44,6 +44,26 @@ pub enum BatteryType {
     Keyboard = 6,
     Pda = 7,
     Phone = 8,
+    MediaPlayer = 9,
+    Tablet = 10,
+    Computer = 11,
+    GamingInput = 12,
+    Pen = 13,
+    Touchpad = 14,
+    Modem = 15,
+    Network = 16,
+    Headset = 17,
+    Speakers = 18,
+    Headphones = 19,
+    Video = 20,
+    OtherAudio = 21,
+    RemoteControl = 22,
+    Printer = 23,
+    Scanner = 24,
+    Camera = 25,
+    Wearable = 26,
+    Toy = 27,
+    BluetoothGeneric = 28,
 }
 
 impl From<u32> for BatteryType {
@@ -58,13 +78,34 @@ impl From<u32> for BatteryType {
             6 => BatteryType::Keyboard,
             7 => BatteryType::Pda,
             8 => BatteryType::Phone,
+            9 => BatteryType::MediaPlayer,
+            10 => BatteryType::Tablet,
+            11 => BatteryType::Computer,
+            12 => BatteryType::GamingInput,
+            13 => BatteryType::Pen,
+            14 => BatteryType::Touchpad,
+            15 => BatteryType::Modem,
+            16 => BatteryType::Network,
+            17 => BatteryType::Headset,
+            18 => BatteryType::Speakers,
+            19 => BatteryType::Headphones,
+            20 => BatteryType::Video,
+            21 => BatteryType::OtherAudio,
+            22 => BatteryType::RemoteControl,
+            23 => BatteryType::Printer,
+            24 => BatteryType::Scanner,
+            25 => BatteryType::Camera,
+            26 => BatteryType::Wearable,
+            27 => BatteryType::Toy,
+            28 => BatteryType::BluetoothGeneric,
             _ => BatteryType::Unknown, // Default case
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, OwnedValue)]
+#[derive(Debug, Copy, Clone, Serialize, OwnedValue, TS)]
 #[repr(u32)]
+#[ts(export_to="../../bindings/UpowerProperties.ts")]
 pub enum BatteryLevel {
     Unknown = 0,
     None = 1,
@@ -75,6 +116,49 @@ pub enum BatteryLevel {
     Full = 8,
 }
 
+impl From<u32> for BatteryLevel {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => BatteryLevel::None,
+            3 => BatteryLevel::Low,
+            4 => BatteryLevel::Critical,
+            6 => BatteryLevel::Normal,
+            7 => BatteryLevel::High,
+            8 => BatteryLevel::Full,
+            _ => BatteryLevel::Unknown, // Default case
+        }
+    }
+}
+
+/// How urgently the user should be warned about the remaining charge, as decided by UPower.
+/// Variants are ordered by severity, so they can be compared.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, OwnedValue, TS)]
+#[repr(u32)]
+#[ts(export_to="../../bindings/UpowerProperties.ts")]
+pub enum WarningLevel {
+    Unknown = 0,
+    None = 1,
+    /// Only used for UPSes.
+    Discharging = 2,
+    Low = 3,
+    Critical = 4,
+    /// UPower is about to perform its critical action.
+    Action = 5,
+}
+
+impl From<u32> for WarningLevel {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => WarningLevel::None,
+            2 => WarningLevel::Discharging,
+            3 => WarningLevel::Low,
+            4 => WarningLevel::Critical,
+            5 => WarningLevel::Action,
+            _ => WarningLevel::Unknown, // Default case
+        }
+    }
+}
+
 #[proxy(
     interface = "org.freedesktop.UPower.Device",
     default_service = "org.freedesktop.UPower",
@@ -99,6 +183,10 @@ pub trait Device {
     #[zbus(property)]
     fn energy_full_design(&self) -> Result<f64>;
 
+    /// Gets history for the device over the last `timespan` seconds.
+    /// `type_` is either "rate" or "charge". Returns (time, value, state) tuples.
+    fn get_history(&self, type_: &str, timespan: u32, resolution: u32) -> Result<Vec<(u32, f64, u32)>>;
+
     #[zbus(property)]
     fn has_history(&self) -> Result<bool>;
 
@@ -151,6 +239,9 @@ pub trait Device {
 
     #[zbus(property)]
     fn voltage(&self) -> Result<f64>;
+
+    #[zbus(property)]
+    fn warning_level(&self) -> Result<WarningLevel>;
 }
 
 #[proxy(interface = "org.freedesktop.UPower", assume_defaults = true)]
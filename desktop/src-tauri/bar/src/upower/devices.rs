@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use futures::{future::Either, stream, StreamExt};
+use serde::Serialize;
+use tauri::async_runtime::{JoinHandle, Mutex};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::export::ordered_stream::OrderedStreamExt;
+use zbus::fdo::PropertiesProxy;
+use zbus::names::InterfaceName;
+use zbus::proxy::CacheProperties;
+use zvariant::{OwnedObjectPath, OwnedValue};
+
+use crate::upower::dbus::{BatteryLevel, BatteryState, BatteryType, UPowerProxy};
+use crate::upower::follow_upower;
+use crate::BarHandler;
+
+/// A single power source or peripheral known to UPower, like a laptop battery, wireless mouse, or UPS.
+/// See https://upower.freedesktop.org/docs/Device.html.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/UpowerProperties.ts")]
+pub struct UpowerDevice {
+    /// The D-Bus object path of the device. Used to identify it in added/removed events.
+    pub path: String,
+    pub device_type: BatteryType,
+    pub model: String,
+    pub vendor: String,
+    /// The OS-specific native path, like "BAT0" or "hidpp_battery_0".
+    pub native_path: String,
+    pub state: BatteryState,
+    /// The amount of energy left, between 0 and 100.
+    pub percentage: f64,
+    /// Coarse battery level for devices that can't report a percentage.
+    /// If this is anything other than `None`, `percentage` is only an approximation.
+    pub battery_level: BatteryLevel,
+    pub is_present: bool,
+    pub is_rechargeable: bool,
+    /// Whether this device powers the system. False for peripherals.
+    pub power_supply: bool,
+    pub icon_name: String,
+}
+
+impl UpowerDevice {
+    fn from_properties(path: &OwnedObjectPath, properties: &HashMap<String, OwnedValue>) -> Self {
+        fn get<T>(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<T>
+        where
+            T: TryFrom<OwnedValue>,
+        {
+            properties.get(name).and_then(|value| value.try_clone().ok()).and_then(|value| T::try_from(value).ok())
+        }
+
+        UpowerDevice {
+            path: path.to_string(),
+            device_type: get::<u32>(properties, "Type").unwrap_or(0).into(),
+            model: get(properties, "Model").unwrap_or_default(),
+            vendor: get(properties, "Vendor").unwrap_or_default(),
+            native_path: get(properties, "NativePath").unwrap_or_default(),
+            state: get::<u32>(properties, "State").unwrap_or(0).into(),
+            percentage: get(properties, "Percentage").unwrap_or(0.0),
+            battery_level: get::<u32>(properties, "BatteryLevel").unwrap_or(0).into(),
+            is_present: get(properties, "IsPresent").unwrap_or(false),
+            is_rechargeable: get(properties, "IsRechargeable").unwrap_or(false),
+            power_supply: get(properties, "PowerSupply").unwrap_or(false),
+            icon_name: get(properties, "IconName").unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UpowerDevices {
+    devices: HashMap<String, UpowerDevice>,
+}
+
+async fn read_device(
+    connection: &zbus::Connection,
+    path: &OwnedObjectPath,
+) -> zbus::Result<(PropertiesProxy<'static>, UpowerDevice)> {
+    let proxy = PropertiesProxy::builder(connection)
+        .destination("org.freedesktop.UPower")?
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let interface = InterfaceName::from_static_str("org.freedesktop.UPower.Device")?;
+    let properties = proxy.get_all(interface).await?;
+    Ok((proxy, UpowerDevice::from_properties(path, &properties)))
+}
+
+/// Watches a device for property changes until it's removed, re-reading it and emitting the new state on every change.
+async fn watch_device<R: Runtime>(
+    app_handle: AppHandle<R>,
+    connection: zbus::Connection,
+    path: OwnedObjectPath,
+) -> zbus::Result<()> {
+    let (proxy, device) = read_device(&connection, &path).await?;
+    let mut changes = proxy.receive_properties_changed().await?.into_stream();
+
+    {
+        let devices = app_handle.state::<Mutex<UpowerDevices>>();
+        devices.lock().await.devices.insert(device.path.clone(), device.clone());
+    }
+    let _ = app_handle.emit("upower_device_added", device);
+
+    let interface = InterfaceName::from_static_str("org.freedesktop.UPower.Device")?;
+    while let Some(change) = changes.next().await {
+        let Ok(args) = change.args() else {
+            continue;
+        };
+        if args.interface_name != interface {
+            continue;
+        }
+
+        let properties = proxy.get_all(interface.clone()).await?;
+        let device = UpowerDevice::from_properties(&path, &properties);
+        {
+            let devices = app_handle.state::<Mutex<UpowerDevices>>();
+            devices.lock().await.devices.insert(device.path.clone(), device.clone());
+        }
+        let _ = app_handle.emit("upower_device_changed", device);
+    }
+    Ok(())
+}
+
+fn spawn_watcher<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    connection: &zbus::Connection,
+    path: OwnedObjectPath,
+) -> JoinHandle<()> {
+    let app_handle = app_handle.clone();
+    let connection = connection.clone();
+    tauri::async_runtime::spawn(async move {
+        let display_path = path.to_string();
+        if let Err(e) = watch_device(app_handle, connection, path).await {
+            eprintln!("Failed to watch UPower device {}: {}", display_path, e);
+        }
+    })
+}
+
+impl BarHandler {
+    /// Tracks every device UPower knows about, including peripherals, and emits hotplug events.
+    pub fn start_upower_device_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(UpowerDevices::default()));
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = Self::run_upower_device_events(app_handle).await {
+                eprintln!("Failed to track UPower devices: {}", e);
+            }
+        });
+    }
+
+    async fn run_upower_device_events<R: Runtime>(app_handle: AppHandle<R>) -> zbus::Result<()> {
+        let connection = zbus::Connection::system().await?;
+        follow_upower(
+            &connection,
+            || track_devices(app_handle.clone(), connection.clone()),
+            || {
+                let app_handle = app_handle.clone();
+                async move {
+                    // Every device disappears with UPower; they're re-added once it comes back
+                    let devices = app_handle.state::<Mutex<UpowerDevices>>();
+                    for (path, _) in devices.lock().await.devices.drain() {
+                        let _ = app_handle.emit("upower_device_removed", path);
+                    }
+                }
+            },
+        ).await
+    }
+}
+
+/// Aborts the device watchers when UPower goes away and we stop tracking devices.
+#[derive(Default)]
+struct Watchers(HashMap<String, JoinHandle<()>>);
+
+impl Drop for Watchers {
+    fn drop(&mut self) {
+        for watcher in self.0.values() {
+            watcher.abort();
+        }
+    }
+}
+
+async fn track_devices<R: Runtime>(app_handle: AppHandle<R>, connection: zbus::Connection) -> zbus::Result<()> {
+    let upower = UPowerProxy::new(&connection).await?;
+
+    // Subscribe before enumerating so we don't miss devices added in between
+    let added = upower.receive_device_added().await?;
+    let removed = upower.receive_device_removed().await?;
+    let mut events = stream::select(
+        added.into_stream().map(Either::Left),
+        removed.into_stream().map(Either::Right),
+    );
+
+    let mut watchers = Watchers::default();
+    for path in upower.enumerate_devices().await? {
+        watchers.0.insert(path.to_string(), spawn_watcher(&app_handle, &connection, path));
+    }
+
+    while let Some(event) = events.next().await {
+        match event {
+            Either::Left(added) => {
+                let Ok(args) = added.args() else { continue };
+                let path = OwnedObjectPath::from(args.device.clone());
+                if let Some(watcher) = watchers.0.remove(path.as_str()) {
+                    watcher.abort();
+                }
+                watchers.0.insert(path.to_string(), spawn_watcher(&app_handle, &connection, path));
+            }
+            Either::Right(removed) => {
+                let Ok(args) = removed.args() else { continue };
+                let path = args.device.to_string();
+                if let Some(watcher) = watchers.0.remove(&path) {
+                    watcher.abort();
+                }
+
+                let devices = app_handle.state::<Mutex<UpowerDevices>>();
+                devices.lock().await.devices.remove(&path);
+                let _ = app_handle.emit("upower_device_removed", path);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_upower_devices(devices: State<'_, Mutex<UpowerDevices>>) -> Result<Vec<UpowerDevice>, ()> {
+    let devices = devices.lock().await;
+    let mut devices = devices.devices.values().cloned().collect::<Vec<_>>();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(devices)
+}
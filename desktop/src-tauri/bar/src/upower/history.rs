@@ -0,0 +1,385 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Mutex;
+use tauri::State;
+use ts_rs::TS;
+
+use crate::config::BatteryConfig;
+use crate::upower::dbus::{BatteryState, BatteryType, DeviceProxy, UPowerProxy};
+use crate::upower::UpowerProperties;
+
+/// How often we write our own samples to disk, in seconds.
+const PERSIST_INTERVAL: u64 = 5 * 60;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export, export_to="../../bindings/BatteryHistory.ts")]
+pub struct BatterySample {
+    /// Unix timestamp of the sample, in seconds.
+    pub time: u64,
+    pub percentage: f64,
+    /// Charging/discharging rate in Watts. 0 if unknown.
+    pub energy_rate: f64,
+    pub state: BatteryState,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/BatteryHistory.ts")]
+pub enum BatteryEstimate {
+    /// Seconds until the battery is empty, smoothed over recent samples.
+    TimeToEmpty(u64),
+    /// Seconds until the battery is full, smoothed over recent samples.
+    TimeToFull(u64),
+    /// Not charging or discharging, or there isn't enough history yet.
+    Unknown,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/BatteryHistory.ts")]
+pub struct BatteryHistory {
+    /// Samples in chronological order, covering at most the configured history length.
+    pub samples: Vec<BatterySample>,
+    pub estimate: BatteryEstimate,
+}
+
+enum HistorySource {
+    /// UPower keeps its own history for this device, so we only fill it in with live samples.
+    UPower,
+    /// UPower doesn't keep history, so we persist our own samples.
+    Own { last_persist: u64 },
+}
+
+pub struct BatteryHistoryState {
+    config: BatteryConfig,
+    source: HistorySource,
+    samples: VecDeque<BatterySample>,
+    estimate: BatteryEstimate,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("desktop").join("battery_history.json"))
+}
+
+/// Finds the first system battery that UPower keeps history for.
+async fn find_history_device(connection: &zbus::Connection) -> zbus::Result<Option<DeviceProxy<'static>>> {
+    let upower = UPowerProxy::new(connection).await?;
+    for path in upower.enumerate_devices().await? {
+        let device = DeviceProxy::builder(connection).path(path)?.build().await?;
+        let is_battery = matches!(device.type_().await?, BatteryType::Battery) && device.power_supply().await?;
+        if is_battery && device.has_history().await? {
+            return Ok(Some(device));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads UPower's charge and rate history and merges them into samples.
+async fn read_upower_history(device: &DeviceProxy<'_>, timespan: u32) -> zbus::Result<Vec<BatterySample>> {
+    let charge = device.get_history("charge", timespan, 0).await?;
+    let rate = device.get_history("rate", timespan, 0).await?;
+
+    let mut samples = charge.into_iter().map(|(time, percentage, state)| {
+        // Rate samples aren't recorded at the same times as charge samples, so use the closest one.
+        let energy_rate = rate.iter()
+            .min_by_key(|(rate_time, _, _)| rate_time.abs_diff(time))
+            .map_or(0.0, |(_, rate, _)| *rate);
+        BatterySample {
+            time: time as u64,
+            percentage,
+            energy_rate,
+            state: state.into(),
+        }
+    }).collect::<Vec<_>>();
+    samples.sort_by_key(|sample| sample.time);
+    Ok(samples)
+}
+
+impl BatteryHistoryState {
+    pub async fn load(config: BatteryConfig, connection: &zbus::Connection) -> Self {
+        let timespan = config.history_hours * 60 * 60;
+
+        let upower_history = match find_history_device(connection).await {
+            Ok(Some(device)) => match read_upower_history(&device, timespan as u32).await {
+                Ok(samples) => Some(samples),
+                Err(e) => {
+                    eprintln!("Failed to read UPower battery history: {}", e);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("Failed to find UPower battery device: {}", e);
+                None
+            }
+        };
+
+        let (source, samples) = match upower_history {
+            Some(samples) => (HistorySource::UPower, samples),
+            None => {
+                let samples = history_path()
+                    .and_then(|path| fs::read_to_string(path).ok())
+                    .and_then(|contents| serde_json::from_str::<Vec<BatterySample>>(&contents).ok())
+                    .unwrap_or_default();
+                (HistorySource::Own { last_persist: now() }, samples)
+            }
+        };
+
+        let mut state = Self {
+            config,
+            source,
+            samples: samples.into(),
+            estimate: BatteryEstimate::Unknown,
+        };
+        state.trim(now());
+        state
+    }
+
+    fn trim(&mut self, now: u64) {
+        let oldest = now.saturating_sub(self.config.history_hours * 60 * 60);
+        while self.samples.front().is_some_and(|sample| sample.time < oldest) {
+            self.samples.pop_front();
+        }
+    }
+
+    fn persist(&self) {
+        let Some(path) = history_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let result = serde_json::to_string(&self.samples)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Failed to save battery history to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Records a sample from the current display device properties.
+    /// Returns the new sample and, if it changed, the new estimate.
+    pub fn record(&mut self, properties: &UpowerProperties) -> (BatterySample, Option<BatteryEstimate>) {
+        let now = now();
+        let sample = BatterySample {
+            time: now,
+            percentage: properties.percentage,
+            energy_rate: properties.energy_rate,
+            state: properties.state,
+        };
+        self.samples.push_back(sample);
+        self.trim(now);
+
+        if let HistorySource::Own { last_persist } = &mut self.source {
+            if now.saturating_sub(*last_persist) >= PERSIST_INTERVAL {
+                *last_persist = now;
+                self.persist();
+            }
+        }
+
+        let estimate = self.compute_estimate(properties, now);
+        if estimate == self.estimate {
+            (sample, None)
+        } else {
+            self.estimate = estimate.clone();
+            (sample, Some(estimate))
+        }
+    }
+
+    /// Estimates the time remaining from the average rate over the configured window.
+    /// Only samples since the battery last changed between charging and discharging are used.
+    fn compute_estimate(&self, properties: &UpowerProperties, now: u64) -> BatteryEstimate {
+        let charging = match properties.state {
+            BatteryState::Charging => true,
+            BatteryState::Discharging => false,
+            _ => return BatteryEstimate::Unknown,
+        };
+
+        let window_start = now.saturating_sub(self.config.estimate_window_minutes * 60);
+        let window = self.samples.iter()
+            .rev()
+            .take_while(|sample| sample.time >= window_start && matches!(
+                (sample.state, charging),
+                (BatteryState::Charging, true) | (BatteryState::Discharging, false)
+            ))
+            .collect::<Vec<_>>();
+
+        // Prefer the energy rate if the battery reports it
+        let rates = window.iter().map(|sample| sample.energy_rate).filter(|rate| *rate > 0.0).collect::<Vec<_>>();
+        let seconds = if !rates.is_empty() && properties.energy_full > 0.0 {
+            let rate = rates.iter().sum::<f64>() / rates.len() as f64;
+            let energy = if charging {
+                properties.energy_full - properties.energy
+            } else {
+                properties.energy
+            };
+            energy / rate * 60.0 * 60.0
+        } else {
+            // Otherwise, use the slope of the percentage over the window
+            let (Some(newest), Some(oldest)) = (window.first(), window.last()) else {
+                return BatteryEstimate::Unknown;
+            };
+            let elapsed = newest.time.saturating_sub(oldest.time) as f64;
+            let change = (newest.percentage - oldest.percentage).abs();
+            if elapsed < 60.0 || change <= 0.0 {
+                return BatteryEstimate::Unknown;
+            }
+            let remaining = if charging { 100.0 - properties.percentage } else { properties.percentage };
+            remaining / (change / elapsed)
+        };
+
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return BatteryEstimate::Unknown;
+        }
+        if charging {
+            BatteryEstimate::TimeToFull(seconds as u64)
+        } else {
+            BatteryEstimate::TimeToEmpty(seconds as u64)
+        }
+    }
+
+    pub fn history(&self) -> BatteryHistory {
+        BatteryHistory {
+            samples: self.samples.iter().copied().collect(),
+            estimate: self.estimate.clone(),
+        }
+    }
+}
+
+impl Drop for BatteryHistoryState {
+    fn drop(&mut self) {
+        if matches!(self.source, HistorySource::Own { .. }) {
+            self.persist();
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_battery_history(history: State<'_, Mutex<BatteryHistoryState>>) -> Result<BatteryHistory, ()> {
+    Ok(history.lock().await.history())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::upower::dbus::BatteryState::{Charging, Discharging};
+
+    const NOW: u64 = 100_000;
+
+    fn properties(state: BatteryState, percentage: f64, energy: f64, energy_full: f64) -> UpowerProperties {
+        let mut properties = UpowerProperties::from_properties(&HashMap::new());
+        properties.state = state;
+        properties.percentage = percentage;
+        properties.energy = energy;
+        properties.energy_full = energy_full;
+        properties
+    }
+
+    /// Builds a history from (seconds ago, percentage, energy rate, state) samples, oldest first.
+    /// It's backed by UPower so nothing is written to disk.
+    fn history(samples: &[(u64, f64, f64, BatteryState)]) -> BatteryHistoryState {
+        BatteryHistoryState {
+            config: BatteryConfig { estimate_window_minutes: 15, ..Default::default() },
+            source: HistorySource::UPower,
+            samples: samples.iter()
+                .map(|&(ago, percentage, energy_rate, state)| BatterySample { time: NOW - ago, percentage, energy_rate, state })
+                .collect(),
+            estimate: BatteryEstimate::Unknown,
+        }
+    }
+
+    #[test]
+    fn energy_rate_estimate() {
+        // Unknown rates are left out of the average
+        let battery = history(&[(600, 50.0, 8.0, Discharging), (300, 48.0, 0.0, Discharging), (0, 46.0, 24.0, Discharging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 46.0, 32.0, 70.0), NOW),
+            BatteryEstimate::TimeToEmpty(2 * 60 * 60),
+        );
+
+        let battery = history(&[(600, 50.0, 8.0, Charging), (0, 52.0, 24.0, Charging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Charging, 52.0, 24.0, 56.0), NOW),
+            BatteryEstimate::TimeToFull(2 * 60 * 60),
+        );
+    }
+
+    #[test]
+    fn percentage_slope_without_energy_rate() {
+        let battery = history(&[(512, 44.0, 0.0, Discharging), (0, 40.0, 0.0, Discharging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 40.0, 0.0, 0.0), NOW),
+            BatteryEstimate::TimeToEmpty(5120),
+        );
+
+        // A rate is no use without the full energy to work out what's left
+        let battery = history(&[(512, 56.0, 10.0, Charging), (0, 60.0, 10.0, Charging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Charging, 60.0, 0.0, 0.0), NOW),
+            BatteryEstimate::TimeToFull(5120),
+        );
+    }
+
+    #[test]
+    fn only_samples_since_state_flip() {
+        let battery = history(&[
+            (800, 40.0, 0.0, Charging),
+            (400, 60.0, 0.0, Charging),
+            (256, 50.0, 0.0, Discharging),
+            (0, 48.0, 0.0, Discharging),
+        ]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 48.0, 0.0, 0.0), NOW),
+            BatteryEstimate::TimeToEmpty(6144),
+        );
+    }
+
+    #[test]
+    fn only_samples_in_window() {
+        let battery = history(&[(3600, 90.0, 0.0, Discharging), (512, 44.0, 0.0, Discharging), (0, 40.0, 0.0, Discharging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 40.0, 0.0, 0.0), NOW),
+            BatteryEstimate::TimeToEmpty(5120),
+        );
+    }
+
+    #[test]
+    fn unknown_under_a_minute() {
+        let battery = history(&[(30, 50.0, 0.0, Discharging), (0, 49.0, 0.0, Discharging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 49.0, 0.0, 0.0), NOW),
+            BatteryEstimate::Unknown,
+        );
+    }
+
+    #[test]
+    fn unknown_without_change() {
+        let battery = history(&[(600, 50.0, 0.0, Discharging), (0, 50.0, 0.0, Discharging)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(Discharging, 50.0, 0.0, 0.0), NOW),
+            BatteryEstimate::Unknown,
+        );
+    }
+
+    #[test]
+    fn unknown_when_not_charging_or_discharging() {
+        let battery = history(&[(600, 100.0, 0.0, BatteryState::FullyCharged), (0, 100.0, 0.0, BatteryState::FullyCharged)]);
+        assert_eq!(
+            battery.compute_estimate(&properties(BatteryState::FullyCharged, 100.0, 50.0, 50.0), NOW),
+            BatteryEstimate::Unknown,
+        );
+        assert_eq!(history(&[]).compute_estimate(&properties(Discharging, 50.0, 0.0, 0.0), NOW), BatteryEstimate::Unknown);
+    }
+}
@@ -1,15 +1,31 @@
+mod charge_thresholds;
 mod dbus;
+mod devices;
+mod history;
+mod policy;
+mod power_supply;
+
+pub(crate) use charge_thresholds::{apply_charge_threshold_preset, get_charge_thresholds, set_charge_thresholds};
+pub use dbus::WarningLevel;
+pub use devices::get_upower_devices;
+pub use history::get_battery_history;
+pub use policy::get_battery_attention;
+pub use power_supply::get_power_supply_state;
+
+use std::{collections::HashMap, future::Future, pin::pin};
 
 use dbus::UPowerProxy;
+use futures::future::{select, Either};
 use futures::StreamExt;
 use serde::Serialize;
 use tauri::async_runtime::Mutex;
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
 use ts_rs::TS;
-use zbus::fdo::PropertiesProxy;
-use zbus::names::InterfaceName;
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::names::{InterfaceName, WellKnownName};
 use zbus::proxy::CacheProperties;
 use zbus::export::ordered_stream::OrderedStreamExt;
+use zvariant::OwnedValue;
 
 /// See https://upower.freedesktop.org/docs/Device.html for documentation on the interface, and
 /// see https://upower.freedesktop.org/docs/UPower.html for documentaation on the display device.
@@ -56,132 +72,229 @@ pub struct UpowerProperties {
     /// presentation relative to the amount of battery left or perceived to be left.
     /// It is recommended that front-ends use the BatteryLevel property first,
     /// if available, followed by the Percentage, to present a more realistic battery level to the user. 
-    icon_name: String
+    icon_name: String,
+    /// How urgently UPower thinks the user should be warned about the remaining charge.
+    warning_level: WarningLevel,
 }
 
-pub async fn create_upower_proxy() -> zbus::Result<PropertiesProxy<'static>> {
-    let connection = zbus::Connection::system().await?;
-    let dbus = Box::pin(connection);
+/// The state of the system's power source, as reported by UPower's display device.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export, export_to="../../bindings/UpowerProperties.ts")]
+pub enum PowerSource {
+    /// UPower isn't running, for example on desktops without it installed or while upowerd restarts.
+    Unavailable,
+    Available(UpowerProperties),
+}
+
+const UPOWER_NAME: &str = "org.freedesktop.UPower";
 
-    let device_proxy = UPowerProxy::new(&dbus).await?;
-    let display_device = device_proxy.get_display_device().await?;
+impl UpowerProperties {
+    /// Reads the display device properties, falling back to defaults for anything missing or mistyped.
+    fn from_properties(properties: &HashMap<String, OwnedValue>) -> Self {
+        fn get<T>(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<T>
+        where
+            T: TryFrom<OwnedValue>,
+        {
+            properties.get(name).and_then(|value| value.try_clone().ok()).and_then(|value| T::try_from(value).ok())
+        }
 
-    let path = display_device.inner().path();
+        UpowerProperties {
+            state: get::<u32>(properties, "State").unwrap_or(0).into(),
+            percentage: get(properties, "Percentage").unwrap_or(0.0),
+            energy: get(properties, "Energy").unwrap_or(0.0),
+            energy_full: get(properties, "EnergyFull").unwrap_or(0.0),
+            energy_rate: get(properties, "EnergyRate").unwrap_or(0.0),
+            time_to_empty: get(properties, "TimeToEmpty").unwrap_or(0),
+            time_to_full: get(properties, "TimeToFull").unwrap_or(0),
+            is_present: get(properties, "IsPresent").unwrap_or(false),
+            icon_name: get(properties, "IconName").unwrap_or_default(),
+            warning_level: get::<u32>(properties, "WarningLevel").unwrap_or(0).into(),
+        }
+    }
+}
+
+/// Runs `run` whenever UPower owns its bus name, restarting it when upowerd restarts.
+/// `lost` is called every time UPower goes away or `run` stops, so callers can reset their state.
+async fn follow_upower<Run, RunFuture, Lost, LostFuture>(
+    connection: &zbus::Connection,
+    mut run: Run,
+    mut lost: Lost,
+) -> zbus::Result<()>
+where
+    Run: FnMut() -> RunFuture,
+    RunFuture: Future<Output = zbus::Result<()>>,
+    Lost: FnMut() -> LostFuture,
+    LostFuture: Future<Output = ()>,
+{
+    let dbus = DBusProxy::new(connection).await?;
+    let name = WellKnownName::from_static_str(UPOWER_NAME)?;
+
+    // Subscribe before checking for an owner so we can't miss UPower starting in between
+    let mut owner_changes = dbus
+        .receive_name_owner_changed_with_args(&[(0, UPOWER_NAME)])
+        .await?
+        .into_stream();
+
+    loop {
+        if dbus.name_has_owner(name.clone().into()).await? {
+            let stopped = match select(pin!(run()), owner_changes.next()).await {
+                Either::Left((Ok(()), _)) => {
+                    eprintln!("UPower stopped sending updates");
+                    true
+                }
+                Either::Left((Err(e), _)) => {
+                    eprintln!("Lost connection to UPower: {}", e);
+                    true
+                }
+                Either::Right((Some(_), _)) => false,
+                Either::Right((None, _)) => return Ok(()),
+            };
+            lost().await;
 
-    let proxy = PropertiesProxy::builder(&dbus)
-        .destination("org.freedesktop.UPower")
-        .expect("failed to set proxy destination address")
-        .path(path)
-        .expect("failed to set proxy path")
+            // If UPower is still around, wait for it to restart instead of retrying in a loop
+            if stopped && owner_changes.next().await.is_none() {
+                return Ok(());
+            }
+        } else {
+            lost().await;
+            if owner_changes.next().await.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Stores new display device properties, emits them, and feeds them to the battery history and policy.
+async fn publish_properties<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    connection: &zbus::Connection,
+    properties: &UpowerProperties,
+) {
+    let power_source = app_handle.state::<Mutex<PowerSource>>();
+    *power_source.lock().await = PowerSource::Available(properties.clone());
+    let _ = app_handle.emit("upower_properties_changed", PowerSource::Available(properties.clone()));
+
+    let history = app_handle.state::<Mutex<BatteryHistoryState>>();
+    let (sample, estimate) = history.lock().await.record(properties);
+    let _ = app_handle.emit("battery_history_sample", sample);
+    if let Some(estimate) = estimate {
+        let _ = app_handle.emit("battery_estimate_changed", estimate);
+    }
+
+    let policy = app_handle.state::<Mutex<BatteryPolicy>>();
+    let update = policy.lock().await.evaluate(properties);
+    if let Some(attention) = update.attention {
+        let _ = app_handle.emit("battery_attention_changed", attention);
+    }
+    if !update.actions.is_empty() {
+        tauri::async_runtime::spawn(policy::run_actions(connection.clone(), update.actions, properties.clone()));
+    }
+
+    let discharging = matches!(properties.state, BatteryState::Discharging);
+    power_profiles::handle_battery_update(app_handle, discharging, properties.percentage).await;
+}
+
+/// Follows the display device until UPower goes away or the connection fails.
+async fn watch_display_device<R: Runtime>(app_handle: AppHandle<R>, connection: zbus::Connection) -> zbus::Result<()> {
+    let upower = UPowerProxy::new(&connection).await?;
+    let display_device = upower.get_display_device().await?;
+
+    let proxy = PropertiesProxy::builder(&connection)
+        .destination(UPOWER_NAME)?
+        .path(display_device.inner().path())?
         .cache_properties(CacheProperties::No)
         .build()
         .await?;
 
-    Ok(proxy)
+    let device_interface_name = InterfaceName::from_static_str("org.freedesktop.UPower.Device")?;
+
+    // Subscribe before reading so we don't miss changes in between
+    let mut event_stream = proxy.receive_properties_changed().await?.into_stream();
+    let properties = proxy.get_all(device_interface_name.clone()).await?;
+    let mut upower_properties = UpowerProperties::from_properties(&properties);
+    publish_properties(&app_handle, &connection, &upower_properties).await;
+
+    while let Some(event) = event_stream.next().await {
+        let Ok(args) = event.args() else {
+            continue;
+        };
+        if args.interface_name != device_interface_name {
+            continue;
+        }
+
+        for (name, changed_value) in args.changed_properties {
+            macro_rules! update_property {
+                ($field:ident, $type:ty) => {
+                    if let Some(value) = changed_value.downcast_ref::<$type>().ok() {
+                        upower_properties.$field = value.into();
+                    }
+                };
+            }
+
+            match name {
+                "State" => update_property!(state, u32),
+                "Percentage" => update_property!(percentage, f64),
+                "Energy" => update_property!(energy, f64),
+                "EnergyFull" => update_property!(energy_full, f64),
+                "EnergyRate" => update_property!(energy_rate, f64),
+                "TimeToEmpty" => update_property!(time_to_empty, i64),
+                "TimeToFull" => update_property!(time_to_full, i64),
+                "IsPresent" => update_property!(is_present, bool),
+                "IconName" => update_property!(icon_name, String),
+                "WarningLevel" => update_property!(warning_level, u32),
+                _ => {}
+            }
+        }
+
+        publish_properties(&app_handle, &connection, &upower_properties).await;
+    }
+    Ok(())
 }
 
+use crate::config::BatteryConfig;
+use crate::power_profiles;
 use crate::upower::dbus::BatteryState;
+use crate::upower::history::BatteryHistoryState;
+use crate::upower::policy::BatteryPolicy;
 use crate::BarHandler;
 
 impl BarHandler {
-    pub fn start_upower_events<R: Runtime>(&mut self, app_handle: &AppHandle<R>) {
-        let upower_proxy = match &self.upower_proxy {
-            Some(proxy) => proxy.clone(),
-            None => {
-                eprintln!("UPower proxy is not initialized.");
-                return;
-            }
-        };
+    pub fn start_upower_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(PowerSource::Unavailable));
 
+        let battery_config = self.config.battery.clone();
         let app_handle = app_handle.clone();
         tauri::async_runtime::spawn(async move {
-            let event_stream = upower_proxy.receive_properties_changed()
-                .await
-                .expect("Failed to create UPower event stream");
-
-            let device_interface_name =
-                InterfaceName::from_static_str("org.freedesktop.UPower.Device")
-                    .expect("Failed to create zbus InterfaceName");
-
-            let properties = upower_proxy.get_all(device_interface_name.clone()).await
-                .expect("Failed to get properties from UPower proxy");
-            
-            // Set initial values
-            let upower_properties = Mutex::new(UpowerProperties {
-                state: properties["State"]
-                    .downcast_ref::<u32>()
-                    .expect("expected state: BatteryState in HashMap")
-                    .into(),
-                percentage: properties["Percentage"]
-                    .downcast_ref::<f64>()
-                    .expect("expected percentage: f64 in HashMap"),
-                energy: properties["Energy"]
-                    .downcast_ref::<f64>()
-                    .expect("expected energy: f64 in HashMap"),
-                energy_full: properties["EnergyFull"]
-                    .downcast_ref::<f64>()
-                    .expect("expected energy_full: f64 in HashMap"),
-                energy_rate: properties["EnergyRate"]
-                    .downcast_ref::<f64>()
-                    .expect("expected energy_rate: f64 in HashMap"),
-                time_to_empty: properties["TimeToEmpty"]
-                    .downcast_ref::<i64>()
-                    .expect("expected time_to_empty: i64 in HashMap"),
-                time_to_full: properties["TimeToFull"]
-                    .downcast_ref::<i64>()
-                    .expect("expected time_to_full: i64 in HashMap"),
-                is_present: properties["IsPresent"]
-                    .downcast_ref::<bool>()
-                    .expect("expected is_present: bool in HashMap"),
-                icon_name: properties["IconName"]
-                    .downcast_ref::<String>()
-                    .expect("expected icon_name: String in HashMap")
-                    .clone()
-            });
-
-            app_handle.manage(upower_properties);
-
-            let mut event_stream = event_stream.into_stream();
-            while let Some(event) = event_stream.next().await {
-                let args = event.args().expect("Invalid signal arguments");
-                if args.interface_name != device_interface_name {
-                    continue;
-                }
+            if let Err(e) = Self::run_upower_events(app_handle, battery_config).await {
+                eprintln!("Failed to track UPower: {}", e);
+            }
+        });
+    }
 
-                let upower_properties = app_handle.state::<Mutex<UpowerProperties>>();
-                let mut upower_properties = upower_properties.lock().await;
+    async fn run_upower_events<R: Runtime>(app_handle: AppHandle<R>, battery_config: BatteryConfig) -> zbus::Result<()> {
+        let connection = zbus::Connection::system().await?;
 
-                for (name, changed_value) in args.changed_properties {
-                    macro_rules! update_property {
-                        ($field:ident, $type:ty) => {
-                            if let Some(value) = changed_value.downcast_ref::<$type>().ok() {
-                                upower_properties.$field = value.into();
-                            }
-                        };
-                    }
+        // Falls back to our own persisted history if UPower isn't running yet
+        let history = BatteryHistoryState::load(battery_config.clone(), &connection).await;
+        app_handle.manage(Mutex::new(history));
+        app_handle.manage(Mutex::new(BatteryPolicy::new(battery_config.thresholds)));
 
-                    match name {
-                        "State" => update_property!(state, u32),
-                        "Percentage" => update_property!(percentage, f64),
-                        "Energy" => update_property!(energy, f64),
-                        "EnergyFull" => update_property!(energy_full, f64),
-                        "EnergyRate" => update_property!(energy_rate, f64),
-                        "TimeToEmpty" => update_property!(time_to_empty, i64),
-                        "TimeToFull" => update_property!(time_to_full, i64),
-                        "IsPresent" => update_property!(is_present, bool),
-                        "IconName" => update_property!(icon_name, String),
-                        _ => {}
-                    }
+        follow_upower(
+            &connection,
+            || watch_display_device(app_handle.clone(), connection.clone()),
+            || {
+                let app_handle = app_handle.clone();
+                async move {
+                    let power_source = app_handle.state::<Mutex<PowerSource>>();
+                    *power_source.lock().await = PowerSource::Unavailable;
+                    let _ = app_handle.emit("upower_properties_changed", PowerSource::Unavailable);
                 }
-
-                app_handle.emit("upower_properties_changed", upower_properties.clone())
-                    .expect("failed to emit UPower properties changed event");
-            }
-        });
+            },
+        ).await
     }
 }
 
 #[tauri::command]
-pub async fn get_upower_properties(upower_properties: State<'_, Mutex<UpowerProperties>>) -> Result<UpowerProperties, ()> {
-    Ok(upower_properties.lock().await.clone())
-}
\ No newline at end of file
+pub async fn get_upower_properties(power_source: State<'_, Mutex<PowerSource>>) -> Result<PowerSource, ()> {
+    Ok(power_source.lock().await.clone())
+}
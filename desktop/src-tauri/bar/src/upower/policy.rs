@@ -0,0 +1,252 @@
+use std::{collections::HashMap, process::{Command, Stdio}};
+
+use tauri::async_runtime::Mutex;
+use tauri::State;
+use zbus::proxy;
+use zvariant::Value;
+
+use crate::config::{BatteryAction, BatteryThreshold};
+use crate::upower::dbus::{BatteryState, UPowerProxy, WarningLevel};
+use crate::upower::UpowerProperties;
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+    fn hybrid_sleep(&self, interactive: bool) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Tracks which low-battery thresholds have fired during the current discharge cycle.
+pub struct BatteryPolicy {
+    thresholds: Vec<BatteryThreshold>,
+    fired: Vec<bool>,
+    attention: bool,
+}
+
+/// What changed after evaluating the policy against new properties.
+pub struct PolicyUpdate {
+    /// Actions from thresholds that were reached for the first time this discharge cycle.
+    pub actions: Vec<BatteryAction>,
+    /// The new attention state, if it changed.
+    pub attention: Option<bool>,
+}
+
+impl BatteryPolicy {
+    pub fn new(thresholds: Vec<BatteryThreshold>) -> Self {
+        Self {
+            fired: vec![false; thresholds.len()],
+            thresholds,
+            attention: false,
+        }
+    }
+
+    pub fn evaluate(&mut self, properties: &UpowerProperties) -> PolicyUpdate {
+        let mut update = PolicyUpdate { actions: Vec::new(), attention: None };
+
+        match properties.state {
+            BatteryState::Discharging | BatteryState::Empty => {}
+            // Plugging in ends the discharge cycle
+            BatteryState::Charging | BatteryState::FullyCharged | BatteryState::PendingCharge => {
+                self.fired.fill(false);
+                if self.attention {
+                    self.attention = false;
+                    update.attention = Some(false);
+                }
+                return update;
+            }
+            _ => return update,
+        }
+
+        for (threshold, fired) in self.thresholds.iter().zip(self.fired.iter_mut()) {
+            let reached = threshold.percentage.is_some_and(|percentage| properties.percentage <= percentage)
+                || threshold.warning_level.is_some_and(|level| properties.warning_level >= level);
+            if !reached || *fired {
+                continue;
+            }
+
+            *fired = true;
+            update.actions.extend(threshold.actions.iter().cloned());
+        }
+
+        if !self.attention && update.actions.contains(&BatteryAction::Attention) {
+            self.attention = true;
+            update.attention = Some(true);
+        }
+        update
+    }
+}
+
+async fn notify(properties: &UpowerProperties) -> zbus::Result<()> {
+    let connection = zbus::Connection::session().await?;
+    let notifications = NotificationsProxy::new(&connection).await?;
+
+    let critical = properties.warning_level >= WarningLevel::Critical;
+    let summary = if critical { "Battery critically low" } else { "Battery low" };
+    let body = if properties.time_to_empty > 0 {
+        format!(
+            "{:.0}% remaining ({}h {:02}m)",
+            properties.percentage,
+            properties.time_to_empty / 3600,
+            properties.time_to_empty / 60 % 60
+        )
+    } else {
+        format!("{:.0}% remaining", properties.percentage)
+    };
+    let hints = HashMap::from([("urgency", Value::U8(if critical { 2 } else { 1 }))]);
+
+    notifications.notify("Battery", 0, &properties.icon_name, summary, &body, &[], hints, -1).await?;
+    Ok(())
+}
+
+/// Suspends or hibernates through logind, deferring to UPower's configured critical action.
+async fn sleep(
+    connection: &zbus::Connection,
+    action: &BatteryAction,
+    properties: &UpowerProperties,
+) -> zbus::Result<()> {
+    // UPower performs its own critical action at this level, so don't race it.
+    if properties.warning_level >= WarningLevel::Action {
+        eprintln!("Skipping battery {:?} action; UPower is performing its critical action", action);
+        return Ok(());
+    }
+
+    let critical_action = UPowerProxy::new(connection).await?.get_critical_action().await?;
+    let login = LoginManagerProxy::new(connection).await?;
+    match (action, critical_action.as_str()) {
+        // Suspending would drain the battery anyway if the user prefers hibernating at critical levels
+        (BatteryAction::Suspend, "HybridSleep") => login.hybrid_sleep(false).await,
+        (BatteryAction::Suspend, "Hibernate") | (BatteryAction::Hibernate, _) => login.hibernate(false).await,
+        _ => login.suspend(false).await,
+    }
+}
+
+/// Runs a user command through the shell without waiting for it.
+pub(super) fn run_command(command: &str) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            tauri::async_runtime::spawn_blocking(move || child.wait());
+        }
+        Err(e) => eprintln!("Failed to run command `{}`: {}", command, e),
+    }
+}
+
+/// Runs the given actions. Attention is handled by the caller since it's bar state rather than a side effect.
+pub async fn run_actions(connection: zbus::Connection, actions: Vec<BatteryAction>, properties: UpowerProperties) {
+    for action in actions {
+        let result = match &action {
+            BatteryAction::Notify => notify(&properties).await,
+            BatteryAction::Attention => Ok(()),
+            BatteryAction::Command(command) => {
+                run_command(command);
+                Ok(())
+            }
+            BatteryAction::Suspend | BatteryAction::Hibernate => sleep(&connection, &action, &properties).await,
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to run battery action {:?}: {}", action, e);
+        }
+    }
+}
+
+/// Whether the battery indicator should draw attention to itself.
+#[tauri::command]
+pub async fn get_battery_attention(policy: State<'_, Mutex<BatteryPolicy>>) -> Result<bool, ()> {
+    Ok(policy.lock().await.attention)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BatteryAction::{Attention, Notify};
+    use crate::config::BatteryConfig;
+
+    fn properties(state: BatteryState, percentage: f64, warning_level: WarningLevel) -> UpowerProperties {
+        let mut properties = UpowerProperties::from_properties(&HashMap::new());
+        properties.state = state;
+        properties.percentage = percentage;
+        properties.warning_level = warning_level;
+        properties
+    }
+
+    /// Runs the default thresholds (notify at 15% or Low, notify and draw attention at 5% or Critical)
+    /// through a sequence of updates, checking the actions and attention change after each one.
+    fn run(steps: &[(BatteryState, f64, WarningLevel, &[BatteryAction], Option<bool>)]) {
+        let mut policy = BatteryPolicy::new(BatteryConfig::default().thresholds);
+        for (i, (state, percentage, warning_level, actions, attention)) in steps.iter().enumerate() {
+            let update = policy.evaluate(&properties(*state, *percentage, *warning_level));
+            assert_eq!(update.actions, *actions, "actions after step {}", i);
+            assert_eq!(update.attention, *attention, "attention after step {}", i);
+        }
+    }
+
+    #[test]
+    fn fires_once_per_crossing() {
+        use crate::upower::dbus::BatteryState::Discharging;
+        run(&[
+            (Discharging, 50.0, WarningLevel::None, &[], None),
+            (Discharging, 15.0, WarningLevel::None, &[Notify], None),
+            (Discharging, 14.0, WarningLevel::Low, &[], None),
+            // UPower's warning level can cross a threshold before the percentage does
+            (Discharging, 10.0, WarningLevel::Critical, &[Notify, Attention], Some(true)),
+            (Discharging, 4.0, WarningLevel::Critical, &[], None),
+            // Not charging or discharging, which changes nothing
+            (BatteryState::Unknown, 4.0, WarningLevel::Critical, &[], None),
+            (Discharging, 3.0, WarningLevel::Critical, &[], None),
+        ]);
+    }
+
+    #[test]
+    fn charging_resets_and_rearms() {
+        use crate::upower::dbus::BatteryState::{Charging, Discharging, FullyCharged};
+        run(&[
+            (Discharging, 5.0, WarningLevel::Critical, &[Notify, Notify, Attention], Some(true)),
+            (Charging, 5.0, WarningLevel::Critical, &[], Some(false)),
+            (Charging, 6.0, WarningLevel::None, &[], None),
+            // Each threshold fires again in the next discharge cycle
+            (Discharging, 12.0, WarningLevel::Low, &[Notify], None),
+            (Discharging, 3.0, WarningLevel::Critical, &[Notify, Attention], Some(true)),
+            (FullyCharged, 100.0, WarningLevel::None, &[], Some(false)),
+            (BatteryState::Empty, 0.0, WarningLevel::Action, &[Notify, Notify, Attention], Some(true)),
+        ]);
+    }
+
+    #[test]
+    fn nothing_without_thresholds() {
+        let mut policy = BatteryPolicy::new(Vec::new());
+        let update = policy.evaluate(&properties(BatteryState::Discharging, 1.0, WarningLevel::Action));
+        assert!(update.actions.is_empty());
+        assert_eq!(update.attention, None);
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::Mutex as StdMutex;
+
+use futures::StreamExt;
+use serde::Serialize;
+use tauri::async_runtime::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use ts_rs::TS;
+use zbus::export::ordered_stream::OrderedStreamExt;
+use zbus::fdo::PropertiesProxy;
+use zbus::names::InterfaceName;
+use zbus::proxy::CacheProperties;
+
+use crate::config::PowerHooksConfig;
+use crate::displays::DisplaysState;
+use crate::upower::dbus::UPowerProxy;
+use crate::upower::policy::run_command;
+use crate::upower::{follow_upower, UPOWER_NAME};
+use crate::BarHandler;
+
+/// Whether we're running on battery and the state of the laptop lid, if there is one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, TS)]
+#[ts(export, export_to="../../bindings/UpowerProperties.ts")]
+pub struct PowerSupplyState {
+    /// False when plugged into AC power, or when UPower isn't running.
+    pub on_battery: bool,
+    pub lid_is_present: bool,
+    pub lid_is_closed: bool,
+}
+
+async fn read_power_supply(upower: &UPowerProxy<'_>) -> zbus::Result<PowerSupplyState> {
+    Ok(PowerSupplyState {
+        on_battery: upower.on_battery().await?,
+        lid_is_present: upower.lid_is_present().await?,
+        lid_is_closed: upower.lid_is_closed().await?,
+    })
+}
+
+fn has_external_display<R: Runtime>(app_handle: &AppHandle<R>) -> bool {
+    app_handle
+        .try_state::<StdMutex<DisplaysState>>()
+        .is_some_and(|displays| displays.lock().unwrap().has_external_display())
+}
+
+/// Runs the configured hooks for whatever changed between two states.
+fn run_hooks<R: Runtime>(app_handle: &AppHandle<R>, hooks: &PowerHooksConfig, old: PowerSupplyState, new: PowerSupplyState) {
+    let mut commands = Vec::new();
+
+    if old.on_battery != new.on_battery {
+        commands.push(if new.on_battery { &hooks.on_ac_unplugged } else { &hooks.on_ac_plugged });
+    }
+
+    if old.lid_is_closed != new.lid_is_closed {
+        if new.lid_is_closed {
+            commands.push(&hooks.on_lid_closed);
+            if !has_external_display(app_handle) {
+                commands.push(&hooks.on_lid_closed_undocked);
+            }
+        } else {
+            commands.push(&hooks.on_lid_opened);
+        }
+    }
+
+    for command in commands.into_iter().flatten() {
+        run_command(command);
+    }
+}
+
+/// Stores and emits a new state. Hooks are only run when `hooks` is set, so the initial read
+/// after startup or a UPower restart doesn't look like the adapter or lid changed.
+async fn update_power_supply<R: Runtime>(app_handle: &AppHandle<R>, hooks: Option<&PowerHooksConfig>, new: PowerSupplyState) {
+    let state = app_handle.state::<Mutex<PowerSupplyState>>();
+    let mut state = state.lock().await;
+    if *state == new {
+        return;
+    }
+
+    if let Some(hooks) = hooks {
+        run_hooks(app_handle, hooks, *state, new);
+    }
+    *state = new;
+    let _ = app_handle.emit("power_supply_changed", new);
+}
+
+/// Follows UPower's own properties until it goes away or the connection fails.
+async fn watch_power_supply<R: Runtime>(
+    app_handle: AppHandle<R>,
+    connection: zbus::Connection,
+    hooks: PowerHooksConfig,
+) -> zbus::Result<()> {
+    let upower = UPowerProxy::builder(&connection).cache_properties(CacheProperties::No).build().await?;
+    let properties = PropertiesProxy::builder(&connection)
+        .destination(UPOWER_NAME)?
+        .path(upower.inner().path())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    let interface = InterfaceName::from_static_str(UPOWER_NAME)?;
+
+    // Subscribe before reading so we don't miss changes in between
+    let mut changes = properties.receive_properties_changed().await?.into_stream();
+    update_power_supply(&app_handle, None, read_power_supply(&upower).await?).await;
+
+    while let Some(change) = changes.next().await {
+        let Ok(args) = change.args() else {
+            continue;
+        };
+        if args.interface_name != interface {
+            continue;
+        }
+        update_power_supply(&app_handle, Some(&hooks), read_power_supply(&upower).await?).await;
+    }
+    Ok(())
+}
+
+impl BarHandler {
+    /// Tracks the AC adapter and lid, running the configured hooks when they change.
+    pub fn start_power_supply_events<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        app_handle.manage(Mutex::new(PowerSupplyState::default()));
+
+        let hooks = self.config.power_hooks.clone();
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let connection = zbus::Connection::system().await?;
+                follow_upower(
+                    &connection,
+                    || watch_power_supply(app_handle.clone(), connection.clone(), hooks.clone()),
+                    || {
+                        let app_handle = app_handle.clone();
+                        async move {
+                            // Without UPower we can't tell, so report the default rather than stale state
+                            let state = app_handle.state::<Mutex<PowerSupplyState>>();
+                            *state.lock().await = PowerSupplyState::default();
+                            let _ = app_handle.emit("power_supply_changed", PowerSupplyState::default());
+                        }
+                    },
+                ).await
+            }.await;
+
+            if let Err(e) = result {
+                eprintln!("Failed to track power supply: {}", e);
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn get_power_supply_state(state: State<'_, Mutex<PowerSupplyState>>) -> Result<PowerSupplyState, ()> {
+    Ok(*state.lock().await)
+}
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BatteryState } from "./UpowerProperties";
+
+export type BatteryEstimate = { "TimeToEmpty": bigint } | { "TimeToFull": bigint } | "Unknown";
+
+export type BatteryHistory = { 
+/**
+ * Samples in chronological order, covering at most the configured history length.
+ */
+samples: Array<BatterySample>, estimate: BatteryEstimate, };
+
+export type BatterySample = { 
+/**
+ * Unix timestamp of the sample, in seconds.
+ */
+time: bigint, percentage: number, 
+/**
+ * Charging/discharging rate in Watts. 0 if unknown.
+ */
+energy_rate: number, state: BatteryState, };
@@ -0,0 +1,36 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The charge limits of a single battery, in percent.
+ * A threshold is None if the hardware doesn't support it; many laptops only support an end threshold.
+ */
+export type BatteryChargeThresholds = { 
+/**
+ * The power supply name, like "BAT0".
+ */
+name: string, start: number | null, end: number | null, };
+
+/**
+ * A named pair of charge thresholds, in percent.
+ */
+export type ChargeThresholdPreset = { name: string, 
+/**
+ * Start charging when the battery drops below this.
+ */
+start: number, 
+/**
+ * Stop charging once the battery reaches this.
+ */
+end: number, };
+
+export type ChargeThresholdSettings = { 
+/**
+ * Ignored on batteries without a start threshold.
+ */
+start: number, end: number, };
+
+export type ChargeThresholds = { 
+/**
+ * Whether any battery supports charge thresholds.
+ */
+supported: boolean, batteries: Array<BatteryChargeThresholds>, presets: Array<ChargeThresholdPreset>, };
@@ -0,0 +1,33 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CompositorEvent = { "WorkspacesChanged": { workspaces: Array<CompositorWorkspace>, } } | { "WindowsChanged": { windows: Array<CompositorWindow>, } } | { "WindowOpenedOrChanged": { window: CompositorWindow, } } | { "WindowClosed": { id: bigint, } } | { "WindowFocusChanged": { id: bigint | null, } } | { "KeyboardLayoutsChanged": { names: Array<string>, current_idx: number, } } | { "KeyboardLayoutSwitched": { idx: number, } };
+
+export type CompositorKind = "Niri" | "Sway";
+
+export type CompositorWindow = { 
+/**
+ * A unique identifier for this window. Only stable for the lifetime of the window.
+ */
+id: bigint, title: string | null, 
+/**
+ * The Wayland app ID, or the X11 class for XWayland windows.
+ */
+app_id: string | null, workspace_id: bigint | null, is_focused: boolean, is_urgent: boolean, };
+
+export type CompositorWorkspace = { 
+/**
+ * A unique identifier for this workspace. Only stable for the lifetime of the workspace.
+ */
+id: bigint, 
+/**
+ * The index or number of the workspace on its output, as shown to the user.
+ */
+idx: number, name: string | null, output: string | null, 
+/**
+ * Whether this workspace is the visible one on its output.
+ */
+is_active: boolean, 
+/**
+ * Whether this workspace has keyboard focus.
+ */
+is_focused: boolean, is_urgent: boolean, };
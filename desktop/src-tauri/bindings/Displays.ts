@@ -0,0 +1,38 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Display = { 
+/**
+ * The connector name, like "eDP-1".
+ */
+name: string, make: string, model: string, serial: string | null, 
+/**
+ * Physical width and height in millimeters, if known.
+ */
+physical_size: [number, number] | null, modes: Array<DisplayMode>, 
+/**
+ * Index of the current mode in `modes`, or null if the output is disabled.
+ */
+current_mode: number | null, enabled: boolean, scale: number, transform: DisplayTransform, 
+/**
+ * Position of the output in the global logical coordinate space.
+ */
+position: [number, number], vrr_supported: boolean, vrr_enabled: boolean, };
+
+export type DisplayChangeStatus = { "Pending": { timeout_secs: bigint, } } | "Confirmed" | "Reverted" | "Discarded";
+
+export type DisplayMode = { width: number, height: number, 
+/**
+ * Refresh rate in millihertz.
+ */
+refresh_rate: number, is_preferred: boolean, };
+
+/**
+ * The settings to apply to a single output.
+ */
+export type DisplaySettings = { enabled: boolean, 
+/**
+ * The mode to use, or null to let niri pick automatically.
+ */
+mode: DisplayMode | null, scale: number, transform: DisplayTransform, position: [number, number], vrr: boolean, };
+
+export type DisplayTransform = "Normal" | "Rotate90" | "Rotate180" | "Rotate270" | "Flipped" | "Flipped90" | "Flipped180" | "Flipped270";
@@ -0,0 +1,36 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ActivationState } from "./NetworkManagerState";
+
+export type Hotspot = { 
+/**
+ * The state of the hotspot connection, or None if it isn't active.
+ */
+state: ActivationState | null, 
+/**
+ * The interface the hotspot is running on, while it's active.
+ */
+interface: string | null, 
+/**
+ * None until a hotspot has been created.
+ */
+ssid: string | null, password: string | null, 
+/**
+ * Devices that got an address from the hotspot, or None if NetworkManager's lease file can't be read.
+ */
+clients: Array<HotspotClient> | null, };
+
+export type HotspotClient = { mac_address: string, ip_address: string, hostname: string | null, };
+
+export type HotspotRequest = { 
+/**
+ * The D-Bus object path of the Wi-Fi device to run the hotspot on.
+ */
+device: string, 
+/**
+ * Overrides the configured or remembered network name.
+ */
+ssid: string | null, 
+/**
+ * Overrides the configured or remembered password.
+ */
+password: string | null, };
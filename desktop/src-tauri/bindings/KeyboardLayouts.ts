@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type KeyboardLayout = { 
+/**
+ * The full XKB layout name, like "English (US)".
+ */
+name: string, 
+/**
+ * A short code to show in the bar, like "EN". Unique among the configured layouts.
+ */
+short_name: string, };
+
+export type KeyboardLayoutTarget = "Next" | "Prev" | { "Index": number };
+
+export type KeyboardLayouts = { layouts: Array<KeyboardLayout>, 
+/**
+ * The index of the active layout in `layouts`.
+ */
+current_idx: number, };
@@ -0,0 +1,37 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type IpAddress = { address: string, prefix: number, };
+
+export type IpConfig = { addresses: Array<IpAddress>, gateway: string | null, dns: Array<string>, };
+
+export type NetworkDevice = { 
+/**
+ * The D-Bus object path of the device.
+ */
+path: string, 
+/**
+ * The interface name, like "wlan0" or "enp3s0".
+ */
+interface: string, device_type: NetworkDeviceType, state: NetworkDeviceState, 
+/**
+ * The MAC address, if the device has one.
+ */
+hw_address: string | null, 
+/**
+ * Only set while the device is activated.
+ */
+ipv4: IpConfig | null, 
+/**
+ * Only set while the device is activated.
+ */
+ipv6: IpConfig | null, };
+
+/**
+ * See NMDeviceState.
+ */
+export type NetworkDeviceState = "Unknown" | "Unmanaged" | "Unavailable" | "Disconnected" | "Connecting" | "NeedAuth" | "Activated" | "Deactivating" | "Failed";
+
+/**
+ * See NMDeviceType. Less common virtual device types are reported as `Other`.
+ */
+export type NetworkDeviceType = "Unknown" | "Ethernet" | "Wifi" | "Bluetooth" | "Modem" | "Bond" | "Vlan" | "Bridge" | "Tun" | "Veth" | "Wireguard" | "WifiP2p" | "Loopback" | "Other";
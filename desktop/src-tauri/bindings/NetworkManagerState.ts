@@ -1,5 +1,35 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type NetworkManagerState = { status: NetworkStatus, };
+export type ActivationState = "Unknown" | "Activating" | "Activated" | "Deactivating" | "Deactivated";
+
+export type ActiveConnectionInfo = { 
+/**
+ * The user-visible connection name.
+ */
+name: string, 
+/**
+ * The connection type, like "802-11-wireless" or "vpn".
+ */
+connection_type: string, state: ActivationState, 
+/**
+ * The network name, for Wi-Fi connections.
+ */
+ssid: string | null, 
+/**
+ * Signal strength as a percentage, for Wi-Fi connections.
+ */
+strength: number | null, };
+
+export type Connectivity = "Unknown" | "None" | "Portal" | "Limited" | "Full";
+
+export type NetworkManagerState = { status: NetworkStatus, 
+/**
+ * Whether the internet is actually reachable over the primary connection.
+ */
+connectivity: Connectivity, 
+/**
+ * The connection being activated or, if there isn't one, the primary connection.
+ */
+connection: ActiveConnectionInfo | null, };
 
 export type NetworkStatus = "WiredConnected" | "WifiConnected" | "CellularConnected" | "VpnConnected" | "WifiDisconnected" | "Offline" | "Unknown";
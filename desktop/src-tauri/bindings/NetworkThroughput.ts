@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type NetworkThroughput = { 
+/**
+ * The interface of the primary connection, or None if there isn't one.
+ */
+interface: string | null, 
+/**
+ * Recent rates, oldest first.
+ */
+history: Array<ThroughputSample>, };
+
+/**
+ * Transfer rates in bytes per second.
+ */
+export type ThroughputSample = { rx: number, tx: number, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PowerProfile = { 
+/**
+ * The profile name, like "power-saver", "balanced", or "performance".
+ */
+name: string, 
+/**
+ * The driver implementing the profile, like "platform_profile" or "amd_pstate".
+ */
+driver: string, };
+
+export type PowerProfiles = { 
+/**
+ * Whether power-profiles-daemon is running. Everything else is empty if it isn't.
+ */
+available: boolean, active: string, profiles: Array<PowerProfile>, 
+/**
+ * Why the performance profile is degraded, like "lap-detected" or "high-operating-temperature".
+ * None if it isn't degraded.
+ */
+performance_degraded: string | null, 
+/**
+ * Whether we're holding power-saver because the battery is low.
+ */
+low_battery_hold: boolean, };
@@ -0,0 +1,43 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Radio = { enabled: boolean, 
+/**
+ * False if a hardware switch blocks the radio, in which case enabling it does nothing.
+ */
+hardware_enabled: boolean, };
+
+export type RadioState = { networking_enabled: boolean, wifi: Radio, 
+/**
+ * Mobile broadband.
+ */
+wwan: Radio, 
+/**
+ * None if there's no Bluetooth adapter.
+ */
+bluetooth: Radio | null, 
+/**
+ * Whether every radio is off.
+ */
+airplane_mode: boolean, rfkill: Array<RfkillDevice>, };
+
+/**
+ * A radio the kernel can block.
+ */
+export type RfkillDevice = { 
+/**
+ * The driver's name for the radio, like "phy0" or "hci0".
+ */
+name: string, device_type: RfkillType, 
+/**
+ * Blocked in software, which we can undo.
+ */
+soft_blocked: boolean, 
+/**
+ * Blocked by a hardware switch, which only the user can undo.
+ */
+hard_blocked: boolean, };
+
+/**
+ * See `enum rfkill_type` in linux/rfkill.h.
+ */
+export type RfkillType = "Wlan" | "Bluetooth" | "Uwb" | "Wimax" | "Wwan" | "Gps" | "Fm" | "Nfc" | "Other";
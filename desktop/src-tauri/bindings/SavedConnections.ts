@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ActivationState } from "./NetworkManagerState";
+
+/**
+ * A connection profile saved in NetworkManager.
+ */
+export type SavedConnection = { uuid: string, 
+/**
+ * The user-visible connection name.
+ */
+name: string, 
+/**
+ * The connection type, like "802-11-wireless" or "vpn".
+ */
+connection_type: string, 
+/**
+ * The state of the active connection, or None if it isn't active.
+ */
+state: ActivationState | null, 
+/**
+ * Whether NetworkManager connects automatically when it's available.
+ */
+autoconnect: boolean, };
+
+/**
+ * Saved connections grouped by type, each sorted by name.
+ */
+export type SavedConnections = { vpn: Array<SavedConnection>, wireguard: Array<SavedConnection>, ethernet: Array<SavedConnection>, wifi: Array<SavedConnection>, 
+/**
+ * Everything else, like bridges or mobile broadband.
+ */
+other: Array<SavedConnection>, };
@@ -0,0 +1,45 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SecretAnswer = { id: number, 
+/**
+ * The values keyed by field, or None if the user cancelled.
+ */
+secrets: { [key in string]?: string } | null, };
+
+/**
+ * A single value to ask the user for.
+ */
+export type SecretField = { 
+/**
+ * The setting property the answer is stored under, like "psk".
+ */
+key: string, label: string, 
+/**
+ * Whether the input should be hidden, which is false for usernames.
+ */
+secret: boolean, };
+
+/**
+ * NetworkManager needs secrets to activate a connection.
+ */
+export type SecretPrompt = { 
+/**
+ * Passed back with the answer.
+ */
+id: number, 
+/**
+ * The user-visible connection name.
+ */
+connection_name: string, 
+/**
+ * The connection type, like "802-11-wireless" or "vpn".
+ */
+connection_type: string, 
+/**
+ * The network name, for Wi-Fi connections.
+ */
+ssid: string | null, 
+/**
+ * Whether the saved secrets were rejected, for example because the password was wrong.
+ */
+retry: boolean, fields: Array<SecretField>, };
@@ -1,5 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
+/**
+ * The colours symbolic icons are drawn in. The frontend sends these from the bar's stylesheet,
+ * so they match the rest of the bar. Any CSS colour works.
+ */
+export type SymbolicColors = { foreground: string, success: string, warning: string, error: string, };
+
 export type SystemTrayItem = { id: string, title: string | null, 
 /**
  * Describes the status of this item or of the associated application.
@@ -20,18 +26,68 @@ export type SystemTrayItems = { [key in string]?: SystemTrayItem };
 
 export type SystrayActivateRequest = { "MenuItem": { address: string, menu_path: string, submenu_id: number, } } | { "Primary": { address: string, x: number, y: number, } } | { "Secondary": { address: string, x: number, y: number, } };
 
-export type SystrayIcon = { "FreedesktopIcon": { theme: string, name: string, } } | { "Pixmaps": { 
+export type SystrayIcon = { "FreedesktopIcon": { name: string, 
+/**
+ * The icon's file, or None if it isn't in the item's theme path or any installed theme.
+ */
+path: string | null, } } | { "Pixmap": { 
 /**
- * The icons in this pixmap icon.
- * Applications can provide multiple icons either for animations or
- * multiple resolutions of the same icon.
+ * Where the icon is served as a PNG. Applications can provide multiple resolutions
+ * of the same icon, so append `?size=<pixels>` to get the one closest to that size.
  * See https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/Icons/
- * Note that the spec doesn't technically forbid including multiple icons _and_ animations.
  */
-icons: Array<SystrayPixmap>, } };
+url: string, } };
+
+/**
+ * The part of a tray item that changed. Only that part is sent, so an animated icon
+ * doesn't re-send every other item with it.
+ */
+export type SystrayItemPatch = { "Title": string | null } | { "Status": SystrayItemStatus } | { "Icon": SystrayIcon } | { "OverlayIcon": SystrayIcon | null } | { "AttentionIcon": SystrayIcon | null } | { "Tooltip": SystrayTooltip | null } | { "Menu": SystrayMenu | null };
 
 export type SystrayItemStatus = "Unknown" | "Passive" | "Active" | "NeedsAttention";
 
+/**
+ * A change to the tray items, keyed by the item's address.
+ */
+export type SystrayItemsChange = { "Added": { address: string, item: SystemTrayItem, } } | { "Removed": { address: string, } } | { "Updated": { address: string, patch: SystrayItemPatch, } };
+
+/**
+ * Emitted as "tray_items_changed".
+ */
+export type SystrayItemsEvent = { 
+/**
+ * One more than the version before this change. If the frontend sees a gap, it missed a change
+ * and should resync.
+ */
+version: number, change: SystrayItemsChange, 
+/**
+ * The new layout, if the change moved any items.
+ */
+layout: SystrayLayout | null, };
+
+/**
+ * All shown tray items as of a version. Also emitted as "tray_items_resynced" when the backend
+ * rebuilds the items on its own.
+ */
+export type SystrayItemsSnapshot = { version: number, 
+/**
+ * Every item that isn't hidden, with the user's rules applied.
+ */
+items: SystemTrayItems, layout: SystrayLayout, };
+
+/**
+ * The order to show items in, by address. Hidden items aren't in either list.
+ */
+export type SystrayLayout = { 
+/**
+ * Items shown on the bar.
+ */
+shown: Array<string>, 
+/**
+ * Passive items tucked away until the overflow is opened.
+ */
+overflow: Array<string>, };
+
 export type SystrayMenu = { 
 /**
  * The identifier for this menu item. Used for activation.
@@ -45,7 +101,11 @@ dbus_path: string | null, items: Array<SystrayMenuItem>, };
 /**
  * Why is this different from normal systray icons? I don't know...
  */
-export type SystrayMenuIcon = { "FreedesktopIcon": { name: string, } } | { "PNGData": Array<number> };
+export type SystrayMenuIcon = { "FreedesktopIcon": { name: string, 
+/**
+ * The icon's file, or None if it isn't in the item's theme path or any installed theme.
+ */
+path: string | null, } } | { "PNGData": Array<number> };
 
 export type SystrayMenuItem = { "Separator": { 
 /**
@@ -107,12 +167,22 @@ disposition: SystrayMenuItemDisposition, } };
 
 export type SystrayMenuItemDisposition = "Normal" | "Informative" | "Warning" | "Alert";
 
-export type SystrayPixmap = { width: number, height: number, 
 /**
- * ARGB32 binary representation of the icon in network byte order.
- * The format of icon data is described in Section Icons
+ * Identifies a menu, or a submenu within it, that the frontend opened or closed.
+ */
+export type SystrayMenuRequest = { address: string, menu_path: string, 
+/**
+ * The menu item whose submenu is shown, or 0 for the root menu.
+ */
+submenu_id: number, };
+
+export type SystrayScrollOrientation = "Vertical" | "Horizontal";
+
+export type SystrayScrollRequest = { address: string, 
+/**
+ * The number of steps scrolled. Positive values scroll down or right.
  */
-pixels: Array<number>, };
+delta: number, orientation: SystrayScrollOrientation, };
 
 /**
  * Note: The implementation does not itself handle ensuring that only one
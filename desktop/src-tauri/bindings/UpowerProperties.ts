@@ -1,7 +1,62 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
+export type BatteryLevel = "Unknown" | "None" | "Low" | "Critical" | "Normal" | "High" | "Full";
+
 export type BatteryState = "Unknown" | "Charging" | "Discharging" | "Empty" | "FullyCharged" | "PendingCharge" | "PendingDischarge";
 
+export type BatteryType = "Unknown" | "LinePower" | "Battery" | "Ups" | "Monitor" | "Mouse" | "Keyboard" | "Pda" | "Phone" | "MediaPlayer" | "Tablet" | "Computer" | "GamingInput" | "Pen" | "Touchpad" | "Modem" | "Network" | "Headset" | "Speakers" | "Headphones" | "Video" | "OtherAudio" | "RemoteControl" | "Printer" | "Scanner" | "Camera" | "Wearable" | "Toy" | "BluetoothGeneric";
+
+/**
+ * The state of the system's power source, as reported by UPower's display device.
+ */
+export type PowerSource = "Unavailable" | { "Available": UpowerProperties };
+
+/**
+ * Whether we're running on battery and the state of the laptop lid, if there is one.
+ */
+export type PowerSupplyState = { 
+/**
+ * False when plugged into AC power, or when UPower isn't running.
+ */
+on_battery: boolean, lid_is_present: boolean, lid_is_closed: boolean, };
+
+/**
+ * A single power source or peripheral known to UPower, like a laptop battery, wireless mouse, or UPS.
+ * See https://upower.freedesktop.org/docs/Device.html.
+ */
+export type UpowerDevice = { 
+/**
+ * The D-Bus object path of the device. Used to identify it in added/removed events.
+ */
+path: string, device_type: BatteryType, model: string, vendor: string, 
+/**
+ * The OS-specific native path, like "BAT0" or "hidpp_battery_0".
+ */
+native_path: string, state: BatteryState, 
+/**
+ * The amount of energy left, between 0 and 100.
+ */
+percentage: number, 
+/**
+ * Coarse battery level for devices that can't report a percentage.
+ * If this is anything other than `None`, `percentage` is only an approximation.
+ */
+battery_level: BatteryLevel, is_present: boolean, is_rechargeable: boolean, 
+/**
+ * Whether this device powers the system. False for peripherals.
+ */
+power_supply: boolean, icon_name: string, 
+/**
+ * How urgently UPower thinks the user should be warned about the remaining charge.
+ */
+warning_level: WarningLevel, };
+
+/**
+ * How urgently the user should be warned about the remaining charge, as decided by UPower.
+ * Variants are ordered by severity, so they can be compared.
+ */
+export type WarningLevel = "Unknown" | "None" | "Discharging" | "Low" | "Critical" | "Action";
+
 /**
  * See https://upower.freedesktop.org/docs/Device.html for documentation on the interface, and
  * see https://upower.freedesktop.org/docs/UPower.html for documentaation on the display device.
@@ -65,4 +120,14 @@ is_present: boolean,
  * It is recommended that front-ends use the BatteryLevel property first,
  * if available, followed by the Percentage, to present a more realistic battery level to the user. 
  */
-icon_name: string, };
+icon_name: string, 
+/**
+ * How urgently UPower thinks the user should be warned about the remaining charge.
+ */
+warning_level: WarningLevel, };
+
+/**
+ * How urgently the user should be warned about the remaining charge, as decided by UPower.
+ * Variants are ordered by severity, so they can be compared.
+ */
+export type WarningLevel = "Unknown" | "None" | "Discharging" | "Low" | "Critical" | "Action";
@@ -0,0 +1,60 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A network visible to a wireless device. Access points sharing an SSID are merged into the strongest one.
+ */
+export type WifiAccessPoint = { 
+/**
+ * The D-Bus object path of the access point, used to connect to it.
+ */
+path: string, ssid: string, bssid: string, 
+/**
+ * Signal strength as a percentage.
+ */
+strength: number, 
+/**
+ * Frequency in MHz.
+ */
+frequency: number, security: WifiSecurity, 
+/**
+ * Whether NetworkManager has a saved connection for this SSID.
+ */
+is_saved: boolean, 
+/**
+ * Whether the device is currently connected to this network.
+ */
+is_active: boolean, };
+
+export type WifiConnectRequest = { 
+/**
+ * The path of the wireless device to connect with.
+ */
+device: string, 
+/**
+ * The path of the access point to connect to.
+ */
+access_point: string, 
+/**
+ * Only used when there isn't a saved connection for the network yet.
+ */
+password: string | null, };
+
+export type WifiDevice = { 
+/**
+ * The D-Bus object path of the device.
+ */
+path: string, 
+/**
+ * The interface name, like "wlan0".
+ */
+interface: string, 
+/**
+ * The active network first, then the rest by strength.
+ */
+access_points: Array<WifiAccessPoint>, 
+/**
+ * CLOCK_BOOTTIME milliseconds of the last finished scan, or -1 if it never scanned.
+ */
+last_scan: bigint, };
+
+export type WifiSecurity = "Open" | "Owe" | "Wep" | "WpaPersonal" | "Wpa3Personal" | "WpaEnterprise";
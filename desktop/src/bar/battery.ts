@@ -1,5 +1,5 @@
 import { invokePayload } from "../utils";
-import { UpowerProperties } from "@bindings/UpowerProperties";
+import { PowerSource, UpowerProperties } from "@bindings/UpowerProperties";
 import { listen } from "@tauri-apps/api/event";
 import { createIconMask } from "../components/iconImage";
 
@@ -15,20 +15,31 @@ export function initBattery() {
     battery.appendChild(batteryText);
     
     (async () => {
-        const initialValue = await invokePayload<undefined, UpowerProperties>("plugin:bar|get_upower_properties", undefined);
-        battery.style.display = initialValue.is_present ? "flex" : "none";
+        const initialValue = await invokePayload<undefined, PowerSource>("plugin:bar|get_upower_properties", undefined);
 
-        updateBattery(initialValue);
-        batteryImage = createIconMask(initialValue.icon_name, "Adwaita", true);
+        batteryImage = createIconMask(initialValue === "Unavailable" ? "" : initialValue.Available.icon_name, "Adwaita", true);
         batteryImage.className = "gradient-image icon";
         battery.prepend(batteryImage);
+        updatePowerSource(initialValue);
     })();
 
-    listen<UpowerProperties>("upower_properties_changed", (event) => {
-        updateBattery(event.payload);
+    listen<PowerSource>("upower_properties_changed", (event) => {
+        updatePowerSource(event.payload);
     });
 }
 
+function updatePowerSource(source: PowerSource) {
+    if(!battery) return;
+
+    // Hide the widget entirely when UPower isn't running
+    if(source === "Unavailable") {
+        battery.style.display = "none";
+        return;
+    }
+
+    updateBattery(source.Available);
+}
+
 // Formats a time in seconds.
 function formatTime(time: number | bigint) {
     if(typeof time === "bigint") {
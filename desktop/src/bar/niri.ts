@@ -1,5 +1,7 @@
+import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { invokePayload } from "../utils";
+import { CompositorEvent, CompositorKind, CompositorWindow, CompositorWorkspace } from "@bindings/Compositor";
 import { Request } from "@bindings/NiriIpcRequest";
 import { Output, Response, Window, Workspace } from "@bindings/NiriIpcResponse";
 import { Event } from "@bindings/NiriIpcEvent";
@@ -36,7 +38,7 @@ function convertEvent(event: Event): TypedEvent {
     };
 }
 
-function getOutputWorkspaces(output: string) {
+function getOutputWorkspaces(output?: string | null) {
     return workspaces.filter(w => w.output === output);
 }
 function getWorkspaceWindows(workspace: number) {
@@ -74,7 +76,99 @@ document.body.addEventListener("mousemove", () => {
     });
 });
 
+function focusWindow(id: number | null) {
+    const windowIndex = windows.findIndex(w => w.id === id);
+    if(windowIndex !== -1) {
+        // Set the focused window
+        windows.forEach(w => w.is_focused = false);
+        windows[windowIndex].is_focused = true;
+
+        // Update the workspace's active window if needed
+        const workspaceIndex = workspaces.findIndex(w => w.id === windows[windowIndex].workspace_id);
+        if(workspaceIndex !== -1) {
+            workspaces[workspaceIndex].active_window_id = windows[windowIndex].id;
+        }
+
+        // If no window is focused, we're probably the one focused (although not necessarily).
+        hackyFixHoverWithNiri();
+    } else {
+        // No window is focused
+        windows.forEach(w => w.is_focused = false);
+    }
+}
+
+function openOrChangeWindow(window: Window) {
+    const existingWindowIndex = windows.findIndex(w => w.id === window.id);
+    if(existingWindowIndex !== -1) {
+        windows[existingWindowIndex] = window;
+    } else {
+        windows.push(window);
+    }
+}
+
+// The compositor-neutral types carry what we show, so they're converted to niri's to share the widgets.
+// Their IDs arrive as plain JSON numbers even though they're typed as bigints.
+
+function fromCompositorWorkspace(workspace: CompositorWorkspace): Workspace {
+    return { ...workspace, id: Number(workspace.id), active_window_id: null };
+}
+
+function fromCompositorWindow(window: CompositorWindow): Window {
+    return {
+        ...window,
+        id: Number(window.id),
+        workspace_id: window.workspace_id === null ? null : Number(window.workspace_id),
+        is_floating: false
+    };
+}
+
 export function initializeNiri() {
+    invoke<CompositorKind | null>("plugin:bar|get_compositor_kind").then((kind) => {
+        if(kind === "Niri") {
+            listenToNiri();
+        } else if(kind !== null) {
+            listenToCompositor();
+        } else {
+            console.warn("Not connected to a supported compositor; workspaces and windows won't be shown.");
+        }
+    }).catch((e) => console.error("Failed to get the compositor kind:", e));
+
+    workspacesElement = document.getElementById("niriWorkspaces") as HTMLDivElement | null;
+    windowsElement = document.getElementById("niriWindows") as HTMLDivElement | null;
+    activeWindowTitleElement = document.getElementById("activeWindowTitle") as HTMLSpanElement | null;
+}
+
+/** Follows other compositors, like sway, through the events every backend sends. */
+function listenToCompositor() {
+    listen<CompositorEvent>("compositor_event", (event) => {
+        const e = event.payload;
+        if("WorkspacesChanged" in e) {
+            workspaces = e.WorkspacesChanged.workspaces.map(fromCompositorWorkspace);
+        } else if("WindowsChanged" in e) {
+            windows = e.WindowsChanged.windows.map(fromCompositorWindow);
+        } else if("WindowOpenedOrChanged" in e) {
+            openOrChangeWindow(fromCompositorWindow(e.WindowOpenedOrChanged.window));
+        } else if("WindowClosed" in e) {
+            const id = Number(e.WindowClosed.id);
+            windows = windows.filter(w => w.id !== id);
+        } else if("WindowFocusChanged" in e) {
+            const id = e.WindowFocusChanged.id;
+            focusWindow(id === null ? null : Number(id));
+        } else {
+            // For now, we don't care about keyboard layouts
+            return;
+        }
+        updateWorkspaceWidgets();
+    });
+
+    (async () => {
+        workspaces = (await invoke<CompositorWorkspace[]>("plugin:bar|get_workspaces")).map(fromCompositorWorkspace);
+        windows = (await invoke<CompositorWindow[]>("plugin:bar|get_windows")).map(fromCompositorWindow);
+        updateWorkspaceWidgets();
+    })().catch((e) => console.error("Failed to get workspaces and windows:", e));
+}
+
+function listenToNiri() {
     listen("niri_event", (event) => {
         const e = convertEvent(event.payload as Event);
 
@@ -139,35 +233,12 @@ export function initializeNiri() {
                 break;
             }
             case "WindowFocusChanged": {
-                const windowIndex = windows.findIndex(w => w.id === e.data.id);
-                if(windowIndex !== -1) {
-                    // Set the focused window
-                    windows.forEach(w => w.is_focused = false);
-                    windows[windowIndex].is_focused = true;
-
-                    // Update the workspace's active window if needed
-                    const workspaceIndex = workspaces.findIndex(w => w.id === windows[windowIndex].workspace_id);
-                    if(workspaceIndex !== -1) {
-                        workspaces[workspaceIndex].active_window_id = windows[windowIndex].id;
-                    }
-
-                    // If no window is focused, we're probably the one focused (although not necessarily).
-                    hackyFixHoverWithNiri();
-                } else {
-                    // No window is focused
-                    windows.forEach(w => w.is_focused = false);
-                }
+                focusWindow(e.data.id ?? null);
                 updateWorkspaceWidgets();
-
                 break;
             }
             case "WindowOpenedOrChanged": {
-                const existingWindowIndex = windows.findIndex(w => w.id === e.data.window.id);
-                if(existingWindowIndex !== -1) {
-                    windows[existingWindowIndex] = e.data.window;
-                } else {
-                    windows.push(e.data.window);
-                }
+                openOrChangeWindow(e.data.window);
                 updateWorkspaceWidgets();
                 break;
             }
@@ -215,17 +286,14 @@ export function initializeNiri() {
             updateWorkspaceWidgets();
         }
     })();
-
-    workspacesElement = document.getElementById("niriWorkspaces") as HTMLDivElement | null;
-    windowsElement = document.getElementById("niriWindows") as HTMLDivElement | null;
-    activeWindowTitleElement = document.getElementById("activeWindowTitle") as HTMLSpanElement | null;
 }
 
 function updateWorkspaceWidgets() {
     if(workspacesElement === null || windowsElement === null || activeWindowTitleElement === null) return;
 
     // TODO: Update when supporting multiple monitors
-    const associatedOutput = Object.keys(outputs)[0];
+    // Outputs are only requested from niri; elsewhere, use the output of the first workspace.
+    const associatedOutput = Object.keys(outputs)[0] ?? workspaces[0]?.output;
     const outputWorkspaces = getOutputWorkspaces(associatedOutput);
     const activeWorkspace = outputWorkspaces.find(w => w.is_active);
     if(!activeWorkspace) {
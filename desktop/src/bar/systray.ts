@@ -1,36 +1,233 @@
 import { invokePayload } from "../utils";
-import { SystemTrayItems } from "@bindings/SystemTray";
+import { SymbolicColors, SystemTrayItem, SystemTrayItems, SystrayActivateRequest, SystrayItemsEvent, SystrayItemsSnapshot, SystrayLayout, SystrayScrollRequest } from "@bindings/SystemTray";
+import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { createSystrayIconElement } from "../components/systrayIcon";
+import { createSystrayIconElement, updateSystrayIconElement } from "../components/systrayIcon";
+import { closeTrayMenu, toggleTrayMenu, updateTrayMenu } from "../popups/trayMenu";
 
 let systrayItems: HTMLDivElement | null = null;
+let overflowToggle: HTMLDivElement | null = null;
+let overflowItems: HTMLDivElement | null = null;
+
+let items: SystemTrayItems = {};
+/** The order to show items in. Items in `overflow` only show while it's open. */
+let layout: SystrayLayout = { shown: [], overflow: [] };
+let overflowOpen = false;
+/** The version of `items`, or null while we're waiting for a snapshot. */
+let version: number | null = null;
+/** Changes that arrived while we were waiting for a snapshot, replayed on top of it. */
+let buffered: SystrayItemsEvent[] = [];
+/** The element for each item, by address. Kept across changes so only the changed item is redrawn. */
+const elements = new Map<string, HTMLElement>();
 
 export function initSystray() {
     systrayItems = document.getElementById("systrayItems") as HTMLDivElement | null;
     if(!systrayItems) return;
 
-    (async () => {
-        const initialItems = await invokePayload<undefined, SystemTrayItems>("plugin:bar|get_systray_items", undefined);
-        updateItems(initialItems);
-    })();
+    overflowToggle = document.createElement("div");
+    overflowToggle.className = "systray-overflow-toggle";
+    overflowToggle.addEventListener("click", () => {
+        overflowOpen = !overflowOpen;
+        updateOverflow();
+    });
+    overflowItems = document.createElement("div");
+    overflowItems.className = "systray-overflow";
 
-    listen<SystemTrayItems>("update_tray_items", (event) => {
-        updateItems(event.payload);
+    // Listen first so nothing is missed between the snapshot and the first change
+    listen<SystrayItemsEvent>("tray_items_changed", (event) => {
+        applyChange(event.payload);
+    });
+    // Sent once the tray is up, and whenever the backend had to rebuild the items itself
+    listen<SystrayItemsSnapshot>("tray_items_resynced", (event) => {
+        applySnapshot(event.payload);
     });
+
+    sendSymbolicColors(systrayItems);
+
+    // This fails if the tray isn't up yet, in which case the snapshot comes as an event
+    invoke<SystrayItemsSnapshot>("plugin:bar|get_systray_items")
+        .then(applySnapshot)
+        .catch((e) => console.warn("System tray isn't available yet:", e));
 }
 
-function updateItems(items: SystemTrayItems) {
-    if(!systrayItems) return;
+/** Symbolic icons are recoloured by the backend, so they need the theme's colours. */
+function sendSymbolicColors(element: HTMLElement) {
+    const style = getComputedStyle(element);
+    invokePayload<SymbolicColors>("plugin:bar|set_symbolic_colors", {
+        foreground: style.color,
+        success: style.getPropertyValue("--success").trim(),
+        warning: style.getPropertyValue("--warning").trim(),
+        error: style.getPropertyValue("--error").trim()
+    }).catch((e) => console.error("Failed to set the tray's symbolic icon colours:", e));
+}
+
+function applySnapshot(snapshot: SystrayItemsSnapshot) {
+    // We already have something newer
+    if(version !== null && snapshot.version < version) return;
+    items = snapshot.items;
+    layout = snapshot.layout;
+    version = snapshot.version;
+
+    for(const [address, element] of elements) {
+        element.remove();
+        updateTrayMenu(address, items[address]?.menu ?? null);
+    }
+    elements.clear();
+    for(const [address, item] of Object.entries(items)) {
+        if(item) elements.set(address, createItemElement(address, item));
+    }
+    placeItems();
+
+    // Dropping these instead would leave us a change behind, which means resyncing again,
+    // forever with items that keep changing like animated icons.
+    const newer = buffered.filter((event) => event.version > snapshot.version).sort((a, b) => a.version - b.version);
+    buffered = [];
+    for(const event of newer) applyChange(event);
+}
+
+function resync() {
+    version = null;
+    invoke<SystrayItemsSnapshot>("plugin:bar|resync_systray_items")
+        .then(applySnapshot)
+        .catch((e) => {
+            // The backend sends a snapshot itself once the tray is back
+            console.error("Failed to resync the system tray:", e);
+            buffered = [];
+        });
+}
+
+function applyChange(event: SystrayItemsEvent) {
+    if(version === null) {
+        buffered.push(event);
+        return;
+    }
+
+    const { version: newVersion, change, layout: newLayout } = event;
+    // The snapshot already includes this change
+    if(newVersion <= version) return;
+    if(newVersion !== version + 1) {
+        console.warn(`Missed tray changes between versions ${version} and ${newVersion}; resyncing.`);
+        resync();
+        return;
+    }
+    version = newVersion;
+    if(newLayout) layout = newLayout;
+
+    if("Added" in change) {
+        const { address, item } = change.Added;
+        items[address] = item;
+        const element = createItemElement(address, item);
+        // An item can be added again under the same address
+        elements.get(address)?.replaceWith(element);
+        elements.set(address, element);
+    } else if("Removed" in change) {
+        const { address } = change.Removed;
+        delete items[address];
+        closeTrayMenu(address);
+        elements.get(address)?.remove();
+        elements.delete(address);
+    } else {
+        const { address, patch } = change.Updated;
+        const item = items[address];
+        const element = elements.get(address);
+        if(!item || !element) {
+            resync();
+            return;
+        }
+        if("Title" in patch) item.title = patch.Title;
+        else if("Status" in patch) item.status = patch.Status;
+        else if("Icon" in patch) item.icon = patch.Icon;
+        else if("OverlayIcon" in patch) item.overlay_icon = patch.OverlayIcon;
+        else if("AttentionIcon" in patch) item.attention_icon = patch.AttentionIcon;
+        else if("Tooltip" in patch) item.tooltip = patch.Tooltip;
+        else if("Menu" in patch) {
+            item.menu = patch.Menu;
+            updateTrayMenu(address, item.menu);
+        }
+        updateItemElement(element, item);
+    }
 
-    // console.log(items);
+    // Only a new layout moves items. A new item always comes with one, unless it replaced an old one in place.
+    if(newLayout || [...elements.values()].some((element) => !element.isConnected)) placeItems();
+}
+
+function tooltipText(item: SystemTrayItem): string {
+    return item.tooltip?.title ?? item.tooltip?.description ?? item.title ?? "";
+}
 
-    systrayItems.innerHTML = "";
-    
-    for(const item of Object.values(items)) {
-        if(!item) continue;
-        const element = createSystrayIconElement(item.icon);
-        if("FreedesktopIcon" in item.icon) console.log(item.icon.FreedesktopIcon);
-        element.title = item.tooltip?.title ?? item.tooltip?.description ?? item.title ?? "";
-        systrayItems.append(element);
+function createItemElement(address: string, item: SystemTrayItem): HTMLElement {
+    const element = createSystrayIconElement(item.icon);
+    element.title = tooltipText(item);
+    addItemListeners(element, address);
+    return element;
+}
+
+function updateItemElement(element: HTMLElement, item: SystemTrayItem) {
+    element.title = tooltipText(item);
+    updateSystrayIconElement(element, item.icon);
+}
+
+/**
+ * Moves the children of `parent` into the given order, leaving the ones already in place alone
+ * so their icons aren't reloaded, and removes any others.
+ */
+function placeChildren(parent: HTMLElement, children: HTMLElement[]) {
+    children.forEach((child, index) => {
+        const current = parent.children[index] ?? null;
+        if(current !== child) parent.insertBefore(child, current);
+    });
+    while(parent.children.length > children.length) {
+        parent.lastElementChild?.remove();
     }
+}
+
+function layoutElements(addresses: string[]): HTMLElement[] {
+    return addresses.flatMap((address) => elements.get(address) ?? []);
+}
+
+/** Puts the items in the order of the layout. The backend already left out hidden items. */
+function placeItems() {
+    if(!systrayItems || !overflowToggle || !overflowItems) return;
+
+    placeChildren(systrayItems, [...layoutElements(layout.shown), overflowToggle, overflowItems]);
+    placeChildren(overflowItems, layoutElements(layout.overflow));
+    updateOverflow();
+}
+
+function updateOverflow() {
+    if(!overflowToggle || !overflowItems) return;
+
+    const count = layout.overflow.length;
+    overflowToggle.style.display = count === 0 ? "none" : "";
+    overflowToggle.classList.toggle("open", overflowOpen);
+    overflowToggle.title = overflowOpen ? "Hide inactive items" : `${count} inactive items`;
+    overflowItems.style.display = count === 0 || !overflowOpen ? "none" : "";
+}
+
+function addItemListeners(element: HTMLElement, address: string) {
+    element.addEventListener("click", (event) => {
+        invokePayload<SystrayActivateRequest>("plugin:bar|activate_tray_item", {
+            Primary: { address, x: Math.round(event.screenX), y: Math.round(event.screenY) }
+        }).catch((e) => console.error(`Failed to activate tray item ${address}:`, e));
+    });
+    element.addEventListener("contextmenu", (event) => {
+        event.preventDefault();
+        toggleTrayMenu(address, items[address]?.menu ?? null, element);
+    });
+    element.addEventListener("auxclick", (event) => {
+        if(event.button !== 1) return;
+        invokePayload<SystrayActivateRequest>("plugin:bar|activate_tray_item", {
+            Secondary: { address, x: Math.round(event.screenX), y: Math.round(event.screenY) }
+        }).catch((e) => console.error(`Failed to activate tray item ${address}:`, e));
+    });
+    element.addEventListener("wheel", (event) => {
+        const horizontal = Math.abs(event.deltaX) > Math.abs(event.deltaY);
+        const delta = Math.sign(horizontal ? event.deltaX : event.deltaY);
+        if(delta === 0) return;
+        invokePayload<SystrayScrollRequest>("plugin:bar|scroll_tray_item", {
+            address,
+            delta,
+            orientation: horizontal ? "Horizontal" : "Vertical"
+        }).catch((e) => console.error(`Failed to scroll tray item ${address}:`, e));
+    }, { passive: true });
 }
\ No newline at end of file
@@ -43,33 +43,54 @@ function getIcon(status: NetworkStatus) {
     }
 }
 
-function updateStatus(state: NetworkManagerState) {
-    if(!networkStatus) return;
+function getTitle(state: NetworkManagerState) {
+    const connection = state.connection;
+    if(connection?.state === "Activating") {
+        return `Connecting to ${connection.ssid ?? connection.name}…`;
+    }
+    if(connection?.state === "Deactivating") {
+        return `Disconnecting from ${connection.ssid ?? connection.name}…`;
+    }
+
+    let title: string;
     switch(state.status) {
         case "Offline":
-            networkStatus.title = "Offline";
-            break;
+            return "Offline";
         case "CellularConnected":
-            networkStatus.title = "Cellular connected";
+            title = "Cellular connected";
             break;
         case "Unknown":
-            networkStatus.title = "Network status unknown";
-            break;
+            return "Network status unknown";
         case "VpnConnected":
-            networkStatus.title = "VPN connected";
+            title = "VPN connected";
             break;
         case "WifiConnected":
-            networkStatus.title = "Wi-Fi connected";
+            title = "Wi-Fi connected";
             break;
         case "WifiDisconnected":
-            networkStatus.title = "Wi-Fi disconnected";
-            break;
+            return "Wi-Fi disconnected";
         case "WiredConnected":
-            networkStatus.title = "Wired connected";
+            title = "Wired connected";
             break;
     }
 
+    if(connection) {
+        title += ` to ${connection.ssid ?? connection.name}`;
+        if(connection.strength !== null) title += ` (${connection.strength}%)`;
+    }
+
+    switch(state.connectivity) {
+        case "Portal": title += "\nSign-in required"; break;
+        case "Limited": title += "\nNo internet access"; break;
+    }
+    return title;
+}
+
+function updateStatus(state: NetworkManagerState) {
+    if(!networkStatus) return;
+    networkStatus.title = getTitle(state);
+
     if(networkStatusIcon) {
         networkStatusIcon.dataset.icon = getIcon(state.status);
     }
-}
\ No newline at end of file
+}
@@ -59,7 +59,7 @@ export function createIconImage(
     return element;
 }
 
-function getDefaultIcon(): string {
+export function getDefaultIcon(): string {
     return `data:image/svg+xml;utf8,<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32" viewBox="0 0 32 32"><circle cx="16" cy="16" r="16" fill="%23ccc"/><text x="16" y="22" font-size="18" text-anchor="middle" fill="%23666" font-family="sans-serif" font-weight="bold">?</text></svg>`;
 }
 
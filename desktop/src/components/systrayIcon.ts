@@ -1,40 +1,34 @@
-import { createIconMask } from "./iconImage";
+import { convertFileSrc } from "@tauri-apps/api/core";
+import { getDefaultIcon } from "./iconImage";
 import type { SystrayIcon } from "@bindings/SystemTray";
 
-export function createSystrayIconElement(icon: SystrayIcon): HTMLElement {
-    let gradientIcon;
+/** The size tray icons are drawn at, in CSS pixels. */
+const ICON_SIZE = 24;
+
+function iconImage(icon: SystrayIcon): string {
     if("FreedesktopIcon" in icon) {
-        const { theme, name } = icon.FreedesktopIcon;
-        gradientIcon = createIconMask(name, theme, false);
-    } else if("Pixmaps" in icon) {
-        // Just use the first pixmap as the icon image for now
-        // TODO: better selection logic
-        const pixmap = icon.Pixmaps.icons[0];
-        
-        gradientIcon = document.createElement("div");
-
-        const canvas = document.createElement("canvas");
-        canvas.width = pixmap.width;
-        canvas.height = pixmap.height;
-        const ctx = canvas.getContext("2d");
-        if(ctx) {
-            // Convert ARGB32 (network byte order) to RGBA for canvas
-            const pixelData = new Uint8ClampedArray(pixmap.width * pixmap.height * 4);
-            for(let i = 0; i < pixelData.length; i += 4) {
-                // ARGB32 (network order): [A, R, G, B]
-                pixelData[i]     = pixmap.pixels[i + 1]; // R
-                pixelData[i + 1] = pixmap.pixels[i + 2]; // G
-                pixelData[i + 2] = pixmap.pixels[i + 3]; // B
-                pixelData[i + 3] = pixmap.pixels[i];     // A
-            }
-            const imageData = ctx.createImageData(pixmap.width, pixmap.height);
-            imageData.data.set(pixelData);
-            ctx.putImageData(imageData, 0, 0);
-            gradientIcon.style.maskImage = `url(${canvas.toDataURL()})`;
-        }
+        // The backend already found the file, or couldn't
+        const { path } = icon.FreedesktopIcon;
+        const src = path ? convertFileSrc(path) : getDefaultIcon();
+        return `url('${src}')`;
+    } else if("Pixmap" in icon) {
+        // The backend picks the closest of the item's sizes and serves it as a PNG
+        const size = Math.round(ICON_SIZE * (window.devicePixelRatio || 1));
+        return `url("${icon.Pixmap.url}?size=${size}")`;
     } else {
         throw new Error("Unknown SystrayIcon variant");
     }
+}
+
+/** Swaps the icon of an element from `createSystrayIconElement`, like for each frame of an animated icon. */
+export function updateSystrayIconElement(element: HTMLElement, icon: SystrayIcon) {
+    const gradientIcon = element.querySelector<HTMLElement>(".gradient-image");
+    if(gradientIcon) gradientIcon.style.maskImage = iconImage(icon);
+}
+
+export function createSystrayIconElement(icon: SystrayIcon): HTMLElement {
+    const gradientIcon = document.createElement("div");
+    gradientIcon.style.maskImage = iconImage(icon);
 
     gradientIcon.className = "icon gradient-image";
     
@@ -2,6 +2,9 @@ import { invoke } from "@tauri-apps/api/core";
 import { emit, listen } from "@tauri-apps/api/event";
 import { ExclusiveRegions } from "@bindings/ExclusiveRegions";
 import { Notification } from "@bindings/Notification";
+import { KeyboardLayoutTarget } from "@bindings/KeyboardLayouts";
+import { PowerProfiles } from "@bindings/PowerProfiles";
+import { RadioState } from "@bindings/RadioState";
 import { init } from "./rendering";
 import { initClock } from "./bar/clock";
 import { invokePayload, debugLog } from "./utils";
@@ -10,6 +13,7 @@ import { updateInputShape } from "./popups/popups";
 import { initLauncher, openLauncher } from "./popups/launcher";
 import { exit, relaunch } from "@tauri-apps/plugin-process";
 import { initNotifications } from "./popups/notifications";
+import { initSecretPrompts } from "./popups/secretPrompt";
 import { initBattery } from "./bar/battery";
 import { initPowerOptions } from "./bar/powerOptions";
 import { initNetworkStatus } from "./bar/wirelessStatus";
@@ -45,6 +49,7 @@ window.addEventListener("DOMContentLoaded", () => {
     initBattery();
     initLauncher();
     initNotifications();
+    initSecretPrompts();
     initNiri();
 
     invokePayload<ExclusiveRegions>("create_exclusive_regions", {
@@ -112,6 +117,52 @@ listen<string>("ipc_call", async (event) => {
             })
             response = "Reloaded.";
             break;
+        case "keyboard_layout_next":
+            await invokePayload<KeyboardLayoutTarget>("plugin:bar|switch_keyboard_layout", "Next");
+            response = "ok";
+            break;
+        case "keyboard_layout_prev":
+            await invokePayload<KeyboardLayoutTarget>("plugin:bar|switch_keyboard_layout", "Prev");
+            response = "ok";
+            break;
+        case "power_profile_power_saver":
+        case "power_profile_balanced":
+        case "power_profile_performance":
+            await invokePayload<string>("plugin:bar|set_power_profile", event.payload.slice("power_profile_".length).replace("_", "-"));
+            response = "ok";
+            break;
+        case "power_profile_next": {
+            const profiles = await invoke<PowerProfiles>("plugin:bar|get_power_profiles");
+            const names = profiles.profiles.map((profile) => profile.name);
+            const next = names[(names.indexOf(profiles.active) + 1) % names.length];
+            if(next) await invokePayload<string>("plugin:bar|set_power_profile", next);
+            response = next ?? "Power profiles aren't available.";
+            break;
+        }
+        case "wifi_toggle": {
+            const radios = await invoke<RadioState>("plugin:bar|get_radio_state");
+            await invokePayload<boolean>("plugin:bar|set_wireless_enabled", !radios.wifi.enabled);
+            response = radios.wifi.enabled ? "Wi-Fi off." : "Wi-Fi on.";
+            break;
+        }
+        case "wwan_toggle": {
+            const radios = await invoke<RadioState>("plugin:bar|get_radio_state");
+            await invokePayload<boolean>("plugin:bar|set_wwan_enabled", !radios.wwan.enabled);
+            response = radios.wwan.enabled ? "Mobile broadband off." : "Mobile broadband on.";
+            break;
+        }
+        case "networking_toggle": {
+            const radios = await invoke<RadioState>("plugin:bar|get_radio_state");
+            await invokePayload<boolean>("plugin:bar|set_networking_enabled", !radios.networking_enabled);
+            response = radios.networking_enabled ? "Networking off." : "Networking on.";
+            break;
+        }
+        case "airplane_mode_toggle": {
+            const radios = await invoke<RadioState>("plugin:bar|get_radio_state");
+            await invokePayload<boolean>("plugin:bar|set_airplane_mode", !radios.airplane_mode);
+            response = radios.airplane_mode ? "Airplane mode off." : "Airplane mode on.";
+            break;
+        }
         case "exit":
             setTimeout(() => {
                 exit();
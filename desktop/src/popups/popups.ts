@@ -17,6 +17,7 @@ export enum PopupType {
     Launcher,
     LeftPanel,
     Notifications,
+    SecretPrompt,
 }
 
 enum PopupOrigin {
@@ -35,6 +36,7 @@ const POPUP_DATA: {
     [PopupType.Launcher]: { origin: PopupOrigin.Top, requiresExclusiveKeyboard: true },
     [PopupType.LeftPanel]: { origin: PopupOrigin.Left, requiresExclusiveKeyboard: false },
     [PopupType.Notifications]: { origin: PopupOrigin.Top, requiresExclusiveKeyboard: false },
+    [PopupType.SecretPrompt]: { origin: PopupOrigin.Left, requiresExclusiveKeyboard: true },
 };
 
 class OpenPopup {
@@ -45,7 +47,9 @@ class OpenPopup {
         private type: PopupType,
         public source: string,
         public element: HTMLDivElement,
-        public rect: Rectangle | null
+        public rect: Rectangle | null,
+        /** Called once when the popup closes or another source takes it over. */
+        public onClose: (() => void) | null = null
     ) {
         this.resizeObserver = new ResizeObserver(() => {
             if(this.open) {
@@ -67,6 +71,7 @@ class OpenPopup {
         if(!this.open) return;
 
         this.open = false;
+        this.closed();
 
         const popupsRect = popupsContainer.getBoundingClientRect();
         const popupRect = this.element.getBoundingClientRect();
@@ -104,6 +109,12 @@ class OpenPopup {
         updateKeyboardExclusivity();
         pollForChanges();
     }
+
+    closed() {
+        const onClose = this.onClose;
+        this.onClose = null;
+        onClose?.();
+    }
 }
 
 const popups: Map<PopupType, OpenPopup> = new Map();
@@ -126,7 +137,8 @@ export function openPopup(
     type: PopupType,
     source: string,
     content: HTMLElement,
-    anchorElement: HTMLElement
+    anchorElement: HTMLElement,
+    onClose?: () => void
 ) {
     if (!popupsContainer) return;
     
@@ -142,8 +154,10 @@ export function openPopup(
             return;
         }
         
+        if(existingPopup.open) existingPopup.closed();
         existingPopup.open = true;
         existingPopup.source = source;
+        existingPopup.onClose = onClose ?? null;
         try {
             existingPopup.element.removeChild(existingPopup.element.children[0]);
             existingPopup.element.appendChild(content);
@@ -154,7 +168,7 @@ export function openPopup(
     } else {
         element = document.createElement("div");
         element.appendChild(content);
-        popups.set(type, new OpenPopup(type, source, element, null));
+        popups.set(type, new OpenPopup(type, source, element, null, onClose));
     }
     
     popupsContainer.appendChild(element);
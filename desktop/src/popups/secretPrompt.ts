@@ -0,0 +1,113 @@
+import { listen } from "@tauri-apps/api/event";
+import type { SecretAnswer, SecretPrompt } from "@bindings/SecretAgent";
+import { invokePayload } from "../utils";
+import { closePopup, openPopup, PopupType } from "./popups";
+
+const POPUP_SOURCE = "secret-prompt";
+
+/** Prompts waiting to be shown. NetworkManager can ask for several at once, like for a VPN over Wi-Fi. */
+const queue: SecretPrompt[] = [];
+let shown: SecretPrompt | null = null;
+
+function answer(prompt: SecretPrompt, secrets: SecretAnswer["secrets"]) {
+    invokePayload<SecretAnswer>("plugin:bar|answer_secret_prompt", { id: prompt.id, secrets })
+        .catch((e) => console.warn(`Failed to answer secret prompt ${prompt.id}:`, e));
+}
+
+function promptTitle(prompt: SecretPrompt): string {
+    const name = prompt.ssid ?? prompt.connection_name;
+    if(prompt.retry) return `Wrong password for ${name}`;
+    return prompt.connection_type === "vpn" ? `Sign in to ${name}` : `Connect to ${name}`;
+}
+
+function createPrompt(prompt: SecretPrompt): HTMLDivElement {
+    const content = document.createElement("div");
+    content.className = "secret-prompt";
+
+    const title = document.createElement("span");
+    title.className = "title";
+    title.textContent = promptTitle(prompt);
+
+    const form = document.createElement("form");
+    const inputs = prompt.fields.map((field) => {
+        const label = document.createElement("label");
+        label.textContent = field.label;
+
+        const input = document.createElement("input");
+        input.type = field.secret ? "password" : "text";
+        input.name = field.key;
+        input.autocomplete = "off";
+        label.append(input);
+        form.append(label);
+        return input;
+    });
+
+    const cancel = document.createElement("button");
+    cancel.type = "button";
+    cancel.textContent = "Cancel";
+    cancel.addEventListener("click", () => closePopup(PopupType.SecretPrompt, POPUP_SOURCE));
+
+    const connect = document.createElement("button");
+    connect.type = "submit";
+    connect.className = "primary";
+    connect.textContent = "Connect";
+
+    const buttons = document.createElement("div");
+    buttons.className = "buttons";
+    buttons.append(cancel, connect);
+    form.append(buttons);
+
+    form.addEventListener("submit", (event) => {
+        event.preventDefault();
+        if(shown !== prompt) return;
+
+        shown = null;
+        answer(prompt, Object.fromEntries(inputs.map((input) => [input.name, input.value])));
+        closePopup(PopupType.SecretPrompt, POPUP_SOURCE);
+    });
+    form.addEventListener("keydown", (event) => {
+        if(event.key === "Escape") closePopup(PopupType.SecretPrompt, POPUP_SOURCE);
+    });
+
+    content.append(title, form);
+    requestAnimationFrame(() => inputs[0]?.focus());
+    return content;
+}
+
+/** Shows the next queued prompt if none is shown. */
+function showNext() {
+    if(shown) return;
+    const anchor = document.getElementById("networkStatus");
+    const prompt = queue.shift();
+    if(!prompt || !anchor) return;
+
+    shown = prompt;
+    openPopup(PopupType.SecretPrompt, POPUP_SOURCE, createPrompt(prompt), anchor, () => {
+        // Closing the prompt any other way than submitting it cancels it
+        if(shown === prompt) {
+            shown = null;
+            answer(prompt, null);
+        }
+        // Let the closing animation finish before opening the next one
+        setTimeout(showNext, 500);
+    });
+}
+
+export function initSecretPrompts() {
+    listen<SecretPrompt>("secret_prompt", (event) => {
+        queue.push(event.payload);
+        showNext();
+    });
+
+    // NetworkManager gave up on the request, so there's nothing to answer
+    listen<number>("secret_prompt_cancelled", (event) => {
+        const id = event.payload;
+        const queued = queue.findIndex((prompt) => prompt.id === id);
+        if(queued !== -1) queue.splice(queued, 1);
+
+        if(shown?.id === id) {
+            shown = null;
+            closePopup(PopupType.SecretPrompt, POPUP_SOURCE);
+        }
+    });
+}
@@ -0,0 +1,169 @@
+import { convertFileSrc } from "@tauri-apps/api/core";
+import type { SystrayActivateRequest, SystrayMenu, SystrayMenuIcon, SystrayMenuItem, SystrayMenuRequest } from "@bindings/SystemTray";
+import { invokePayload } from "../utils";
+import { closePopup, openPopup, PopupType } from "./popups";
+
+type OpenMenu = {
+    address: string,
+    menuPath: string,
+    menu: SystrayMenu,
+    content: HTMLDivElement,
+    /** The submenus that are shown, by the id of the item they belong to. */
+    expanded: Set<number>,
+};
+
+let openMenu: OpenMenu | null = null;
+
+function popupSource(address: string): string {
+    return `systray:${address}`;
+}
+
+/** Tells the application which menu is shown, so it can fill it in. */
+function sendMenuEvent(command: "tray_menu_opened" | "tray_menu_closed", menu: OpenMenu, submenuId: number) {
+    invokePayload<SystrayMenuRequest>(`plugin:bar|${command}`, {
+        address: menu.address,
+        menu_path: menu.menuPath,
+        submenu_id: submenuId
+    }).catch((e) => console.warn(`Failed to send ${command} to tray item ${menu.address}:`, e));
+}
+
+/** "__" is shown as an underscore, and any other underscore marks the access key, which we don't show. */
+function menuLabel(label: string | null): string {
+    return (label ?? "").replace(/__|_/g, (match) => match === "__" ? "_" : "");
+}
+
+function menuIconSource(icon: SystrayMenuIcon): string | null {
+    if("FreedesktopIcon" in icon) {
+        const { path } = icon.FreedesktopIcon;
+        return path ? convertFileSrc(path) : null;
+    }
+    const binary = icon.PNGData.reduce((data, byte) => data + String.fromCharCode(byte), "");
+    return `data:image/png;base64,${btoa(binary)}`;
+}
+
+function activateMenuItem(menu: OpenMenu, id: number) {
+    invokePayload<SystrayActivateRequest>("plugin:bar|activate_tray_item", {
+        MenuItem: { address: menu.address, menu_path: menu.menuPath, submenu_id: id }
+    }).catch((e) => console.error(`Failed to activate menu item ${id} of tray item ${menu.address}:`, e));
+    closePopup(PopupType.LeftPanel, popupSource(menu.address));
+}
+
+function toggleSubmenu(menu: OpenMenu, id: number) {
+    if(menu.expanded.delete(id)) {
+        sendMenuEvent("tray_menu_closed", menu, id);
+    } else {
+        menu.expanded.add(id);
+        sendMenuEvent("tray_menu_opened", menu, id);
+    }
+    renderMenu(menu);
+}
+
+function createMenuItems(menu: OpenMenu, menuItems: SystrayMenuItem[]): HTMLElement[] {
+    const elements: HTMLElement[] = [];
+    for(const menuItem of menuItems) {
+        if("Separator" in menuItem) {
+            if(!menuItem.Separator.visible) continue;
+            const separator = document.createElement("div");
+            separator.className = "systray-menu-separator";
+            elements.push(separator);
+            continue;
+        }
+
+        const { id, label, enabled, visible, icon, toggle_info, submenu } = menuItem.Item;
+        if(!visible) continue;
+
+        const row = document.createElement("div");
+        row.className = "systray-menu-item";
+        row.classList.toggle("disabled", !enabled);
+
+        const toggle = document.createElement("span");
+        toggle.className = "systray-menu-toggle";
+        if(typeof toggle_info === "object") {
+            const radio = "Radio" in toggle_info;
+            toggle.classList.add(radio ? "radio" : "checkmark");
+            toggle.classList.toggle("on", radio ? toggle_info.Radio : toggle_info.Checkmark);
+        }
+        row.append(toggle);
+
+        const iconSource = icon ? menuIconSource(icon) : null;
+        if(iconSource) {
+            const image = document.createElement("img");
+            image.className = "systray-menu-icon";
+            image.src = iconSource;
+            row.append(image);
+        }
+
+        const text = document.createElement("span");
+        text.className = "systray-menu-label";
+        text.textContent = menuLabel(label);
+        row.append(text);
+        elements.push(row);
+
+        if(submenu) {
+            const expanded = menu.expanded.has(id);
+            row.classList.add("has-submenu");
+            row.classList.toggle("expanded", expanded);
+            row.addEventListener("click", () => {
+                if(enabled) toggleSubmenu(menu, id);
+            });
+
+            if(expanded) {
+                const children = document.createElement("div");
+                children.className = "systray-submenu";
+                children.append(...createMenuItems(menu, submenu));
+                elements.push(children);
+            }
+        } else {
+            row.addEventListener("click", () => {
+                if(enabled) activateMenuItem(menu, id);
+            });
+        }
+    }
+    return elements;
+}
+
+function renderMenu(menu: OpenMenu) {
+    menu.content.replaceChildren(...createMenuItems(menu, menu.menu.items));
+}
+
+/** Opens an item's menu next to its icon, or closes it if it's already open. Does nothing if the item has no menu. */
+export function toggleTrayMenu(address: string, menu: SystrayMenu | null, anchor: HTMLElement) {
+    if(!menu?.dbus_path) return;
+
+    if(openMenu?.address === address) {
+        closePopup(PopupType.LeftPanel, popupSource(address));
+        return;
+    }
+
+    const content = document.createElement("div");
+    content.className = "systray-menu";
+    const opened: OpenMenu = { address, menuPath: menu.dbus_path, menu, content, expanded: new Set() };
+
+    // Some applications only fill in their menu once they're told it's shown.
+    // The new layout arrives as an update to the item, which redraws the menu.
+    sendMenuEvent("tray_menu_opened", opened, 0);
+    renderMenu(opened);
+
+    openPopup(PopupType.LeftPanel, popupSource(address), content, anchor, () => {
+        for(const id of opened.expanded) sendMenuEvent("tray_menu_closed", opened, id);
+        sendMenuEvent("tray_menu_closed", opened, 0);
+        if(openMenu === opened) openMenu = null;
+    });
+    openMenu = opened;
+}
+
+/** Redraws the open menu if it belongs to this item, or closes it if the item no longer has one. */
+export function updateTrayMenu(address: string, menu: SystrayMenu | null) {
+    if(openMenu?.address !== address) return;
+
+    if(!menu?.dbus_path) {
+        closePopup(PopupType.LeftPanel, popupSource(address));
+        return;
+    }
+    openMenu.menu = menu;
+    renderMenu(openMenu);
+}
+
+export function closeTrayMenu(address: string) {
+    if(openMenu?.address === address) closePopup(PopupType.LeftPanel, popupSource(address));
+}